@@ -0,0 +1,40 @@
+/* Checks every level in a folder, `data/levels` if no folder is given, see `validate`.
+   Exits with 1 if any level has errors or can't be loaded.
+*/
+
+use game::{validate_folder, Severity};
+
+fn main() {
+    let folder = std::env::args().nth(1).unwrap_or_else(|| "data/levels".into());
+    let reports = match validate_folder(&folder) {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("can't read {}: {}", folder, e);
+            std::process::exit(1);
+        },
+    };
+
+    let mut failed = false;
+    for (file, issues) in reports {
+        match issues {
+            Ok(issues) => {
+                if issues.is_empty() {
+                    println!("{}: ok", file);
+                } else {
+                    println!("{}:", file);
+                }
+                for issue in &issues {
+                    println!("    {}", issue);
+                }
+                failed |= issues.iter().any(|issue| issue.severity == Severity::Error);
+            },
+            Err(e) => {
+                println!("{}: can't load: {}", file, e);
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::geom::{
+    vector::prelude::*,
+    aabb::AABB,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Zoom levels camera steps through with `zoom_in`/`zoom_out`.
+///
+/// Tile size times any of them is a whole number of pixels,
+/// so tiles don't get gaps between them
+pub const ZOOM_LEVELS: [f32; 7] = [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0];
+const DEFAULT_ZOOM_LEVEL: usize = 2;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Follow {
+    /// Camera is always exactly at the target
+    Snap,
+    /// Exponential smoothing, `speed` is roughly how many times per second
+    /// the remaining distance shrinks by e
+    Lerp { speed: f32 },
+    /// Damped spring, overshoots if damping is low
+    Spring { stiffness: f32, damping: f32 },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Shake {
+    /// Offset in world units at full trauma
+    pub max_offset: V2f,
+    /// Trauma lost per second
+    pub decay: f32,
+    pub frequency: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    /// Center of the view in world units
+    pub pos: V2f,
+
+    pub follow: Follow,
+    /// Half-size of the area around camera center where target can move
+    /// without camera following it
+    pub deadzone: V2f,
+    /// How far ahead of the target (in facing direction) camera looks
+    pub look_ahead: f32,
+    /// Camera never shows anything outside of these
+    pub bounds: Option<AABB<f32>>,
+    pub shake: Shake,
+
+    zoom_level: usize,
+    vel: V2f,
+    look_ahead_offset: f32,
+    trauma: f32,
+    time: f32,
+    view_size: V2f,
+}
+
+impl Camera {
+    pub fn new(pos: V2f) -> Self {
+        Self {
+            pos,
+            follow: Follow::Snap,
+            deadzone: V2::diag(0.0),
+            look_ahead: 0.0,
+            bounds: None,
+            shake: Shake {
+                max_offset: V2::new(0.5, 0.5),
+                decay: 1.5,
+                frequency: 25.0,
+            },
+
+            zoom_level: DEFAULT_ZOOM_LEVEL,
+            vel: V2::diag(0.0),
+            look_ahead_offset: 0.0,
+            trauma: 0.0,
+            time: 0.0,
+            view_size: V2::diag(0.0),
+        }
+    }
+
+    pub fn zoom(&self) -> f32 { ZOOM_LEVELS[self.zoom_level] }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom_level = std::cmp::min(self.zoom_level + 1, ZOOM_LEVELS.len() - 1);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom_level = self.zoom_level.saturating_sub(1);
+    }
+
+    pub fn reset_zoom(&mut self) {
+        self.zoom_level = DEFAULT_ZOOM_LEVEL;
+    }
+
+    /// Zooms in or out by one level, `target` stays at the same place on the screen
+    pub fn zoom_around(&mut self, target: V2f, zoom_in: bool) {
+        let before = self.zoom();
+        if zoom_in {
+            self.zoom_in();
+        } else {
+            self.zoom_out();
+        }
+        self.pos = target + (self.pos - target) * (before / self.zoom());
+    }
+
+    /// Centers on `rect` at the biggest zoom level that shows all of it.
+    ///
+    /// `view_size` is the size of the screen in world units at zoom 1.0
+    pub fn fit(&mut self, rect: AABB<f32>, view_size: V2f) {
+        let size = rect.max - rect.min;
+        self.zoom_level = (0..ZOOM_LEVELS.len()).rev()
+            .find(|&i| size.x * ZOOM_LEVELS[i] <= view_size.x && size.y * ZOOM_LEVELS[i] <= view_size.y)
+            .unwrap_or(0);
+        self.pos = (rect.min + rect.max) * 0.5;
+    }
+
+    /// Trauma is clamped to 0..=1, shake strength is trauma squared
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = utils::clamp(self.trauma + amount, 0.0, 1.0);
+    }
+
+    /// Must be called once a frame before `follow`.
+    ///
+    /// `view_size` is the size of the screen in world units at zoom 1.0
+    pub fn update(&mut self, view_size: V2f, dt: f32) {
+        self.view_size = view_size / self.zoom();
+        self.time += dt;
+        self.trauma = (self.trauma - self.shake.decay * dt).max(0.0);
+    }
+
+    /// Moves camera towards `target`.
+    ///
+    /// `facing` is -1.0 for left, 1.0 for right, and 0.0 for no look-ahead
+    pub fn follow(&mut self, target: V2f, facing: f32, dt: f32) {
+        // look-ahead changes direction smoothly, otherwise turning around jerks the camera
+        let look_ahead_target = facing * self.look_ahead;
+        self.look_ahead_offset += (look_ahead_target - self.look_ahead_offset) * smoothing(4.0, dt);
+        let target = target + V2::new(self.look_ahead_offset, 0.0);
+
+        let desired = V2 {
+            x: deadzone_axis(self.pos.x, target.x, self.deadzone.x),
+            y: deadzone_axis(self.pos.y, target.y, self.deadzone.y),
+        };
+
+        match self.follow {
+            Follow::Snap => {
+                self.pos = desired;
+                self.vel = V2::diag(0.0);
+            },
+            Follow::Lerp { speed } => {
+                self.pos += (desired - self.pos) * smoothing(speed, dt);
+                self.vel = V2::diag(0.0);
+            },
+            Follow::Spring { stiffness, damping } => {
+                let acc = (desired - self.pos) * stiffness - self.vel * damping;
+                self.vel += acc * dt;
+                self.pos += self.vel * dt;
+            },
+        }
+
+        let clamped = self.clamp_to_bounds(self.pos);
+        if clamped.x != self.pos.x {
+            self.vel.x = 0.0;
+        }
+        if clamped.y != self.pos.y {
+            self.vel.y = 0.0;
+        }
+        self.pos = clamped;
+    }
+
+    /// Visible world area (bottom left to top right) including shake
+    pub fn view(&self) -> AABB<f32> {
+        let center = self.clamp_to_bounds(self.pos) + self.shake_offset();
+        let half = self.view_size * 0.5;
+        AABB {
+            min: center - half,
+            max: center + half,
+        }
+    }
+
+    fn shake_offset(&self) -> V2f {
+        let strength = self.trauma * self.trauma;
+        if strength == 0.0 {
+            return V2::diag(0.0);
+        }
+
+        // sum of sines with unrelated frequencies is random enough for a shake
+        let t = self.time * self.shake.frequency;
+        let noise = |seed: f32| ((t + seed).sin() + (t * 1.73 + seed * 2.9).sin()) * 0.5;
+        V2 {
+            x: self.shake.max_offset.x * strength * noise(0.0),
+            y: self.shake.max_offset.y * strength * noise(17.0),
+        }
+    }
+
+    /// If bounds are smaller than the view camera is centered on them
+    fn clamp_to_bounds(&self, pos: V2f) -> V2f {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => return pos,
+        };
+        let half = self.view_size * 0.5;
+
+        let axis = |pos: f32, min: f32, max: f32, half: f32| if max - min <= half * 2.0 {
+            (min + max) * 0.5
+        } else {
+            utils::clamp(pos, min + half, max - half)
+        };
+
+        V2 {
+            x: axis(pos.x, bounds.min.x, bounds.max.x, half.x),
+            y: axis(pos.y, bounds.min.y, bounds.max.y, half.y),
+        }
+    }
+}
+
+/// Frame rate independent lerp factor
+fn smoothing(speed: f32, dt: f32) -> f32 {
+    1.0 - (-speed * dt).exp()
+}
+
+/// Position camera has to be at so that `target` is inside of the deadzone
+fn deadzone_axis(camera: f32, target: f32, half_size: f32) -> f32 {
+    if target > camera + half_size {
+        target - half_size
+    } else if target < camera - half_size {
+        target + half_size
+    } else {
+        camera
+    }
+}
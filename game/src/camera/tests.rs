@@ -0,0 +1,107 @@
+use super::*;
+use crate::geom::vector::distance_sq;
+
+const DT: f32 = 1.0 / 60.0;
+
+fn close(a: V2f, b: V2f) -> bool {
+    distance_sq(a, b) < 1.0e-6
+}
+
+/// Camera at the origin with a 10x10 view at zoom 1.0
+fn camera() -> Camera {
+    let mut camera = Camera::new(V2::diag(0.0));
+    camera.update(V2::diag(10.0), DT);
+    camera
+}
+
+#[test]
+fn deadzone_follows_only_outside() {
+    let mut camera = camera();
+    camera.deadzone = V2::new(1.0, 2.0);
+
+    camera.follow(V2::new(0.5, -1.5), 0.0, DT);
+    assert!(close(camera.pos, V2::diag(0.0)), "{:?}", camera.pos);
+    camera.follow(V2::new(3.0, -5.0), 0.0, DT);
+    assert!(close(camera.pos, V2::new(2.0, -3.0)), "{:?}", camera.pos);
+}
+
+#[test]
+fn smooth_modes_settle_on_target() {
+    for &follow in &[Follow::Lerp { speed: 8.0 }, Follow::Spring { stiffness: 60.0, damping: 10.0 }] {
+        let mut camera = camera();
+        camera.follow = follow;
+        camera.follow(V2::new(4.0, 2.0), 0.0, DT);
+        assert!(camera.pos.x > 0.0 && camera.pos.x < 4.0, "{:?} jumped to {:?}", follow, camera.pos);
+        for _ in 0..600 {
+            camera.follow(V2::new(4.0, 2.0), 0.0, DT);
+        }
+        assert!(close(camera.pos, V2::new(4.0, 2.0)), "{:?} stopped at {:?}", follow, camera.pos);
+    }
+}
+
+#[test]
+fn bounds_clamp_and_center() {
+    let mut camera = camera();
+    // wider than the view, but not as high
+    camera.bounds = Some(AABB { min: V2::diag(0.0), max: V2::new(20.0, 5.0) });
+
+    camera.follow(V2::new(1.0, 1.0), 0.0, DT);
+    assert!(close(camera.pos, V2::new(5.0, 2.5)), "{:?}", camera.pos);
+    camera.follow(V2::new(100.0, 100.0), 0.0, DT);
+    assert!(close(camera.pos, V2::new(15.0, 2.5)), "{:?}", camera.pos);
+    assert!(close(camera.view().max, V2::new(20.0, 7.5)));
+}
+
+#[test]
+fn fit_picks_the_biggest_zoom_that_shows_all() {
+    let mut camera = camera();
+    camera.fit(AABB { min: V2::diag(0.0), max: V2::new(8.0, 4.0) }, V2::diag(10.0));
+    assert_eq!(camera.zoom(), 1.0);
+    assert!(close(camera.pos, V2::new(4.0, 2.0)));
+
+    camera.fit(AABB { min: V2::diag(-1.0), max: V2::diag(1.0) }, V2::diag(10.0));
+    assert_eq!(camera.zoom(), 4.0);
+    // too big for any zoom level, the smallest is used
+    camera.fit(AABB { min: V2::diag(0.0), max: V2::diag(100.0) }, V2::diag(10.0));
+    assert_eq!(camera.zoom(), ZOOM_LEVELS[0]);
+}
+
+#[test]
+fn zoom_around_keeps_target_in_place() {
+    let mut camera = camera();
+    let target = V2::new(4.0, 2.0);
+    let on_screen = |camera: &Camera| (target - camera.pos) * camera.zoom();
+    let before = on_screen(&camera);
+
+    camera.zoom_around(target, true);
+    assert_eq!(camera.zoom(), 1.5);
+    assert!(close(on_screen(&camera), before), "{:?}", on_screen(&camera));
+    camera.zoom_around(target, false);
+    camera.zoom_around(target, false);
+    assert_eq!(camera.zoom(), 0.5);
+    assert!(close(on_screen(&camera), before), "{:?}", on_screen(&camera));
+}
+
+#[test]
+fn trauma_decays() {
+    let mut camera = camera();
+    camera.add_trauma(2.0);
+    assert_eq!(camera.trauma, 1.0);
+
+    let mut offsets = Vec::new();
+    for _ in 0..10 {
+        camera.update(V2::diag(10.0), DT);
+        let offset = camera.view().min + V2::diag(5.0) - camera.pos;
+        assert!(offset.x.abs() <= camera.shake.max_offset.x && offset.y.abs() <= camera.shake.max_offset.y);
+        offsets.push(offset);
+    }
+    assert!(offsets.iter().any(|offset| !close(*offset, V2::diag(0.0))));
+
+    // 1.5 per second
+    assert!((camera.trauma - 0.75).abs() < 1.0e-4, "{}", camera.trauma);
+    camera.update(V2::diag(10.0), 0.25);
+    assert!((camera.trauma - 0.375).abs() < 1.0e-4, "{}", camera.trauma);
+    camera.update(V2::diag(10.0), 1.0);
+    assert_eq!(camera.trauma, 0.0);
+    assert!(close(camera.view().min + V2::diag(5.0), camera.pos));
+}
@@ -5,6 +5,13 @@ use std::{
     path::Path,
 };
 
+pub mod json;
+pub mod xml;
+pub mod zlib;
+
+#[cfg(test)]
+mod tests;
+
 pub mod prelude {
     pub use super::{Save, Load};
     pub use std::{path::Path, io};
@@ -28,4 +35,181 @@ pub fn read_all(filepath: impl AsRef<Path>) -> io::Result<Vec<u8>> {
 pub fn write_all(filepath: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
     use std::io::Write;
     File::create(filepath)?.write_all(bytes)
-}
\ No newline at end of file
+}
+/// `io::Error` with `InvalidData` kind, for files that were read but make no sense
+pub fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Little-endian reader over a byte slice.
+///
+/// Running out of bytes is an `UnexpectedEof` error, never a panic
+pub struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool { self.bytes.is_empty() }
+
+    pub fn read_bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("expected {} more bytes, got {}", len, self.bytes.len()),
+            ));
+        }
+        let (result, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(result)
+    }
+
+    pub fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    /// u32 length followed by UTF-8 bytes
+    pub fn read_str(&mut self) -> io::Result<String> {
+        let len = self.read_u32()? as usize;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| invalid_data(format!("string is not valid UTF-8: {}", e)))
+    }
+}
+
+/// Little-endian writer, counterpart of `BinaryReader`
+#[derive(Default)]
+pub struct BinaryWriter {
+    bytes: Vec<u8>,
+}
+
+impl BinaryWriter {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn into_bytes(self) -> Vec<u8> { self.bytes }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn write_u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    pub fn write_u32(&mut self, v: u32) {
+        self.write_bytes(&v.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, v: f32) {
+        self.write_u32(v.to_bits());
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        self.write_u32(s.len() as u32);
+        self.write_bytes(s.as_bytes());
+    }
+}
+
+// Versioned files
+
+/* Layout shared by binary files, all numbers are little-endian:
+    magic       4 bytes, says what kind of file it is
+    version     u32, bumped on every change of the payload layout
+    payload
+    checksum    u32, CRC-32 of everything before it
+*/
+
+/// CRC-32 (IEEE), the one zip and png use
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub fn write_versioned(magic: &[u8; 4], version: u32, payload: &[u8]) -> Vec<u8> {
+    let mut writer = BinaryWriter::new();
+    writer.write_bytes(magic);
+    writer.write_u32(version);
+    writer.write_bytes(payload);
+    let checksum = crc32(&writer.bytes);
+    writer.write_u32(checksum);
+    writer.into_bytes()
+}
+
+/// Version and payload of a file written by `write_versioned`.
+///
+/// `None` if the file doesn't start with `magic`, so callers can fall back to older formats.
+/// Checksum is checked, version is up to the caller
+pub fn read_versioned<'a>(file: &'a [u8], magic: &[u8; 4]) -> io::Result<Option<(u32, &'a [u8])>> {
+    if !file.starts_with(magic) {
+        return Ok(None);
+    }
+    let checksum_start = match file.len().checked_sub(4) {
+        Some(start) if start >= magic.len() + 4 => start,
+        _ => return Err(invalid_data("file is too short for its header".into())),
+    };
+    let (content, checksum) = file.split_at(checksum_start);
+    let checksum = BinaryReader::new(checksum).read_u32()?;
+    if crc32(content) != checksum {
+        return Err(invalid_data("checksum mismatch, file is damaged".into()));
+    }
+
+    let mut reader = BinaryReader::new(&content[magic.len()..]);
+    let version = reader.read_u32()?;
+    Ok(Some((version, reader.bytes)))
+}
+
+/// Standard alphabet, padding is optional, whitespace is ignored
+pub fn decode_base64(text: &str) -> io::Result<Vec<u8>> {
+    let value = |c: u8| -> io::Result<u32> {
+        Ok(u32::from(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(invalid_data(format!("invalid base64 character {:?}", c as char))),
+        }))
+    };
+
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let digits = match digits.iter().position(|&c| c == b'=') {
+        Some(padding) if digits[padding..].iter().all(|&c| c == b'=') && digits.len() % 4 == 0 => &digits[..padding],
+        Some(_) => return Err(invalid_data("invalid base64 padding".into())),
+        None => &digits[..],
+    };
+    if digits.len() % 4 == 1 {
+        return Err(invalid_data("invalid base64 length".into()));
+    }
+
+    let mut result = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut bits = 0;
+        for &c in chunk {
+            bits = bits << 6 | value(c)?;
+        }
+        bits <<= 6 * (4 - chunk.len() as u32);
+        let bytes = bits.to_be_bytes();
+        result.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Ok(result)
+}
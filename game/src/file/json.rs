@@ -0,0 +1,308 @@
+/* Minimal JSON reader (RFC 8259), enough for files written by tools like LDtk.
+
+   Numbers are f64, objects keep the order of their keys.
+*/
+
+use std::io;
+use super::invalid_data;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    pub fn is_null(&self) -> bool { *self == Value::Null }
+
+    /// Field of an object, `None` for missing fields and values that aren't objects
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Field that must be there
+    pub fn field(&self, key: &str) -> io::Result<&Value> {
+        self.get(key).ok_or_else(|| invalid_data(format!("missing field `{}`", key)))
+    }
+
+    fn expected(&self, what: &str) -> io::Error {
+        invalid_data(format!("expected {}, found {}", what, self.type_name()))
+    }
+
+    pub fn as_f64(&self) -> io::Result<f64> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            _ => Err(self.expected("number")),
+        }
+    }
+
+    /// Number without a fractional part that fits into i64
+    pub fn as_i64(&self) -> io::Result<i64> {
+        let n = self.as_f64()?;
+        if n.fract() != 0.0 || n.abs() > 9.0e15 {
+            return Err(invalid_data(format!("expected an integer, found {}", n)));
+        }
+        Ok(n as i64)
+    }
+
+    pub fn as_str(&self) -> io::Result<&str> {
+        match self {
+            Value::String(s) => Ok(s),
+            _ => Err(self.expected("string")),
+        }
+    }
+
+    pub fn as_array(&self) -> io::Result<&[Value]> {
+        match self {
+            Value::Array(values) => Ok(values),
+            _ => Err(self.expected("array")),
+        }
+    }
+}
+
+/// Deepest nesting of arrays and objects, deeper input is an error and not a stack overflow
+pub const MAX_DEPTH: u32 = 256;
+
+pub fn parse(text: &str) -> io::Result<Value> {
+    let mut parser = Parser { text: text.as_bytes(), pos: 0, depth: 0 };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != text.len() {
+        return Err(parser.error("unexpected data after the value"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    /// Arrays and objects that `pos` is in, up to `MAX_DEPTH`
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> io::Error {
+        let line = self.text[..self.pos].iter().filter(|&&c| c == b'\n').count() + 1;
+        invalid_data(format!("json line {}: {}", line, msg))
+    }
+
+    fn peek(&self) -> Option<u8> { self.text.get(self.pos).copied() }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> io::Result<()> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", c as char)))
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> io::Result<Value> {
+        if self.text[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn value(&mut self) -> io::Result<Value> {
+        match self.peek() {
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of data")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> io::Result<Value>) -> io::Result<Value> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error(&format!("nested deeper than {}", MAX_DEPTH)));
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn object(&mut self) -> io::Result<Value> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(fields));
+                },
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> io::Result<Value> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            self.skip_whitespace();
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(values));
+                },
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    /// -? (0 | [1-9][0-9]*) (.[0-9]+)? ([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> io::Result<Value> {
+        let start = self.pos;
+        let invalid = |parser: &Self| parser.error("invalid number");
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => { self.digits(); },
+            _ => return Err(invalid(self)),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(invalid(self));
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(invalid(self));
+            }
+        }
+
+        // only ASCII was skipped
+        std::str::from_utf8(&self.text[start..self.pos]).ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| invalid(self))
+    }
+
+    fn hex4(&mut self) -> io::Result<u32> {
+        // from_str_radix alone would take a sign
+        let digits = self.text.get(self.pos..self.pos + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect(b'"')?;
+        let mut result = Vec::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("string is not closed"))?;
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("string is not closed"))?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // surrogate pair
+                            if (0xD800..0xDC00).contains(&code) && self.text[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            std::char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+                        },
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    result.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                },
+                0..=0x1F => return Err(self.error("control character in a string")),
+                _ => result.push(c),
+            }
+        }
+        // input was a &str and escapes produce valid UTF-8
+        String::from_utf8(result).map_err(|_| self.error("string is not valid UTF-8"))
+    }
+}
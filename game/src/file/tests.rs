@@ -0,0 +1,261 @@
+use super::*;
+
+/// Output limit for streams that should decompress
+const MAX_LEN: usize = 1 << 16;
+
+/// What `compress_sample` compresses, numbers are CSV-like to look like tile data
+fn sample() -> Vec<u8> {
+    (0..400).map(|i| format!("{},", i * 7 % 13)).collect::<String>().into_bytes()
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn base64() {
+    assert_eq!(decode_base64("").unwrap(), b"");
+    assert_eq!(decode_base64("Zg==").unwrap(), b"f");
+    assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+    assert_eq!(decode_base64("Zm9v").unwrap(), b"foo");
+    assert_eq!(decode_base64(" Zm9v\n YmE ").unwrap(), b"fooba");
+    assert!(decode_base64("Zm9v!").is_err());
+    assert!(decode_base64("Z").is_err());
+    assert!(decode_base64("Zg=a").is_err());
+}
+
+#[test]
+fn zlib_blocks() {
+    // stored
+    let stored = [0x78, 0x01, 0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27];
+    assert_eq!(zlib::decompress(&stored, MAX_LEN).unwrap(), b"abc");
+
+    // fixed huffman codes with a back reference
+    let fixed = [120, 218, 75, 76, 74, 78, 196, 64, 0, 88, 117, 8, 11];
+    assert_eq!(zlib::decompress(&fixed, MAX_LEN).unwrap(), b"abcabcabcabcabcabcabc");
+
+    // dynamic huffman codes, written by python's zlib
+    let dynamic = decode_base64("eNrtyTEBACAMA0FDPyQFCvg3hgq23HpiYw7FZWAxsVm4aJRMJn/lA5g7pyA=").unwrap();
+    assert_eq!(zlib::decompress(&dynamic, MAX_LEN).unwrap(), sample());
+}
+
+#[test]
+fn zlib_output_limit() {
+    let stored = [0x78, 0x01, 0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27];
+    assert_eq!(zlib::decompress(&stored, 3).unwrap(), b"abc");
+    assert!(zlib::decompress(&stored, 2).is_err());
+
+    // 3 literals, then a back reference for the other 18 bytes
+    let fixed = [120, 218, 75, 76, 74, 78, 196, 64, 0, 88, 117, 8, 11];
+    assert_eq!(zlib::decompress(&fixed, 21).unwrap().len(), 21);
+    assert!(zlib::decompress(&fixed, 20).is_err());
+    assert!(zlib::decompress(&fixed, 2).is_err());
+
+    let dynamic = decode_base64("eNrtyTEBACAMA0FDPyQFCvg3hgq23HpiYw7FZWAxsVm4aJRMJn/lA5g7pyA=").unwrap();
+    let error = zlib::decompress(&dynamic, sample().len() - 1).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn zlib_damaged() {
+    let dynamic = decode_base64("eNrtyTEBACAMA0FDPyQFCvg3hgq23HpiYw7FZWAxsVm4aJRMJn/lA5g7pyA=").unwrap();
+    for len in 0..dynamic.len() {
+        assert!(zlib::decompress(&dynamic[..len], MAX_LEN).is_err(), "truncated to {} bytes", len);
+    }
+    for i in 0..dynamic.len() {
+        for bit in 0..8 {
+            let mut damaged = dynamic.clone();
+            damaged[i] ^= 1 << bit;
+            // padding bits after the last block are never read, so only a panic is a failure there
+            let result = zlib::decompress(&damaged, MAX_LEN);
+            if i < 2 || i >= dynamic.len() - 4 {
+                assert!(result.is_err(), "damage in header or checksum byte {} is not detected", i);
+            }
+        }
+    }
+}
+
+/// Deflate bits written by hand, for streams that no compressor writes
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    bit: u32,
+}
+
+impl Bits {
+    /// Numbers are packed starting from the least significant bit
+    fn put(mut self, value: u32, count: u32) -> Self {
+        for i in 0..count {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            *self.bytes.last_mut().unwrap() |= ((value >> i & 1) as u8) << self.bit;
+            self.bit = (self.bit + 1) % 8;
+        }
+        self
+    }
+
+    /// Huffman codes are packed starting from the most significant bit
+    fn code(mut self, code: u32, len: u32) -> Self {
+        for i in (0..len).rev() {
+            self = self.put(code >> i & 1, 1);
+        }
+        self
+    }
+
+    /// In a zlib stream with a checksum of zeros, which is never reached
+    fn zlib(self) -> Vec<u8> {
+        let mut result = vec![0x78, 0x01];
+        result.extend_from_slice(&self.bytes);
+        result.extend_from_slice(&[0; 4]);
+        result
+    }
+}
+
+#[test]
+fn zlib_corrupt_streams() {
+    let stored = [0x78, 0x01, 0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27];
+    let fixed = [120, 218, 75, 76, 74, 78, 196, 64, 0, 88, 117, 8, 11];
+    for stream in &[&stored[..], &fixed[..]] {
+        for len in 0..stream.len() {
+            assert!(zlib::decompress(&stream[..len], MAX_LEN).is_err(), "truncated to {} bytes", len);
+        }
+    }
+
+    // last block, then the block type
+    let last = |kind| Bits::default().put(1, 1).put(kind, 2);
+    // dynamic block with the fewest codes: 257 literal and length codes, 1 distance code,
+    // and code lengths of the code length symbols 16, 17, 18 and 0
+    let dynamic = |lengths: [u32; 4]| lengths.iter().fold(last(2).put(0, 5).put(0, 5).put(0, 4), |bits, &len| bits.put(len, 3));
+    let cases = vec![
+        ("invalid block type", last(3)),
+        ("stored block length mismatch", last(0).put(0, 5).put(3, 16).put(3, 16)),
+        ("over-subscribed code length code", dynamic([1, 1, 1, 0])),
+        // the only code is 0 for symbol 18, a 1 never ends a code
+        ("incomplete code length code", dynamic([0, 0, 1, 0]).put(0xFFFF, 16)),
+        ("repeat of the first length", dynamic([1, 1, 0, 0]).code(0, 1)),
+        // two runs of 138 zeros for 258 lengths
+        ("too many code lengths", dynamic([0, 1, 1, 0]).code(1, 1).put(127, 7).code(1, 1).put(127, 7)),
+        // 138 and 120 zeros
+        ("no end of block code", dynamic([0, 1, 1, 0]).code(1, 1).put(127, 7).code(1, 1).put(109, 7)),
+        // fixed codes of 280-287 are 8 bits from 0b1100_0000, 286 and 287 are never valid
+        ("invalid length code", last(1).code(0b1100_0110, 8)),
+        // length 3 from 7 bit code 257, then 5 bit distance code 30 or 0,
+        // fixed distance codes 30 and 31 are never valid
+        ("invalid distance code", last(1).code(1, 7).code(30, 5)),
+        ("distance before the start", last(1).code(1, 7).code(0, 5)),
+    ];
+    for (name, bits) in cases {
+        let stream = bits.zlib();
+        let error = zlib::decompress(&stream, MAX_LEN).err().unwrap_or_else(|| panic!("{} is accepted", name));
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}: {}", name, error);
+    }
+}
+
+#[test]
+fn xml() {
+    let doc = xml::parse(r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!-- comment -->
+        <map width="2" name='a &amp; b'>
+            <layer id="1"/>
+            <data encoding="csv">1,&#50;<![CDATA[,<3>]]></data>
+        </map>
+    "#).unwrap();
+
+    assert_eq!(doc.name, "map");
+    assert_eq!(doc.parse_attr::<i32>("width").unwrap(), Some(2));
+    assert_eq!(doc.attr("name"), Some("a & b"));
+    assert_eq!(doc.children.len(), 2);
+    assert_eq!(doc.child("data").unwrap().text, "1,2,<3>");
+    assert!(doc.parse_attr::<i32>("name").is_err());
+    assert!(doc.required_attr("height").is_err());
+
+    assert!(xml::parse("<a><b></a>").is_err());
+    assert!(xml::parse("<a>").is_err());
+    assert!(xml::parse("<a/><b/>").is_err());
+    assert!(xml::parse("<a x=\"&nope;\"/>").is_err());
+    assert!(xml::parse("<!DOCTYPE a><a/>").is_err());
+}
+
+#[test]
+fn xml_malformed() {
+    // CDATA is taken as it is, up to the first `]]>`
+    let doc = xml::parse("<a><![CDATA[&amp; <b>]]]]><![CDATA[>]]></a>").unwrap();
+    assert_eq!(doc.text, "&amp; <b>]]>");
+    assert!(doc.children.is_empty());
+
+    let bad = [
+        "<a", "<a x", "<a x=", "<a x=\"1\"", "<a x=\"1", "<a x=1/>", "<a></a", "<a></", "<a>text", "< a/>",
+        "<a><!-- comment</a>", "<a><![CDATA[text</a>", "<a><![CDATA[text]]</a>", "<a><?pi</a>", "<?xml",
+        "<a>&amp</a>", "<a x='&lt'/>", "<a>&#;</a>", "<a>&#x;</a>", "<a>&#xD800;</a>", "<a>&#99999999;</a>",
+        "<a>&#x110000;</a>", "<a>&AMP;</a>",
+    ];
+    for bad in &bad {
+        assert!(xml::parse(bad).is_err(), "accepted {:?}", bad);
+    }
+}
+
+#[test]
+fn xml_nesting_depth() {
+    let nested = |depth: usize| format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+    let max = xml::MAX_DEPTH as usize;
+    assert!(xml::parse(&nested(max)).is_ok());
+
+    let error = xml::parse(&nested(max + 1)).err().unwrap();
+    assert!(error.to_string().contains("deeper"), "{}", error);
+    // closed, so only the depth limit stops it
+    assert!(xml::parse(&nested(1_000_000)).is_err());
+}
+
+#[test]
+fn json() {
+    use json::Value;
+
+    let doc = json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\ud83d\ude00\n"}, "a": 0} "#).unwrap();
+    let a = doc.field("a").unwrap().as_array().unwrap();
+    assert_eq!(a, [Value::Number(1.0), Value::Number(-25.0), Value::Bool(true), Value::Null]);
+    assert_eq!(doc.field("b").unwrap().field("c").unwrap().as_str().unwrap(), "x\"\u{e9}\u{1F600}\n");
+    assert!(doc.field("missing").is_err());
+    assert!(a[0].as_str().is_err());
+    assert_eq!(a[1].as_i64().unwrap(), -25);
+
+    for bad in &["", "{", "[1,]", "{\"a\" 1}", "01", "1.", ".5", "-", "1e", "\"\\x\"", "\"a", "[1] 2", "tru", "\"\u{1}\""] {
+        assert!(json::parse(bad).is_err(), "accepted {:?}", bad);
+    }
+    assert!(json::parse(&"[".repeat(10_000)).is_err());
+}
+
+#[test]
+fn json_malformed() {
+    let bad = [
+        // unterminated strings
+        "\"abc", "\"abc\\", "\"abc\\\"", "[\"a", "{\"a", "{\"a\": \"b}",
+        // escapes
+        "\"\\x\"", "\"\\U0041\"", "\"\\u12\"", "\"\\u12g4\"", "\"\\u-123\"", "\"\\u+123\"",
+        // surrogates, lone, reversed or with a bad second half
+        "\"\\ud800\"", "\"\\udc00\"", "\"\\udc00\\ud800\"", "\"\\ud800x\"", "\"\\ud800\\u0041\"",
+        "\"\\ud83d\\ude0\"", "\"\\ud83d\\\"",
+        // commas
+        "[1,]", "[1,,2]", "[,]", "[,1]", "{\"a\": 1,}", "{,}", "{\"a\": 1,,\"b\": 2}", "[1 2]",
+    ];
+    for bad in &bad {
+        assert!(json::parse(bad).is_err(), "accepted {:?}", bad);
+    }
+    assert_eq!(json::parse("\"\\ud83d\\ude00\\/\"").unwrap().as_str().unwrap(), "\u{1F600}/");
+}
+
+#[test]
+fn json_nesting_depth() {
+    let nested = |depth: usize, open: &str, close: &str| format!("{}0{}", open.repeat(depth), close.repeat(depth));
+    let max = json::MAX_DEPTH as usize;
+    for &(open, close) in &[("[", "]"), ("{\"a\":", "}"), ("[{\"a\": [", "]}]")] {
+        let levels = open.matches(|c| c == '[' || c == '{').count();
+        assert!(json::parse(&nested(max / levels, open, close)).is_ok(), "{}", open);
+
+        let error = json::parse(&nested(max / levels + 1, open, close)).err().unwrap();
+        assert!(error.to_string().contains("deeper"), "{}: {}", open, error);
+    }
+    // closed, so only the depth limit stops it
+    assert!(json::parse(&nested(1_000_000, "[", "]")).is_err());
+}
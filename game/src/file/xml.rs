@@ -0,0 +1,238 @@
+/* Minimal XML reader, enough for files written by tools like Tiled.
+
+   Supports elements, attributes, text, comments, CDATA, the `<?xml ?>` declaration
+   and the predefined and numeric character references.
+   DTDs and namespaces aren't supported, a DOCTYPE is an error.
+*/
+
+use std::io;
+use super::invalid_data;
+
+#[derive(Clone, Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// All text directly inside of the element, concatenated
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Attribute that must be there
+    pub fn required_attr(&self, name: &str) -> io::Result<&str> {
+        self.attr(name)
+            .ok_or_else(|| invalid_data(format!("<{}> has no `{}` attribute", self.name, name)))
+    }
+
+    /// Attribute parsed as `T`, `None` if it's missing
+    pub fn parse_attr<T>(&self, name: &str) -> io::Result<Option<T>>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        match self.attr(name) {
+            Some(value) => value.parse().map(Some).map_err(|e| invalid_data(format!(
+                "<{}> attribute `{}` = {:?}: {}", self.name, name, value, e,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Deepest nesting of elements, deeper input is an error and not a stack overflow
+pub const MAX_DEPTH: u32 = 256;
+
+/// Parses a document and returns its root element
+pub fn parse(text: &str) -> io::Result<Element> {
+    let mut parser = Parser { text, pos: 0, depth: 0 };
+    parser.skip_misc()?;
+    if parser.starts_with("<!DOCTYPE") {
+        return Err(parser.error("DOCTYPE is not supported"));
+    }
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.pos != text.len() {
+        return Err(parser.error("unexpected data after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Elements that `pos` is in, up to `MAX_DEPTH`
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> io::Error {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        invalid_data(format!("xml line {}: {}", line, msg))
+    }
+
+    fn rest(&self) -> &'a str { &self.text[self.pos..] }
+
+    fn starts_with(&self, s: &str) -> bool { self.rest().starts_with(s) }
+
+    fn expect(&mut self, s: &str) -> io::Result<()> {
+        if self.starts_with(s) {
+            self.pos += s.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", s)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips until after `end`
+    fn skip_past(&mut self, end: &str) -> io::Result<&'a str> {
+        match self.rest().find(end) {
+            Some(i) => {
+                let skipped = &self.rest()[..i];
+                self.pos += i + end.len();
+                Ok(skipped)
+            },
+            None => Err(self.error(&format!("missing `{}`", end))),
+        }
+    }
+
+    /// Whitespace, comments and processing instructions
+    fn skip_misc(&mut self) -> io::Result<()> {
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> io::Result<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == ':'))
+            .unwrap_or_else(|| rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self) -> io::Result<Element> {
+        self.expect("<")?;
+        let mut element = Element { name: self.name()?.into(), ..Element::default() };
+
+        // attributes
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.starts_with(">") {
+                self.pos += 1;
+                break;
+            }
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.starts_with("\"") { "\"" } else { "'" };
+            self.expect(quote)?;
+            let value = self.skip_past(quote)?;
+            element.attributes.push((name.into(), self.unescape(value)?));
+        }
+
+        // content
+        loop {
+            if self.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(self.error(&format!("expected </{}>, found </{}>", element.name, name)));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<![CDATA[") {
+                self.pos += "<![CDATA[".len();
+                let text = self.skip_past("]]>")?;
+                element.text.push_str(text);
+            } else if self.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.starts_with("<") {
+                self.depth += 1;
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error(&format!("nested deeper than {}", MAX_DEPTH)));
+                }
+                element.children.push(self.element()?);
+                self.depth -= 1;
+            } else if self.rest().is_empty() {
+                return Err(self.error(&format!("<{}> is not closed", element.name)));
+            } else {
+                let len = self.rest().find('<').unwrap_or_else(|| self.rest().len());
+                let text = &self.rest()[..len];
+                let text = self.unescape(text)?;
+                element.text.push_str(&text);
+                self.pos += len;
+            }
+        }
+    }
+
+    fn unescape(&self, s: &str) -> io::Result<String> {
+        let mut result = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(start) = rest.find('&') {
+            result.push_str(&rest[..start]);
+            let end = rest[start..].find(';')
+                .ok_or_else(|| self.error("unterminated character reference"))?;
+            let reference = &rest[start + 1..start + end];
+            let c = match reference {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = if let Some(hex) = reference.strip_prefix("#x") {
+                        u32::from_str_radix(hex, 16).ok()
+                    } else if let Some(dec) = reference.strip_prefix('#') {
+                        dec.parse().ok()
+                    } else {
+                        None
+                    };
+                    code.and_then(std::char::from_u32)
+                        .ok_or_else(|| self.error(&format!("unknown reference &{};", reference)))?
+                },
+            };
+            result.push(c);
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
@@ -0,0 +1,310 @@
+/* zlib (RFC 1950) and DEFLATE (RFC 1951) decompression.
+
+   Only decompression, it's used to read data that other tools wrote.
+*/
+
+use std::io;
+use super::invalid_data;
+
+/// Decompresses a zlib stream: 2 byte header, deflate data, adler-32 of the result.
+/// Output longer than `max_len` is an error, so a small stream can't expand to fill the memory
+pub fn decompress(bytes: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    if bytes.len() < 6 {
+        return Err(invalid_data("zlib: stream is too short".into()));
+    }
+    let (cmf, flg) = (bytes[0], bytes[1]);
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 {
+        return Err(invalid_data("zlib: unsupported compression method".into()));
+    }
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(invalid_data("zlib: header checksum mismatch".into()));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid_data("zlib: preset dictionaries are not supported".into()));
+    }
+
+    let mut input = BitReader { bytes: &bytes[2..], pos: 0, bit: 0 };
+    let result = inflate(&mut input, max_len)?;
+
+    let adler_start = 2 + input.byte_pos();
+    let adler = match bytes.get(adler_start..adler_start + 4) {
+        Some(adler) => u32::from_be_bytes([adler[0], adler[1], adler[2], adler[3]]),
+        None => return Err(invalid_data("zlib: missing adler-32 checksum".into())),
+    };
+    if adler32(&result) != adler {
+        return Err(invalid_data("zlib: adler-32 checksum mismatch".into()));
+    }
+    Ok(result)
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    b << 16 | a
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn eof() -> io::Error {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "deflate: unexpected end of data")
+    }
+
+    /// Bits are packed starting from the least significant one
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut result = 0;
+        for i in 0..count {
+            let byte = *self.bytes.get(self.pos).ok_or_else(Self::eof)?;
+            result |= u32::from(byte >> self.bit & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(result)
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    /// Position of the first byte that wasn't read from
+    fn byte_pos(&self) -> usize {
+        if self.bit == 0 { self.pos } else { self.pos + 1 }
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        debug_assert_eq!(self.bit, 0);
+        let result = self.bytes.get(self.pos..self.pos + len).ok_or_else(Self::eof)?;
+        self.pos += len;
+        Ok(result)
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    /// Number of codes of each length
+    counts: [u16; 16],
+    /// Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0_u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // over-subscribed codes can't be decoded, incomplete ones are allowed
+        let mut left = 1_i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(invalid_data("deflate: invalid huffman code".into()));
+            }
+        }
+
+        let mut offsets = [0_u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut BitReader) -> io::Result<u16> {
+        let mut code = 0_i32;
+        let mut first = 0_i32;
+        let mut index = 0_i32;
+        for len in 1..16 {
+            code |= input.bits(1)? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("deflate: invalid huffman code in data".into()))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+fn too_long(max_len: usize) -> io::Error {
+    invalid_data(format!("deflate: output is longer than {} bytes", max_len))
+}
+
+fn inflate(input: &mut BitReader, max_len: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align_to_byte();
+                let header = input.bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(invalid_data("deflate: stored block length mismatch".into()));
+                }
+                if out.len() + len as usize > max_len {
+                    return Err(too_long(max_len));
+                }
+                out.extend_from_slice(input.bytes(len as usize)?);
+            },
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                inflate_block(input, &mut out, max_len, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(input)?;
+                inflate_block(input, &mut out, max_len, &literals, &distances)?;
+            },
+            _ => return Err(invalid_data("deflate: invalid block type".into())),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn fixed_codes() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0_u8; 288];
+    for (symbol, len) in lengths.iter_mut().enumerate() {
+        *len = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(input: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_length_count = input.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(invalid_data("deflate: too many codes".into()));
+    }
+
+    let mut code_lengths = [0_u8; 19];
+    for &i in &ORDER[..code_length_count] {
+        code_lengths[i] = input.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(input)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let previous = *lengths.last()
+                    .ok_or_else(|| invalid_data("deflate: repeat without a previous length".into()))?;
+                (previous, 3 + input.bits(2)?)
+            },
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid_data("deflate: code lengths overflow".into()));
+    }
+    if lengths[256] == 0 {
+        return Err(invalid_data("deflate: no end of block code".into()));
+    }
+
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals)?, Huffman::new(distances)?))
+}
+
+fn inflate_block(
+    input: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        match symbol {
+            0..=255 if out.len() == max_len => return Err(too_long(max_len)),
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid_data("deflate: invalid length code".into()));
+                }
+                let len = LENGTH_BASE[i] as usize + input.bits(u32::from(LENGTH_EXTRA[i]))? as usize;
+
+                let i = distances.decode(input)? as usize;
+                if i >= DIST_BASE.len() {
+                    return Err(invalid_data("deflate: invalid distance code".into()));
+                }
+                let dist = DIST_BASE[i] as usize + input.bits(u32::from(DIST_EXTRA[i]))? as usize;
+                if dist > out.len() {
+                    return Err(invalid_data("deflate: distance is past the start of data".into()));
+                }
+                if out.len() + len > max_len {
+                    return Err(too_long(max_len));
+                }
+
+                // copies can overlap with what they produce
+                let start = out.len() - dist;
+                for j in 0..len {
+                    let byte = out[start + j];
+                    out.push(byte);
+                }
+            },
+        }
+    }
+}
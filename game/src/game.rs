@@ -1,13 +1,33 @@
 mod tilemap;
-
+mod tileset;
+mod autotile;
+mod tiled;
+mod ldtk;
+mod level;
+mod level_index;
+mod history;
+mod brush;
+mod stamp;
+mod palette;
+mod minimap;
+pub mod validate;
+mod spawns;
+mod states;
+mod menus;
+#[cfg(test)]
+mod tests;
+
+use std::mem;
 use platform::input::{Input, KBKey, MouseKey};
 use crate::{
     render::{
         self,
         Color,
         Bitmap,
-        text::FontBitmaps,
+        text::{FontBitmaps, TextStyle},
         canvas_info::CanvasInfo,
+        space::{CanvasPos, ScreenPos, WorldPos},
+        world,
     },
     geom::{
         vector::{
@@ -18,12 +38,26 @@ use crate::{
         aabb::AABB,
     },
     file::{Load, Save},
+    camera::{Camera, Follow},
+    ui::{Ui, UiState},
 };
 use tilemap::{
     Tilemap,
     Tile,
-    TileInfo,
+    Anchor,
+    MAX_SIDE,
 };
+use tileset::TileSet;
+use level::{Level, LevelSnapshot, Spawn};
+use level_index::{LevelIndex, Side};
+use ldtk::LdtkLevel;
+use history::History;
+use brush::{Tool, Drag};
+use stamp::{Stamp, StampLibrary};
+use palette::PaletteItem;
+use validate::{Issue, Severity};
+use states::{GameState, StateStack};
+use menus::Options;
 
 /* TODO: next
     - game:
@@ -31,12 +65,9 @@ use tilemap::{
 
     - engine:
         - timer
-        - interface (menus, buttons, etc)
         - rendering api:
-            - abstract away coordinate system handling
             - command buffer
             - something about bitmaps
-            - text?
 
         - transformations:
             - scaling
@@ -52,31 +83,80 @@ use tilemap::{
     - generating vfx at runtime (particles)
 */
 
-enum GameState {
-    Playing,
-    LevelEditor,
-}
-
 struct GameData {
     pub canvas_info: CanvasInfo,
-
-    pub state: GameState,
-
-    pub tilemap: Tilemap,
-    pub tile_info: TileInfo,
+    pub camera: Camera,
+    pub editor_camera: Camera,
+
+    pub states: StateStack,
+    pub options: Options,
+    /// Set by the title screen, the window closes at the end of the frame
+    pub quit: bool,
+
+    pub level: Level,
+    /// File in `LEVELS_FOLDER` the level is saved to, `None` for a level that was never saved
+    pub level_file: Option<String>,
+    /// `level.to_bytes()` when it was last opened or saved, to tell if there are unsaved changes
+    pub saved_level: Vec<u8>,
+    pub level_index: LevelIndex,
+    /// Takes all editor input while it's open
+    pub editor_dialog: Option<Dialog>,
+    pub tileset: TileSet,
+    /// `tileset` came with an imported level instead of from `TILESET_PATH`
+    pub tileset_imported: bool,
+    /// Index of the tile layer editor paints on
+    pub editor_layer: usize,
+    /// Tile editor paints with
+    pub editor_tile: Tile,
+    /// Entity kind editor places instead of painting tiles, see `spawns::KINDS`
+    pub editor_entity: Option<&'static str>,
+    /// Index of the spawn whose properties are shown
+    pub editor_spawn: Option<usize>,
+    pub spawn_drag: Option<SpawnDrag>,
+    pub editor_tool: Tool,
+    /// Drag of `editor_tool` that isn't finished yet
+    pub editor_drag: Option<Drag>,
+    /// Handle on the level outline that is being dragged, see `resize_handles`
+    pub editor_resize: Option<Anchor>,
+    /// Selected tiles of the editor layer
+    pub selection: Option<AABB<i32>>,
+    /// Tiles lifted from the selection while it's being moved
+    pub floating: Option<Stamp>,
+    /// What the stamp tool pastes
+    pub clipboard: Option<Stamp>,
+    pub stamps: StampLibrary,
+    /// Stamp of the library that was put into the clipboard last
+    pub stamp_index: usize,
+    /// Undo and redo of editor changes to `level`
+    pub history: History,
+    /// Problems of the level are shown, see `validate`
+    pub show_issues: bool,
+    /// Levels of an imported LDtk project, the one at `world_index` is in `level`
+    pub world: Vec<LdtkLevel>,
+    pub world_index: usize,
 
     pub player: Entity,
     pub hook: Entity,
 
     pub player_attack_counter: f32,
 
-    pub enemies: [Entity; 1],
+    pub enemies: Vec<Entity>,
+    pub pickups: Vec<Pickup>,
+    pub triggers: Vec<Trigger>,
+    /// Where the player restarts, set by checkpoint triggers
+    pub checkpoint: Option<V2f>,
+    /// Where the player starts instead of the player spawn, see `start_playing`
+    pub play_start: Option<V2f>,
+    /// Editor state from before playing, put back when returning to the editor
+    pub editor_snapshot: Option<EditorSnapshot>,
+    pub coins: i32,
 
     pub player_bmps: PlayerBmps,
     pub enemy_bmp_right: Bitmap,
     pub enemy_bmp_left: Bitmap,
 
-    pub font_bmp: render::text::FontBitmaps,
+    pub font_bmp: FontBitmaps,
+    pub ui: UiState,
     pub text: String,
     pub text_timer: f32,
 }
@@ -87,16 +167,99 @@ struct PlayerBmps {
     pub hook: Bitmap,
 }
 
+/// Puts the player at the checkpoint, the play start or its spawn,
+/// everything else is created from the level's spawns again
 fn restart(data: &mut GameData) {
-    data.player = Entity::new_character((2.5, 2.5).into(), 1);
+    let pos = data.level.spawn("player").map_or((2.5, 2.5).into(), |spawn| spawn.pos);
+    data.player = Entity::new_character(data.checkpoint.or(data.play_start).unwrap_or(pos), 1);
+    data.camera.pos = data.player.pos;
+    data.coins = 0;
+
+    let spawns = &data.level.spawns;
+    data.enemies = spawns.iter()
+        .filter(|spawn| spawn.kind == "enemy")
+        .map(|spawn| Entity::new_character(spawn.pos, spawns::int_prop(spawn, "hp")))
+        .collect();
+    data.pickups = spawns.iter()
+        .filter(|spawn| spawn.kind == "pickup")
+        .map(|spawn| Pickup {
+            pos: spawn.pos,
+            item: if spawns::prop(spawn, "item") == "heart" { Item::Heart } else { Item::Coin },
+        })
+        .collect();
+    data.triggers = spawns.iter()
+        .filter(|spawn| spawn.kind == "trigger")
+        .map(|spawn| {
+            let min = V2f::from(V2i::from(spawn.pos.floor()));
+            let size = V2i::new(spawns::int_prop(spawn, "width"), spawns::int_prop(spawn, "height"));
+            Trigger {
+                area: AABB { min, max: min + V2f::from(size) },
+                action: match spawns::prop(spawn, "action") {
+                    "hazard" => TriggerAction::Hazard,
+                    "exit" => TriggerAction::Exit,
+                    _ => TriggerAction::Checkpoint(spawn.pos),
+                },
+            }
+        })
+        .collect();
+}
+
+fn player_camera() -> Camera {
+    let mut camera = Camera::new((2.5, 2.5).into());
+    camera.follow = camera_follow(&Options::default());
+    camera.deadzone = (1.0, 1.5).into();
+    camera.look_ahead = 2.0;
+    camera
+}
+
+fn camera_follow(options: &Options) -> Follow {
+    if options.springy_camera {
+        Follow::Spring { stiffness: 60.0, damping: 10.0 }
+    } else {
+        Follow::Lerp { speed: 8.0 }
+    }
+}
+
+/// Folder the editor opens and saves levels in
+const LEVELS_FOLDER: &str = "data/levels";
+/// Order and connections of the levels, see `level_index`
+const LEVEL_INDEX_FILE: &str = "index.txt";
+/// Level opened at startup. Levels are saved as text, binary file is only read if there is no text one
+const LEVEL_FILE: &str = "map_00.txt";
+const OLD_LEVEL_PATH: &str = "data/levels/map_00";
+/// Map made in Tiled, imported with Ctrl+I, see `tiled`
+const TILED_PATH: &str = "data/levels/map_00.tmx";
+/// LDtk project, imported with Ctrl+L, see `ldtk`
+const LDTK_PATH: &str = "data/levels/world.ldtk";
+
+const TILESET_PATH: &str = "data/tiles/tileset.txt";
+const SPRITE_FOLDER: &str = "data/sprites/size_16/";
+
+/// Stamps saved in the editor with Ctrl+B
+const STAMPS_FILE: &str = "stamps.txt";
+
+fn in_levels_folder(file: &str) -> String {
+    format!("{}/{}", LEVELS_FOLDER, file)
 }
 
+/// Most editor steps that can be undone
+const EDITOR_HISTORY_LIMIT: usize = 200;
+
 const FONT: &str = "data/fonts/FiraCode-Bold.ttf";
+/// Tried in order for characters `FONT` doesn't have
+const FALLBACK_FONTS: &[&str] = &["data/fonts/NotoSans-Bold.ttf", "data/fonts/DroidSansFallback.ttf"];
 const FONT_SIZE: i32 = 36;
 
-pub fn startup(_screen_width: i32, _screen_height: i32) -> *mut () {
-    const SPRITE_FOLDER: &str = "data/sprites/size_16/";
+fn load_font(canvas_scale: i32) -> FontBitmaps {
+    let mut font = FontBitmaps::new(FONT, FONT_SIZE / canvas_scale).unwrap();
+    for path in FALLBACK_FONTS {
+        // without a fallback there are just more replacement glyphs
+        let _ = font.add_fallback(path);
+    }
+    font
+}
 
+pub fn startup(_screen_width: i32, _screen_height: i32) -> *mut () {
     let tile_size = 16;
     let screen_scale = 4;
     let hook_bmp = Bitmap::load(format!("{}{}", SPRITE_FOLDER, "hook.png")).unwrap();
@@ -116,16 +279,40 @@ pub fn startup(_screen_width: i32, _screen_height: i32) -> *mut () {
                 [0.0                       , (-tile_size as f32).recip()],
             ]),
             camera: (0.0, 0.0).into(),
+            zoom: 1.0,
         },
-
-        state: GameState::LevelEditor,
-        tilemap: Tilemap::load("data/levels/map_00").unwrap_or_else(|_| Tilemap::new(15, 15)),
-        tile_info: TileInfo {
-            size: tile_size,
-            screen_width: 0.0,
-            screen_height: 0.0,
-            bmps: [Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_ground.png")).unwrap(); 1],
-        },
+        camera: player_camera(),
+        editor_camera: Camera::new((0.0, 0.0).into()),
+
+        states: StateStack::new(GameState::Title),
+        options: Options::default(),
+        quit: false,
+        level: Level::load(in_levels_folder(LEVEL_FILE))
+            .or_else(|_| Level::load(OLD_LEVEL_PATH))
+            .unwrap_or_else(|_| Level::new(15, 15)),
+        level_file: Some(LEVEL_FILE.into()),
+        saved_level: Vec::new(),
+        level_index: LevelIndex::load(in_levels_folder(LEVEL_INDEX_FILE)).unwrap_or_default(),
+        editor_dialog: None,
+        tileset: default_tileset(),
+        tileset_imported: false,
+        editor_layer: 0,
+        editor_tile: Tile::EMPTY,
+        editor_entity: None,
+        editor_spawn: None,
+        spawn_drag: None,
+        editor_tool: Tool::Pencil,
+        editor_drag: None,
+        editor_resize: None,
+        selection: None,
+        floating: None,
+        clipboard: None,
+        stamps: StampLibrary::load(in_levels_folder(STAMPS_FILE)).unwrap_or_default(),
+        stamp_index: 0,
+        history: History::new(EDITOR_HISTORY_LIMIT),
+        show_issues: false,
+        world: Vec::new(),
+        world_index: 0,
 
         player: Entity::new_character((2.5, 2.5).into(), 1),
         hook: {
@@ -143,7 +330,13 @@ pub fn startup(_screen_width: i32, _screen_height: i32) -> *mut () {
 
         player_attack_counter: 0.0,
 
-        enemies: [Entity::new_character((3.5, 1.5).into(), 5); 1],
+        enemies: Vec::new(),
+        pickups: Vec::new(),
+        triggers: Vec::new(),
+        checkpoint: None,
+        play_start: None,
+        editor_snapshot: None,
+        coins: 0,
         player_bmps: PlayerBmps {
             right: Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_player_right.png")).unwrap(),
             left: Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_player_left.png")).unwrap(),
@@ -151,10 +344,15 @@ pub fn startup(_screen_width: i32, _screen_height: i32) -> *mut () {
         },
         enemy_bmp_right: Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_enemy_right.png")).unwrap(),
         enemy_bmp_left: Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_enemy_left.png")).unwrap(),
-        font_bmp: render::text::FontBitmaps::new(FONT, FONT_SIZE / screen_scale).unwrap(),
+        font_bmp: load_font(screen_scale),
+        ui: UiState::default(),
         text: String::new(),
         text_timer: 0.0,
     });
+    result.saved_level = result.level.to_bytes();
+    result.editor_camera.pos = V2f::from(result.level.dim()) * 0.5;
+    result.editor_layer = result.level.collision_layer_index();
+    result.editor_tile = result.tileset.tiles().next().unwrap_or(Tile(1));
     restart(result.as_mut());
 
     // FIXME: ugh
@@ -179,30 +377,51 @@ pub fn update_and_render(
         window.toggle_fullscreen();
     }
     if input.keyboard[KBKey::F12].pressed() {
-        data.canvas_info.scale = if data.canvas_info.scale == 1 { 4 } else { 1 };
-        data.font_bmp = render::text::FontBitmaps::new(FONT, FONT_SIZE / data.canvas_info.scale)
-            .unwrap();
+        data.options.big_pixels = !data.options.big_pixels;
+    }
+    let scale = if data.options.big_pixels { 4 } else { 1 };
+    if scale != data.canvas_info.scale {
+        data.canvas_info.scale = scale;
+        data.font_bmp = load_font(scale);
     }
 
     let mut window_bmp = Bitmap::from(window_buffer);
     // FIXME: alloc dealloc every frame
     let mut canvas = Bitmap::with_dimensions(window_bmp.width() / data.canvas_info.scale, window_bmp.height() / data.canvas_info.scale);
 
-    if data.canvas_info.width != canvas.width() {
-        data.canvas_info.width = canvas.width();
-        data.tile_info.screen_width =
-            data.canvas_info.width as f32 / data.tile_info.size as f32;
-    }
-    if data.canvas_info.height != canvas.height() {
-        data.canvas_info.height = canvas.height();
-        data.tile_info.screen_height =
-            data.canvas_info.height as f32 / data.tile_info.size as f32;
+    data.canvas_info.width = canvas.width();
+    data.canvas_info.height = canvas.height();
+
+    data.tileset.update(dt);
+
+    // the game pauses when the window loses focus
+    if !window.is_active() && data.states.top() == GameState::Playing {
+        data.states.push(GameState::Paused);
     }
 
-    let info = match data.state {
-        GameState::Playing => playing(&mut canvas, input, data, dt),
-        GameState::LevelEditor => level_editor(&mut canvas, input, data, dt),
-    };
+    // only the top state is updated, the one under an overlay is just drawn
+    let drawn = data.states.drawn();
+    let mut info = String::new();
+    for (i, &state) in drawn.iter().enumerate() {
+        let top = i + 1 == drawn.len();
+        match state {
+            GameState::Title => menus::title(&mut canvas, input, data, top),
+            GameState::Playing => {
+                if top {
+                    update_playing(input, data, dt);
+                }
+                info = draw_playing(&mut canvas, data);
+            },
+            GameState::LevelEditor => info = level_editor(&mut canvas, input, data, dt),
+            GameState::Paused => menus::pause(&mut canvas, input, data),
+            GameState::Options => menus::options(&mut canvas, input, data),
+            GameState::GameOver => menus::game_over(&mut canvas, input, data),
+            GameState::LevelComplete => menus::level_complete(&mut canvas, input, data),
+        }
+    }
+    if data.quit {
+        window.close();
+    }
 
     render::scale_up(&canvas, &mut window_bmp, data.canvas_info.scale);
 
@@ -220,21 +439,53 @@ pub fn update_and_render(
     std::mem::forget(window_bmp);
 }
 
-#[allow(clippy::useless_format)]
-fn playing(
-    canvas: &mut Bitmap,
-    input:  &Input,
-    data:   &mut GameData,
-    dt:     f32,
-) -> String {
+/// Goes back to the editor, whatever happened while playing is dropped
+fn back_to_editor(data: &mut GameData) {
+    data.states.reset(GameState::LevelEditor);
+    if let Some(snapshot) = data.editor_snapshot.take() {
+        data.level.restore(snapshot.level);
+        data.editor_camera = snapshot.camera;
+    }
+}
+
+/// Player lost a life: back to the checkpoint with one hp less, or game over when it was the last
+fn die(data: &mut GameData) {
+    let hp = data.player.health.hp - 1;
+    data.camera.add_trauma(0.5 * data.options.screen_shake);
+    if hp > 0 {
+        let (pickups, coins) = (mem::take(&mut data.pickups), data.coins);
+        restart(data);
+        data.player.health.hp = hp;
+        data.pickups = pickups;
+        data.coins = coins;
+    } else {
+        data.player.health.hp = 0;
+        data.states.push(GameState::GameOver);
+    }
+}
+
+/// Hitbox of the player's attack and its offset from the player, while attacking
+fn attack_box(data: &GameData) -> Option<(AABB<f32>, V2f)> {
+    if data.player_attack_counter <= 0.0 {
+        return None;
+    }
+    let attack_offset = match data.player.facing {
+        Direction::Left => (-1.0, 0.0).into(),
+        Direction::Right => (1.0, 0.0).into(),
+    };
+    Some((data.player.collision_aabb().translate(attack_offset), attack_offset))
+}
+
+fn update_playing(input: &Input, data: &mut GameData, dt: f32) {
     use KBKey::*;
 
     if input.keyboard[K].pressed() && input.keyboard[Ctrl].is_down() {
-        data.state = GameState::LevelEditor;
-        render::clear(canvas, Color::BLACK);
+        back_to_editor(data);
+        return;
     }
     if input.keyboard[Escape].pressed() {
-        restart(data);
+        data.states.push(GameState::Paused);
+        return;
     }
 
     // attack update ///////////////////////////////////////////////////////////////
@@ -246,12 +497,9 @@ fn playing(
         data.player_attack_counter = 0.3;
     }
 
-    let attack_aabb = if data.player_attack_counter > 0.0 {
-        let attack_offset = match data.player.facing {
-            Direction::Left => (-1.0, 0.0).into(),
-            Direction::Right => (1.0, 0.0).into(),
-        };
-        let attack_aabb = data.player.collision_aabb().translate(attack_offset);
+    if let Some((attack_aabb, _)) = attack_box(data) {
+        let shake = data.options.screen_shake;
+        let camera = &mut data.camera;
         data.enemies.iter_mut()
             .filter(|enemy| enemy.health.hp > 0 && aabb_collision(attack_aabb, enemy.collision_aabb()))
             .for_each(|enemy| match enemy.health.knockback {
@@ -262,12 +510,10 @@ fn playing(
                         time_remaining: 1.0,
                         just_hit: true,
                     };
+                    camera.add_trauma(0.5 * shake);
                 },
             });
-        Some((attack_aabb, attack_offset))
-    } else {
-        None
-    };
+    }
 
     // player movement //////////////////////////////////////////////////////////
     let player_command = Some(MovementCommand::Platformer {
@@ -281,7 +527,39 @@ fn playing(
     if let Some(MovementCommand::Platformer { dir: Some(dir), .. }) = player_command {
         data.player.facing  = dir;
     }
-    data.player.mov(&data.tilemap, player_command, dt);
+    data.player.mov(data.level.collision(), &data.tileset, player_command, dt);
+    if data.player.touches_hazard(data.level.collision(), &data.tileset) {
+        die(data);
+        return;
+    }
+
+    // pickups and triggers //////////////////////////////////////////////////
+    let player_aabb = data.player.collision_aabb();
+    let (taken, left): (Vec<_>, Vec<_>) = mem::take(&mut data.pickups).into_iter()
+        .partition(|pickup| aabb_collision(player_aabb, pickup.aabb()));
+    data.pickups = left;
+    for pickup in taken {
+        match pickup.item {
+            Item::Coin => data.coins += 1,
+            Item::Heart => data.player.health.hp += 1,
+        }
+    }
+    let (mut hazard, mut complete) = (false, false);
+    for trigger in data.triggers.iter().filter(|trigger| aabb_collision(player_aabb, trigger.area)) {
+        match trigger.action {
+            TriggerAction::Checkpoint(pos) => data.checkpoint = Some(pos),
+            TriggerAction::Hazard => hazard = true,
+            TriggerAction::Exit => complete = true,
+        }
+    }
+    if hazard {
+        die(data);
+        return;
+    }
+    if complete {
+        data.states.push(GameState::LevelComplete);
+        return;
+    }
 
     // enemy movement //////////////////////////////////////////////////////
     for enemy in data.enemies.iter_mut().filter(|x| x.health.hp > 0) {
@@ -333,75 +611,559 @@ fn playing(
         if let Some(MovementCommand::Platformer { dir: Some(dir), .. }) = enemy_command {
             enemy.facing = dir;
         }
-        enemy.mov(&data.tilemap, enemy_command, dt);
+        enemy.mov(data.level.collision(), &data.tileset, enemy_command, dt);
     }
 
     ///////////////////////////////////////////////////////////////
     /* camera movement */ {
-        let screen_center = V2f::new(data.tile_info.screen_width, data.tile_info.screen_height) * 0.5;
+        let facing = match data.player.facing {
+            Direction::Left => -1.0,
+            Direction::Right => 1.0,
+        };
+        data.camera.bounds = Some(AABB { min: (0.0, 0.0).into(), max: data.level.dim().into() });
+        data.camera.follow = camera_follow(&data.options);
+        data.camera.update(data.canvas_info.unzoomed_view_size(), dt);
+        data.camera.follow(data.player.pos, facing, dt);
 
         // camera origin is bottom left corner of a screen
-        data.canvas_info.camera = data.player.pos - screen_center;
-        data.canvas_info.camera.x = utils::clamp(
-            data.canvas_info.camera.x,
-            0.0,
-            data.tilemap.width() as f32 - data.tile_info.screen_width,
-        );
-        data.canvas_info.camera.y = utils::clamp(
-            data.canvas_info.camera.y,
-            0.0,
-            data.tilemap.height() as f32 - data.tile_info.screen_height,
-        );
-    }
-
-    // draw ////////////////////////////////////////////////////////
+        data.canvas_info.camera = data.camera.view().min;
+        data.canvas_info.zoom = data.camera.zoom();
+    }
+}
+
+#[allow(clippy::useless_format)]
+fn draw_playing(canvas: &mut Bitmap, data: &GameData) -> String {
     render::clear(canvas, Color::BLACK);
 
-    data.tilemap.draw(canvas, &data.canvas_info, &data.tile_info);
+    data.level.draw_background(canvas, &data.canvas_info, &data.tileset);
 
     let player_bmp = match data.player.facing  {
         Direction::Right => &data.player_bmps.right,
         Direction::Left => &data.player_bmps.left,
     };
 
-    let player_up_left = render::v2_to_screen(data.player.pos, &data.canvas_info) - V2::diag(data.tile_info.size / 2);
-    render::draw_bmp(canvas, player_bmp, player_up_left);
-
-    let player_collision_rect = render::aabb_to_screen(data.player.collision_aabb(), &data.canvas_info);
-    render::draw_rect(canvas, player_collision_rect.min, player_collision_rect.max, Color::YELLOW, 1);
+    world::draw_bmp_centered(canvas, &data.canvas_info, player_bmp, data.player.pos);
+    if data.options.show_boxes {
+        world::draw_rect(canvas, &data.canvas_info, data.player.collision_aabb(), Color::YELLOW, 1);
+    }
 
     // attack collision box
+    let attack_aabb = attack_box(data);
     if let Some((attack_aabb, _)) = attack_aabb {
-        let AABB { min, max } = render::aabb_to_screen(attack_aabb, &data.canvas_info);
-        render::fill_rect(canvas, min, max, { let mut c = Color::RED; c.a = 0x77; c });
+        world::fill_rect(canvas, &data.canvas_info, attack_aabb, { let mut c = Color::RED; c.a = 0x77; c });
     }
 
     if let Some((_, attack_offset)) = attack_aabb {
         let bmp = &data.player_bmps.hook;
         let attack_pos = data.player.collision_aabb().top_left() + attack_offset;
-        let attack_screen_pos = render::v2_to_screen(attack_pos, &data.canvas_info);
-        render::draw_bmp(canvas, bmp, attack_screen_pos);
+        world::draw_bmp(canvas, &data.canvas_info, bmp, attack_pos);
     }
 
     for enemy in &data.enemies {
         match enemy.health.knockback {
             Knockback::Knocked { time_remaining, .. } if (time_remaining * 20.0).sin() > 0.0 => (),
             _ => {
-                let pos = render::v2_to_screen(enemy.pos, &data.canvas_info);
                 let bmp = match enemy.facing {
                     Direction::Right => &data.enemy_bmp_right,
                     Direction::Left => &data.enemy_bmp_left,
                 };
-                render::draw_bmp(canvas, bmp, pos - V2::diag(data.tile_info.size / 2));
+                world::draw_bmp_centered(canvas, &data.canvas_info, bmp, enemy.pos);
             },
         }
 
         // enemy collision box
-        let AABB { min, max } = render::aabb_to_screen(enemy.collision_aabb(), &data.canvas_info);
-        render::draw_rect(canvas, min, max, Color::YELLOW, 1);
+        if data.options.show_boxes {
+            world::draw_rect(canvas, &data.canvas_info, enemy.collision_aabb(), Color::YELLOW, 1);
+        }
+    }
+
+    for pickup in &data.pickups {
+        let color = match pickup.item {
+            Item::Coin => Color::YELLOW,
+            Item::Heart => Color::RED,
+        };
+        world::fill_rect(canvas, &data.canvas_info, pickup.aabb(), color);
+    }
+
+    // trigger areas, exits are always shown
+    for trigger in &data.triggers {
+        let color = match trigger.action {
+            TriggerAction::Checkpoint(_) if data.options.show_boxes => Color::WHITE,
+            TriggerAction::Hazard if data.options.show_boxes => Color::RED,
+            TriggerAction::Exit => Color::PURPLE,
+            _ => continue,
+        };
+        world::draw_rect(canvas, &data.canvas_info, trigger.area, color, 1);
+    }
+
+    data.level.draw_foreground(canvas, &data.canvas_info, &data.tileset);
+
+    // the last hp is a warning
+    let hp_color = if data.player.health.hp <= 1 { "{red}" } else { "" };
+    let hud = format!("{}hp: {}{{}}  coins: {}", hp_color, data.player.health.hp, data.coins);
+    let style = TextStyle::default().shadowed(Color::BLACK);
+    data.font_bmp.draw_text(canvas, V2::diag(4), &hud, None, style);
+
+    format!(" {} coins: {}", data.player.pos.x + data.player.origin_to_bottom_left.x, data.coins)
+}
+
+/// Message of a level issue, red for errors and yellow for warnings
+fn issue_text(issue: &Issue) -> String {
+    let color = if issue.severity == Severity::Error { "red" } else { "yellow" };
+    format!("{{{}}}{}", color, issue.message)
+}
+
+/// Half of the size of a resize handle, in canvas pixels
+const HANDLE_SIZE: i32 = 2;
+
+/// Handles on the corners and edges of a level of size `dim` and their world positions
+fn resize_handles(dim: V2i) -> Vec<(Anchor, V2f)> {
+    let mut result = Vec::new();
+    for y in 0..=2 {
+        for x in 0..=2 {
+            let handle = Anchor { x, y };
+            if handle != Anchor::CENTER {
+                let pos = V2f::new(dim.x as f32 * x as f32 * 0.5, dim.y as f32 * y as f32 * 0.5);
+                result.push((handle, pos));
+            }
+        }
+    }
+    result
+}
+
+/// Level rectangle in tiles after `handle` is dragged to `mouse`,
+/// from one to `MAX_SIDE` tiles on each side
+fn resized_rect(dim: V2i, handle: Anchor, mouse: V2f) -> AABB<i32> {
+    let mut rect = AABB { min: V2::new(0, 0), max: dim };
+    let p: V2i = mouse.round().into();
+    match handle.x {
+        0 => rect.min.x = utils::clamp(p.x, rect.max.x - MAX_SIDE, rect.max.x - 1),
+        2 => rect.max.x = utils::clamp(p.x, rect.min.x + 1, rect.min.x + MAX_SIDE),
+        _ => (),
+    }
+    match handle.y {
+        0 => rect.min.y = utils::clamp(p.y, rect.max.y - MAX_SIDE, rect.max.y - 1),
+        2 => rect.max.y = utils::clamp(p.y, rect.min.y + 1, rect.min.y + MAX_SIDE),
+        _ => (),
+    }
+    rect
+}
+
+/// Resizes `level` after `handle` is dragged to `mouse` and released,
+/// the opposite handle stays in place. Returns how far the old tiles moved
+fn release_handle(history: &mut History, level: &mut Level, handle: Anchor, mouse: V2f) -> V2i {
+    let rect = resized_rect(level.dim(), handle, mouse);
+    let size = rect.max - rect.min;
+    let offset = Anchor { x: 2 - handle.x, y: 2 - handle.y }.offset(level.dim(), size);
+    if size != level.dim() || offset != V2::new(0, 0) {
+        history.resize(level, size.x, size.y, offset);
     }
+    offset
+}
+
+fn palette_selection(data: &GameData) -> PaletteItem {
+    data.editor_entity.map_or(PaletteItem::Tile(data.editor_tile), PaletteItem::Entity)
+}
 
-    format!(" {}", data.player.pos.x + data.player.origin_to_bottom_left.x)
+fn select_palette_item(data: &mut GameData, item: PaletteItem) {
+    match item {
+        PaletteItem::Tile(tile) => {
+            data.editor_tile = tile;
+            data.editor_entity = None;
+            data.editor_spawn = None;
+        },
+        PaletteItem::Entity(kind) => data.editor_entity = Some(kind),
+    }
+}
+
+/// Bitmap the editor shows for an entity kind
+fn entity_bmp<'a>(data: &'a GameData, kind: &str) -> Option<&'a Bitmap> {
+    match kind {
+        "player" => Some(&data.player_bmps.right),
+        "enemy" => Some(&data.enemy_bmp_right),
+        _ => None,
+    }
+}
+
+/// Sets tiles of `area` to empty
+fn erase(history: &mut History, level: &mut Level, layer: usize, area: AABB<i32>) {
+    for y in area.min.y..area.max.y {
+        for x in area.min.x..area.max.x {
+            history.set_tile(level, layer, V2::new(x, y), Tile::EMPTY);
+        }
+    }
+}
+
+/// Puts `stamp` with its bottom left corner at `pos`, empty tiles of it are left out
+fn paste(history: &mut History, level: &mut Level, layer: usize, stamp: &Stamp, pos: V2i) {
+    for (p, tile) in stamp.cells() {
+        history.set_tile(level, layer, pos + p, tile);
+    }
+}
+
+/// Copy, cut and paste of the selection and the stamp library
+fn selection_and_stamps(input: &Input, data: &mut GameData) {
+    let ctrl = input.keyboard[KBKey::Ctrl].is_down();
+    let selected = || data.selection.and_then(|area| {
+        data.level.tile_layers().nth(data.editor_layer).and_then(|layer| Stamp::copy(&layer.tiles, area))
+    });
+
+    if ctrl && (input.keyboard[KBKey::C].pressed() || input.keyboard[KBKey::X].pressed()) {
+        if let Some(stamp) = selected() {
+            data.clipboard = Some(stamp);
+            data.text_timer = 1.0;
+            data.text = "Copied".into();
+        }
+    }
+    let erase_selection = (ctrl && input.keyboard[KBKey::X].pressed()) || input.keyboard[KBKey::Delete].pressed();
+    if let (true, Some(area), None) = (erase_selection, data.selection, data.editor_drag) {
+        erase(&mut data.history, &mut data.level, data.editor_layer, area);
+        data.history.end_group();
+    }
+    if ctrl && input.keyboard[KBKey::V].pressed() && data.clipboard.is_some() && data.editor_drag.is_none() {
+        data.editor_tool = Tool::Stamp;
+    }
+    if input.keyboard[KBKey::Escape].pressed() && data.editor_drag.is_none() {
+        data.selection = None;
+    }
+
+    if let (false, Some(stamp)) = (ctrl, &mut data.clipboard) {
+        if input.keyboard[KBKey::H].pressed() {
+            *stamp = stamp.flipped_x();
+        }
+        if input.keyboard[KBKey::V].pressed() {
+            *stamp = stamp.flipped_y();
+        }
+        if input.keyboard[KBKey::R].pressed() {
+            *stamp = stamp.rotated();
+        }
+    }
+
+    // library
+    if ctrl && input.keyboard[KBKey::B].pressed() {
+        if let Some(stamp) = &mut data.clipboard {
+            if stamp.name.is_empty() {
+                stamp.name = format!("stamp_{}", data.stamps.stamps.len());
+            }
+            data.stamps.stamps.push(stamp.clone());
+            data.stamp_index = data.stamps.stamps.len() - 1;
+            data.text_timer = 1.0;
+            data.text = match data.stamps.save(in_levels_folder(STAMPS_FILE)) {
+                Ok(()) => "Stamp saved".into(),
+                //TODO: error info
+                Err(_) => "{red}Error saving stamps".into(),
+            };
+        }
+    }
+    let count = data.stamps.stamps.len();
+    if count > 0 && !ctrl && data.editor_drag.is_none() {
+        let next = match (input.keyboard[KBKey::N].pressed(), input.keyboard[KBKey::M].pressed()) {
+            (true, false) => Some((data.stamp_index + count - 1) % count),
+            (false, true) => Some((data.stamp_index + 1) % count),
+            _ => None,
+        };
+        if let Some(next) = next {
+            data.stamp_index = next;
+            data.clipboard = Some(data.stamps.stamps[next].clone());
+            data.editor_tool = Tool::Stamp;
+        }
+    }
+}
+
+/// `editor_spawn` held by the left mouse button
+struct SpawnDrag {
+    /// Spawns before the button was pressed, for undo
+    before: Vec<Spawn>,
+    /// From the mouse to the spawn position
+    offset: V2f,
+    /// Mouse position when the button was pressed, the spawn moves once the mouse leaves it
+    press: V2f,
+    moved: bool,
+}
+
+struct EditorSnapshot {
+    level: LevelSnapshot,
+    camera: Camera,
+}
+
+/// Switches from the editor to playing, the player starts at `start` instead of its spawn
+fn start_playing(data: &mut GameData, start: Option<V2f>) {
+    data.history.end_group();
+    data.editor_snapshot = Some(EditorSnapshot { level: data.level.snapshot(), camera: data.editor_camera.clone() });
+    data.states.push(GameState::Playing);
+    data.play_start = start;
+    data.checkpoint = None;
+    restart(data);
+}
+
+/// Dialog of the editor, see `file_dialog`
+enum Dialog {
+    /// Files of the levels folder, the selected one is highlighted
+    Open { files: Vec<String>, selected: usize },
+    /// File name being typed, without the extension
+    SaveAs { name: String },
+    /// Unsaved changes would be lost by the action
+    Discard(FileAction),
+}
+
+enum FileAction {
+    New,
+    Open(String),
+    /// From `TILED_PATH`
+    ImportTiled,
+    /// From `LDTK_PATH`
+    ImportLdtk,
+}
+
+/// Tile set of levels that weren't imported
+fn default_tileset() -> TileSet {
+    TileSet::load(TILESET_PATH).unwrap_or_else(|_| {
+        TileSet::with_ground(Bitmap::load(format!("{}{}", SPRITE_FOLDER, "test_ground.png")).unwrap())
+    })
+}
+
+/// Replaces the level being edited with one that has no unsaved changes
+fn set_level(data: &mut GameData, level: Level, file: Option<String>) {
+    if data.tileset_imported {
+        set_tileset(data, default_tileset(), false);
+    }
+    data.saved_level = level.to_bytes();
+    data.level = level;
+    data.level_file = file;
+    data.world.clear();
+    data.history.clear();
+    data.editor_spawn = None;
+    data.selection = None;
+    data.editor_layer = data.level.collision_layer_index();
+    data.editor_camera.pos = V2f::from(data.level.dim()) * 0.5;
+    data.checkpoint = None;
+    restart(data);
+}
+
+/// Replaces the level being edited and the tile set with imported ones,
+/// neither of them is saved anywhere yet
+fn set_imported_level(data: &mut GameData, level: Level, tileset: TileSet) {
+    set_level(data, level, None);
+    data.saved_level = Vec::new();
+    set_tileset(data, tileset, true);
+}
+
+fn set_tileset(data: &mut GameData, tileset: TileSet, imported: bool) {
+    data.tileset = tileset;
+    data.tileset_imported = imported;
+    data.editor_tile = data.tileset.tiles().next().unwrap_or(Tile(1));
+}
+
+/// Does `action`, unless it would lose unsaved changes and they aren't to be `discard`ed,
+/// then returns the dialog that asks about it
+fn file_action(data: &mut GameData, action: FileAction, discard: bool) -> Option<Dialog> {
+    if !discard && data.level.to_bytes() != data.saved_level {
+        return Some(Dialog::Discard(action));
+    }
+    match action {
+        FileAction::New => set_level(data, Level::new(15, 15), None),
+        FileAction::Open(file) => match Level::load(in_levels_folder(&file)) {
+            Ok(level) => set_level(data, level, Some(file)),
+            Err(_) => {
+                data.text_timer = 1.0;
+                //TODO: error info
+                data.text = "{red}Error opening level".into();
+            },
+        },
+        FileAction::ImportTiled => {
+            data.text_timer = 1.0;
+            data.text = match tiled::import(TILED_PATH) {
+                Ok(map) => {
+                    set_imported_level(data, map.level, map.tileset);
+                    "Imported".into()
+                },
+                //TODO: error info
+                Err(_) => "{red}Error importing map".into(),
+            };
+        },
+        FileAction::ImportLdtk => {
+            data.text_timer = 1.0;
+            data.text = match ldtk::import(LDTK_PATH) {
+                Ok(mut project) if !project.levels.is_empty() => {
+                    let level = mem::replace(&mut project.levels[0].level, Level::new(1, 1));
+                    set_imported_level(data, level, project.tileset);
+                    data.world = project.levels;
+                    data.world_index = 0;
+                    "Imported".into()
+                },
+                //TODO: error info
+                _ => "{red}Error importing project".into(),
+            };
+        },
+    }
+    None
+}
+
+/// Saves the level to `file` in the levels folder and adds it to the index
+fn save_level(data: &mut GameData, file: String) {
+    data.text_timer = 1.0;
+    if data.level.save(in_levels_folder(&file)).is_err() {
+        //TODO: error info
+        data.text = "{red}Error saving level".into();
+        return;
+    }
+    data.saved_level = data.level.to_bytes();
+    data.level_index.add(&file);
+    data.level_file = Some(file);
+    data.text = match data.level_index.save(in_levels_folder(LEVEL_INDEX_FILE)) {
+        Ok(()) => "Saved".into(),
+        Err(_) => "{red}Error saving level index".into(),
+    };
+}
+
+fn open_dialog(data: &mut GameData) -> Option<Dialog> {
+    let skip = [LEVEL_INDEX_FILE, STAMPS_FILE];
+    match level_index::level_files(LEVELS_FOLDER, level::TEXT_EXTENSION, &data.level_index, &skip) {
+        Ok(files) => {
+            let selected = files.iter().position(|file| Some(file) == data.level_file.as_ref()).unwrap_or(0);
+            Some(Dialog::Open { files, selected })
+        },
+        Err(_) => {
+            data.text_timer = 1.0;
+            data.text = "{red}Error reading levels folder".into();
+            None
+        },
+    }
+}
+
+/// Handles input of `dialog` and draws it instead of the editor, returns it if it stays open
+fn file_dialog(canvas: &mut Bitmap, input: &Input, data: &mut GameData, dialog: Dialog) -> Option<Dialog> {
+    let pressed = |key: KBKey| input.keyboard[key].pressed();
+    let CanvasPos(mouse) = data.canvas_info.screen_to_canvas(ScreenPos::from(input.mouse.pos()));
+
+    render::clear(canvas, Color::BLACK);
+    render::draw_rect(canvas, (0, 0).into(), canvas.dim(), Color::YELLOW, 2);
+    let center = canvas.dim() / 2;
+
+    match dialog {
+        Dialog::Open { mut files, mut selected } => {
+            let ctrl = input.keyboard[KBKey::Ctrl].is_down();
+            if !ctrl {
+                match (pressed(KBKey::Up), pressed(KBKey::Down)) {
+                    (true, false) => selected = selected.saturating_sub(1),
+                    (false, true) => selected = (selected + 1).min(files.len().saturating_sub(1)),
+                    _ => (),
+                }
+            }
+
+            let reorder = match (pressed(KBKey::PageUp), pressed(KBKey::PageDown)) {
+                (true, false) => Some(false),
+                (false, true) => Some(true),
+                _ => None,
+            };
+            let side = [(KBKey::Left, Side::Left), (KBKey::Right, Side::Right), (KBKey::Up, Side::Up), (KBKey::Down, Side::Down)]
+                .iter()
+                .find(|&&(key, _)| ctrl && pressed(key))
+                .map(|&(_, side)| side);
+            let mut index_changed = false;
+            if let (Some(later), Some(file)) = (reorder, files.get(selected).cloned()) {
+                data.level_index.add(&file);
+                data.level_index.move_level(&file, later);
+                index_changed = true;
+            }
+            // connects a side of the level being edited to the selected one
+            if let (Some(side), Some(from), Some(to)) = (side, data.level_file.clone(), files.get(selected).cloned()) {
+                if from != to {
+                    data.level_index.connect(&from, side, &to);
+                    index_changed = true;
+                }
+            }
+            if index_changed {
+                if data.level_index.save(in_levels_folder(LEVEL_INDEX_FILE)).is_err() {
+                    data.text_timer = 1.0;
+                    data.text = "{red}Error saving level index".into();
+                }
+                let file = files.get(selected).cloned();
+                if let Some(Dialog::Open { files: new_files, .. }) = open_dialog(data) {
+                    selected = new_files.iter().position(|f| Some(f) == file.as_ref()).unwrap_or(0);
+                    files = new_files;
+                }
+            }
+
+            let items: Vec<String> = files.iter()
+                .map(|file| {
+                    let order = data.level_index.levels.iter()
+                        .position(|level| level == file)
+                        .map_or_else(|| "-".to_string(), |i| (i + 1).to_string());
+                    let connections: Vec<String> = Side::ALL.iter()
+                        .filter_map(|&side| data.level_index.neighbour(file, side).map(|to| format!("{}: {}", side.name(), to)))
+                        .collect();
+                    format!("{}. {}   {}", order, file, connections.join(", "))
+                })
+                .collect();
+
+            let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse);
+            ui.begin_window("Open level", center, V2::new(0.5, 0.5));
+            ui.label("Open level. Click to select, Enter to open, Escape to cancel.");
+            ui.label("Page Up and Page Down to reorder, Ctrl and arrows to connect the open level.");
+            if files.is_empty() {
+                ui.label("No levels");
+            }
+            let rows = ((center.y * 2) / ui.line_height() - 6).max(3) as usize;
+            ui.list("files", &items, &mut selected, rows);
+            let open = ui.button("Open");
+            ui.same_line();
+            let cancel = ui.button("Cancel");
+            ui.end_window();
+            ui.finish();
+
+            if cancel || pressed(KBKey::Escape) {
+                None
+            } else if (open || pressed(KBKey::Enter)) && !files.is_empty() {
+                file_action(data, FileAction::Open(files[selected].clone()), false)
+            } else {
+                Some(Dialog::Open { files, selected })
+            }
+        },
+        Dialog::SaveAs { mut name } => {
+            let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse);
+            ui.begin_window("Save as", center, V2::new(0.5, 0.5));
+            ui.label("Save level as");
+            ui.focus("##name");
+            let width = data.font_bmp.height() * 12;
+            let entered = ui.text_field("##name", &mut name, width);
+            ui.same_line();
+            ui.label(&format!(".{}", level::TEXT_EXTENSION));
+            let save = ui.button("Save");
+            ui.same_line();
+            let cancel = ui.button("Cancel");
+            ui.end_window();
+            ui.finish();
+
+            name.retain(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+            if cancel || pressed(KBKey::Escape) {
+                None
+            } else if (save || entered) && !name.is_empty() {
+                save_level(data, format!("{}.{}", name, level::TEXT_EXTENSION));
+                None
+            } else {
+                Some(Dialog::SaveAs { name })
+            }
+        },
+        Dialog::Discard(action) => {
+            let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse);
+            ui.begin_window("Discard", center, V2::new(0.5, 0.5));
+            ui.label("There are unsaved changes, they will be lost.");
+            let discard = ui.button("Continue");
+            ui.same_line();
+            let cancel = ui.button("Cancel");
+            ui.end_window();
+            ui.finish();
+
+            if discard || pressed(KBKey::Y) {
+                file_action(data, action, true)
+            } else if cancel || pressed(KBKey::N) || pressed(KBKey::Escape) {
+                None
+            } else {
+                Some(Dialog::Discard(action))
+            }
+        },
+    }
 }
 
 #[allow(clippy::useless_format)]
@@ -412,21 +1174,92 @@ fn level_editor(
     dt:     f32,
 ) -> String {
     if input.keyboard[KBKey::K].pressed() && input.keyboard[KBKey::Ctrl].is_down() {
-        data.state = GameState::Playing;
+        start_playing(data, None);
     }
 
-    if input.keyboard[KBKey::S].pressed() && input.keyboard[KBKey::Ctrl].is_down() {
-        let save_result = data.tilemap.save("data/levels/map_00");
-        data.text_timer = 1.0;
-        data.text = if save_result.is_err() {
-            //TODO: error info
-            "Error saving bitmap".into()
-        } else {
-            "Saved".into()
+    if let Some(dialog) = data.editor_dialog.take() {
+        data.editor_dialog = file_dialog(canvas, input, data, dialog);
+        return String::new();
+    }
+
+    if input.keyboard[KBKey::Ctrl].is_down() {
+        if input.keyboard[KBKey::S].pressed() {
+            match (&data.level_file, input.keyboard[KBKey::Shift].is_down()) {
+                (Some(file), false) => save_level(data, file.clone()),
+                _ => data.editor_dialog = Some(Dialog::SaveAs { name: String::new() }),
+            }
+        }
+        if input.keyboard[KBKey::N].pressed() {
+            data.editor_dialog = file_action(data, FileAction::New, false);
+        }
+        if input.keyboard[KBKey::O].pressed() {
+            data.editor_dialog = open_dialog(data);
+        }
+        if input.keyboard[KBKey::I].pressed() {
+            data.editor_dialog = file_action(data, FileAction::ImportTiled, false);
+        }
+        if input.keyboard[KBKey::L].pressed() {
+            data.editor_dialog = file_action(data, FileAction::ImportLdtk, false);
+        }
+    }
+
+    // switch between levels of the imported LDtk world
+    if !data.world.is_empty() {
+        let count = data.world.len();
+        let next = match (input.keyboard[KBKey::PageUp].pressed(), input.keyboard[KBKey::PageDown].pressed()) {
+            (true, false) => Some((data.world_index + count - 1) % count),
+            (false, true) => Some((data.world_index + 1) % count),
+            _ => None,
         };
+        if let Some(next) = next.filter(|&next| next != data.world_index) {
+            let level = mem::replace(&mut data.world[next].level, Level::new(1, 1));
+            data.world[data.world_index].level = mem::replace(&mut data.level, level);
+            data.world_index = next;
+            data.history.clear();
+            data.editor_spawn = None;
+            data.editor_layer = data.level.collision_layer_index();
+            data.editor_camera.pos = V2f::from(data.level.dim()) * 0.5;
+            restart(data);
+        }
     }
 
-    let mut new_tilemap_size = data.tilemap.dim();
+    if input.keyboard[KBKey::Ctrl].is_down() {
+        let undone = match (input.keyboard[KBKey::Z].pressed(), input.keyboard[KBKey::Y].pressed()) {
+            (true, false) => Some(data.history.undo(&mut data.level)),
+            (false, true) => Some(data.history.redo(&mut data.level)),
+            _ => None,
+        };
+        if undone == Some(false) {
+            data.text_timer = 1.0;
+            data.text = "{yellow}Nothing to undo or redo".into();
+        }
+        if undone.is_some() {
+            data.editor_spawn = None;
+            data.spawn_drag = None;
+        }
+    }
+
+    if input.keyboard[KBKey::Tab].pressed() {
+        data.editor_layer = (data.editor_layer + 1) % data.level.tile_layers().count();
+    }
+    if input.keyboard[KBKey::F5].pressed() {
+        data.show_issues = !data.show_issues;
+    }
+
+    // cycle through the palette
+    let palette_items = palette::items(&data.tileset);
+    let forward = match (input.keyboard[KBKey::Q].pressed(), input.keyboard[KBKey::E].pressed()) {
+        (true, false) => Some(false),
+        (false, true) => Some(true),
+        _ => None,
+    };
+    if let Some(forward) = forward {
+        if let Some(item) = palette::cycle(&palette_items, palette_selection(data), forward) {
+            select_palette_item(data, item);
+        }
+    }
+
+    let mut new_tilemap_size = data.level.dim();
     match (input.keyboard[KBKey::Right].pressed(), input.keyboard[KBKey::Left].pressed()) {
         (true, false) => new_tilemap_size.x += 1,
         (false, true) => new_tilemap_size.x -= 1,
@@ -437,124 +1270,439 @@ fn level_editor(
         (false, true) => new_tilemap_size.y -= 1,
         _ => (),
     }
-    if new_tilemap_size != data.tilemap.dim()
-        && new_tilemap_size.x > 0
-        && new_tilemap_size.y > 0
+    if input.keyboard[KBKey::Shift].is_down() {
+        // shift the content instead, tiles moved over the edge are dropped
+        let size = data.level.dim();
+        let offset = new_tilemap_size - size;
+        if offset != V2::new(0, 0) {
+            data.history.resize(&mut data.level, size.x, size.y, offset);
+        }
+    } else if new_tilemap_size != data.level.dim()
+        && (1..=MAX_SIDE).contains(&new_tilemap_size.x)
+        && (1..=MAX_SIDE).contains(&new_tilemap_size.y)
     {
-        data.tilemap.resize(new_tilemap_size.x, new_tilemap_size.y);
+        let offset = Anchor::BOTTOM_LEFT.offset(data.level.dim(), new_tilemap_size);
+        data.history.resize(&mut data.level, new_tilemap_size.x, new_tilemap_size.y, offset);
     }
 
     if !input.keyboard[KBKey::Ctrl].is_down() {
         const CAMERA_SPEED: f32 = 10.0;
+        let speed = CAMERA_SPEED / data.editor_camera.zoom();
         match (input.keyboard[KBKey::A].is_down(), input.keyboard[KBKey::D].is_down()) {
-            (false, true ) => data.canvas_info.camera.x += CAMERA_SPEED * dt,
-            (true , false) => data.canvas_info.camera.x -= CAMERA_SPEED * dt,
+            (false, true ) => data.editor_camera.pos.x += speed * dt,
+            (true , false) => data.editor_camera.pos.x -= speed * dt,
             _ => (),
         }
         match (input.keyboard[KBKey::S].is_down(), input.keyboard[KBKey::W].is_down()) {
-            (false, true ) => data.canvas_info.camera.y += CAMERA_SPEED * dt,
-            (true , false) => data.canvas_info.camera.y -= CAMERA_SPEED * dt,
+            (false, true ) => data.editor_camera.pos.y += speed * dt,
+            (true , false) => data.editor_camera.pos.y -= speed * dt,
             _ => (),
         }
     }
 
-    let mouse_screen = V2i::from(input.mouse.pos()) / data.canvas_info.scale;
-    let mouse = {
-        // moving origin from top left to bottom left
-        let mouse_pos = V2f::from(mouse_screen) - V2f::new(0.0, data.canvas_info.height as f32);
-        V2i::from(&data.canvas_info.screen_to_game_matrix * mouse_pos + data.canvas_info.camera)
+    // zoom around the cursor, or around the center if it's outside of the canvas
+    let zoom_in = input.keyboard[KBKey::Plus].pressed() || input.keyboard[KBKey::NAdd].pressed();
+    let zoom_out = input.keyboard[KBKey::Minus].pressed() || input.keyboard[KBKey::NSub].pressed();
+    if zoom_in != zoom_out {
+        let cursor = ScreenPos::from(input.mouse.pos());
+        let CanvasPos(p) = data.canvas_info.screen_to_canvas(cursor);
+        let target = if canvas.bounds().contains(p) {
+            data.canvas_info.screen_to_world(cursor).0
+        } else {
+            data.editor_camera.pos
+        };
+        data.editor_camera.zoom_around(target, zoom_in);
+    }
+    if input.keyboard[KBKey::F].pressed() && !input.keyboard[KBKey::Ctrl].is_down() {
+        let level = AABB { min: V2::diag(0.0), max: V2f::from(data.level.dim()) };
+        data.editor_camera.fit(level, data.canvas_info.unzoomed_view_size());
+    }
+    if input.keyboard[KBKey::D0].pressed() && input.keyboard[KBKey::Ctrl].is_down() {
+        data.editor_camera.reset_zoom();
+    }
+
+    data.editor_camera.update(data.canvas_info.unzoomed_view_size(), dt);
+    data.canvas_info.camera = data.editor_camera.view().min;
+    data.canvas_info.zoom = data.editor_camera.zoom();
+
+    let mouse_screen = ScreenPos::from(input.mouse.pos());
+    let CanvasPos(mouse_canvas) = data.canvas_info.screen_to_canvas(mouse_screen);
+    let WorldPos(mouse_world) = data.canvas_info.screen_to_world(mouse_screen);
+    let mouse = WorldPos(mouse_world).tile();
+
+    // sides that lead to other levels may have gaps in the border
+    let issues = if data.show_issues {
+        let open_sides: Vec<Side> = data.level_file.as_ref().map_or_else(Vec::new, |file| {
+            Side::ALL.iter().copied().filter(|&side| data.level_index.neighbour(file, side).is_some()).collect()
+        });
+        validate::validate(&data.level, |tile| data.tileset.props(tile).solid, &open_sides)
+    } else {
+        Vec::new()
     };
+    let hovered_issue = issues.iter().find(|issue| issue.area.map_or(false, |area| area.contains(mouse)));
 
-    let mouse_pos_textbox: Option<(String, V2i)> = if (0..canvas.width()).contains(&mouse_screen.x)
-        && (0..canvas.height()).contains(&mouse_screen.y)
-    {
-        let margin = (10, 10).into();
-        let mut text_pos = mouse_screen + margin;
+    let mouse_text = hovered_issue.map_or_else(|| format!("{} : {}", mouse.x, mouse.y), issue_text);
+    let mouse_on_canvas = canvas.bounds().contains(mouse_canvas);
 
-        let text = format!("{} : {}", mouse.x, mouse.y);
-        let width = data.font_bmp.width(&text);
-        let height = data.font_bmp.height();
+    // play from here
+    if input.keyboard[KBKey::P].pressed() && input.keyboard[KBKey::Ctrl].is_down() {
+        if data.level.collision().get(mouse.x, mouse.y).is_some() {
+            start_playing(data, Some(mouse_world));
+        } else {
+            data.text_timer = 1.0;
+            data.text = "{yellow}Cursor is outside of the level".into();
+        }
+    }
 
-        // move textbox, so that it doesn't intersect edges of a screen
-        if text_pos.x + width > canvas.width() {
-            text_pos.x = mouse_screen.x - width - margin.x;
+    let tool_keys = [KBKey::D1, KBKey::D2, KBKey::D3, KBKey::D4, KBKey::D5, KBKey::D6, KBKey::D7, KBKey::D8];
+    if !input.keyboard[KBKey::Ctrl].is_down() {
+        for (&key, &tool) in tool_keys.iter().zip(Tool::ALL.iter()) {
+            if input.keyboard[key].pressed() && data.editor_drag.is_none() {
+                data.editor_tool = tool;
+            }
         }
-        if text_pos.y + height > canvas.height() {
-            text_pos.y = mouse_screen.y - height - margin.y;
+    }
+
+    selection_and_stamps(input, data);
+
+    let palette_layout = palette::Layout::new(canvas.dim(), palette_items.len());
+    let over_palette = palette_layout.rect.contains(mouse_canvas);
+    if over_palette && input.mouse[MouseKey::LB].pressed() && data.editor_drag.is_none() {
+        if let Some(i) = palette_layout.item_at(mouse_canvas) {
+            select_palette_item(data, palette_items[i]);
         }
+    }
 
-        Some((text, text_pos))
-    } else {
-        None
-    };
+    // clicking or dragging on the minimap moves the camera
+    let minimap = minimap::Minimap::new(canvas.dim(), data.level.dim());
+    let over_minimap = !over_palette && minimap.rect.contains(mouse_canvas);
+    let dragging = data.editor_drag.is_some() || data.editor_resize.is_some() || data.spawn_drag.is_some();
+    if over_minimap && input.mouse[MouseKey::LB].is_down() && !dragging {
+        data.editor_camera.pos = minimap.to_world(mouse_canvas);
+    }
+
+    // windows of the last frame, see `ui`
+    let over_windows = data.ui.wants_mouse(mouse_canvas);
+
+    // dragging a handle on the level outline moves that edge or corner
+    let handle_under_mouse = resize_handles(data.level.dim()).into_iter()
+        .find(|&(_, pos)| {
+            let CanvasPos(p) = data.canvas_info.world_to_canvas(WorldPos(pos));
+            (p.x - mouse_canvas.x).abs() <= HANDLE_SIZE && (p.y - mouse_canvas.y).abs() <= HANDLE_SIZE
+        })
+        .map(|(handle, _)| handle);
+    if let (Some(handle), false, None) = (handle_under_mouse, over_palette || over_minimap || over_windows, data.editor_drag) {
+        if input.mouse[MouseKey::LB].pressed() {
+            data.editor_resize = Some(handle);
+        }
+    }
+    let resized = data.editor_resize.map(|handle| resized_rect(data.level.dim(), handle, mouse_world));
+    if let (Some(handle), false) = (data.editor_resize, input.mouse[MouseKey::LB].is_down()) {
+        data.editor_resize = None;
+        // old tiles keep their place in the world, so the camera moves with the origin
+        let offset = release_handle(&mut data.history, &mut data.level, handle, mouse_world);
+        data.editor_camera.pos = data.editor_camera.pos + V2f::from(offset);
+        data.selection = data.selection.map(|area| area.translate(offset));
+    }
 
-    let maybe_tile = if input.mouse[MouseKey::LB].is_down() {
-        Some(Tile::Ground)
-    } else if input.mouse[MouseKey::RB].is_down() {
-        Some(Tile::Empty)
+    let selected_spawn = data.editor_entity.and(data.editor_spawn).filter(|&i| i < data.level.spawns.len());
+    let over_ui = over_palette || over_minimap || over_windows || handle_under_mouse.is_some() || data.editor_resize.is_some();
+
+    // left button selects and moves a spawn or places a new one, right button removes one
+    if let (Some(name), false, None) = (data.editor_entity, over_ui, data.editor_drag) {
+        let under_mouse = spawns::find_at(&data.level.spawns, mouse_world);
+        if input.mouse[MouseKey::LB].pressed() {
+            let before = data.level.spawns.clone();
+            let inside = data.level.collision().get(mouse.x, mouse.y).is_some();
+            match (under_mouse, spawns::kind(name)) {
+                (Some(i), _) => data.editor_spawn = Some(i),
+                (None, Some(kind)) if inside => {
+                    if kind.unique {
+                        data.level.spawns.retain(|spawn| spawn.kind != kind.name);
+                    }
+                    data.level.spawns.push(kind.spawn(V2f::from(mouse) + V2::diag(0.5)));
+                    data.editor_spawn = Some(data.level.spawns.len() - 1);
+                },
+                _ => data.editor_spawn = None,
+            }
+            if let Some(i) = data.editor_spawn {
+                let offset = data.level.spawns[i].pos - mouse_world;
+                data.spawn_drag = Some(SpawnDrag { before, offset, press: mouse_world, moved: false });
+            }
+        }
+        if let (Some(i), true) = (under_mouse, input.mouse[MouseKey::RB].pressed()) {
+            let before = data.level.spawns.clone();
+            data.level.spawns.remove(i);
+            data.editor_spawn = None;
+            data.history.spawns_changed(&data.level, before);
+        }
+    }
+    // moved spawns snap to half tiles, placing and moving is one undo step.
+    // A click doesn't move the spawn, so spawns off the grid stay where they are
+    if let (Some(drag), Some(i)) = (&mut data.spawn_drag, data.editor_spawn) {
+        drag.moved |= mouse_world != drag.press;
+        if let (Some(spawn), true) = (data.level.spawns.get_mut(i), drag.moved) {
+            spawn.pos = ((mouse_world + drag.offset) * 2.0).round() / 2.0;
+        }
+    }
+    if !input.mouse[MouseKey::LB].is_down() {
+        if let Some(drag) = data.spawn_drag.take() {
+            data.history.spawns_changed(&data.level, drag.before);
+        }
+    }
+
+    // left button paints with the editor tile, right button erases
+    let pressed_tile = if over_ui || data.editor_entity.is_some() {
+        None
+    } else if input.mouse[MouseKey::LB].pressed() {
+        Some(data.editor_tile)
+    } else if input.mouse[MouseKey::RB].pressed() {
+        Some(Tile::EMPTY)
     } else {
         None
     };
-
-    if let Some(tile) = maybe_tile {
-        if (0..data.tilemap.width()).contains(&mouse.x)
-            && (0..data.tilemap.height()).contains(&mouse.y)
-        {
-            data.tilemap[(mouse.x, mouse.y)] = tile;
+    let button_down = input.mouse[MouseKey::LB].is_down() || input.mouse[MouseKey::RB].is_down();
+
+    if let (Some(tile), None) = (pressed_tile, data.editor_drag) {
+        data.editor_drag = Some(Drag { start: mouse, last: mouse, tile });
+        match data.editor_tool {
+            Tool::Fill => {
+                let filled = data.level.tile_layers().nth(data.editor_layer)
+                    .map_or_else(Vec::new, |layer| brush::flood_fill(&layer.tiles, mouse));
+                for p in filled {
+                    data.history.set_tile(&mut data.level, data.editor_layer, p, tile);
+                }
+            },
+            Tool::Eyedropper if input.mouse[MouseKey::LB].pressed() => {
+                let picked = data.level.tile_layers().nth(data.editor_layer)
+                    .and_then(|layer| layer.tiles.get(mouse.x, mouse.y))
+                    .filter(|&tile| tile != Tile::EMPTY);
+                if let Some(picked) = picked {
+                    select_palette_item(data, PaletteItem::Tile(picked));
+                }
+            },
+            Tool::Select => match data.selection {
+                // lift the selected tiles, they are put back where the mouse is released
+                Some(area) if area.contains(mouse) && input.mouse[MouseKey::LB].pressed() => {
+                    data.floating = data.level.tile_layers().nth(data.editor_layer)
+                        .and_then(|layer| Stamp::copy(&layer.tiles, area));
+                    erase(&mut data.history, &mut data.level, data.editor_layer, area);
+                },
+                _ => data.selection = None,
+            },
+            Tool::Stamp if input.mouse[MouseKey::LB].pressed() => {
+                if let Some(stamp) = &data.clipboard {
+                    paste(&mut data.history, &mut data.level, data.editor_layer, stamp, mouse);
+                }
+            },
+            _ => (),
         }
     }
 
+    // one stroke or shape is one undo step
+    match &mut data.editor_drag {
+        Some(drag) if button_down => {
+            match data.editor_tool {
+                Tool::Pencil => {
+                    for p in brush::line(drag.last, mouse) {
+                        data.history.set_tile(&mut data.level, data.editor_layer, p, drag.tile);
+                    }
+                },
+                Tool::Select if data.floating.is_none() => data.selection = Some(stamp::selection(drag.start, mouse)),
+                _ => (),
+            }
+            drag.last = mouse;
+        },
+        Some(drag) => {
+            for p in brush::shape(data.editor_tool, drag.start, mouse) {
+                data.history.set_tile(&mut data.level, data.editor_layer, p, drag.tile);
+            }
+            if let (Some(stamp), Some(area)) = (data.floating.take(), data.selection) {
+                let moved = area.translate(mouse - drag.start);
+                paste(&mut data.history, &mut data.level, data.editor_layer, &stamp, moved.min);
+                data.selection = Some(moved);
+            }
+            data.history.end_group();
+            data.editor_drag = None;
+        },
+        None => (),
+    }
+
+    // what a tool will paint: shape, moved or pasted tiles, or the tile under the cursor
+    let stamp_preview = |stamp: &Stamp, pos: V2i| -> Vec<(V2i, Tile)> {
+        stamp.cells().map(|(p, tile)| (pos + p, tile)).collect()
+    };
+    let preview = match (data.editor_drag, data.editor_tool) {
+        _ if over_ui || data.editor_entity.is_some() => Vec::new(),
+        (Some(drag), tool) if tool.is_shape() => brush::shape(tool, drag.start, mouse)
+            .into_iter()
+            .map(|p| (p, drag.tile))
+            .collect(),
+        (Some(drag), Tool::Select) => match (&data.floating, data.selection) {
+            (Some(stamp), Some(area)) => stamp_preview(stamp, area.min + (mouse - drag.start)),
+            _ => Vec::new(),
+        },
+        (None, Tool::Stamp) => data.clipboard.as_ref().map_or_else(Vec::new, |stamp| stamp_preview(stamp, mouse)),
+        (Some(_), _) | (None, Tool::Select) => Vec::new(),
+        (None, _) => vec![(mouse, data.editor_tile)],
+    };
+
     render::clear(canvas, Color::BLACK);
 
-    data.tilemap.draw(canvas, &data.canvas_info, &data.tile_info);
-    data.tilemap.draw_grid(canvas, &data.canvas_info, &data.tile_info);
+    data.level.draw_background(canvas, &data.canvas_info, &data.tileset);
+    data.level.draw_foreground(canvas, &data.canvas_info, &data.tileset);
+    for spawn in &data.level.spawns {
+        match entity_bmp(data, &spawn.kind) {
+            Some(bmp) => world::draw_bmp_centered(canvas, &data.canvas_info, bmp, spawn.pos),
+            None => {
+                let rect = AABB { min: spawn.pos - V2::diag(0.25), max: spawn.pos + V2::diag(0.25) };
+                world::fill_rect(canvas, &data.canvas_info, rect, Color::PURPLE);
+            },
+        }
+        if spawn.kind == "trigger" {
+            let min = V2f::from(V2i::from(spawn.pos.floor()));
+            let size = V2i::new(spawns::int_prop(spawn, "width"), spawns::int_prop(spawn, "height"));
+            let color = match spawns::prop(spawn, "action") {
+                "hazard" => Color::RED,
+                "exit" => Color::PURPLE,
+                _ => Color::WHITE,
+            };
+            world::draw_rect(canvas, &data.canvas_info, AABB { min, max: min + V2f::from(size) }, color, 1);
+        }
+    }
+    if let Some(spawn) = selected_spawn.map(|i| &data.level.spawns[i]) {
+        let rect = AABB { min: spawn.pos - V2::diag(0.5), max: spawn.pos + V2::diag(0.5) };
+        world::draw_rect(canvas, &data.canvas_info, rect, Color::YELLOW, 1);
+    }
+    for (p, preview_tile) in preview.into_iter().filter(|(p, _)| data.level.collision().get(p.x, p.y).is_some()) {
+        let color = if data.editor_tool == Tool::Eyedropper {
+            Color::YELLOW
+        } else if preview_tile == Tile::EMPTY {
+            Color::RED
+        } else {
+            // as if surrounded by the same tile
+            if let Some(bmp) = data.tileset.frame(preview_tile, 0xFF) {
+                render::world::draw_bmp(canvas, &data.canvas_info, bmp, (p.x as f32, (p.y + 1) as f32));
+            }
+            Color::WHITE
+        };
+        let cell = AABB { min: V2f::from(p), max: V2f::from(p) + V2::diag(1.0) };
+        render::world::draw_rect(canvas, &data.canvas_info, cell, color, 1);
+    }
+    if let Some(area) = data.selection {
+        let moved = match (data.editor_drag, &data.floating) {
+            (Some(drag), Some(_)) => area.translate(mouse - drag.start),
+            _ => area,
+        };
+        let rect = AABB { min: V2f::from(moved.min), max: V2f::from(moved.max) };
+        render::world::draw_rect(canvas, &data.canvas_info, rect, Color::PURPLE, 1);
+    }
+    for issue in &issues {
+        if let Some(area) = issue.area {
+            let color = if issue.severity == Severity::Error { Color::RED } else { Color::YELLOW };
+            let rect = AABB { min: V2f::from(area.min), max: V2f::from(area.max) };
+            render::world::draw_rect(canvas, &data.canvas_info, rect, color, 1);
+        }
+    }
+    data.level.collision().draw_grid(canvas, &data.canvas_info);
     //FIXME: horizontal line upper pixel is not drawn
-    data.tilemap.draw_outline(canvas, &data.canvas_info);
-
-    fn draw_text_box(
-        dst: &mut Bitmap,
-        font: &FontBitmaps,
-        text: &str,
-        p: V2i,
-        scale: i32,
-    ) -> V2i {
-        let margin = V2i::new(8, 8) / scale;
-
-        //TODO: get_bbox method?
-        let min_text_box = p;
-        let max_text_box = min_text_box
-            + (font.width(text), font.height()).into()
-            + margin * 2;
-        render::fill_rect(dst, min_text_box, max_text_box, Color::BLACK);
-        render::draw_rect(dst, min_text_box, max_text_box, Color::WHITE, 1);
-        font.draw_string(dst, min_text_box + margin, text);
-
-        max_text_box
-    }
-
-    let bottom_left = draw_text_box(
-        canvas,
-        &data.font_bmp,
-        &format!("{}x{}", data.tilemap.width(), data.tilemap.height()),
-        (50 / data.canvas_info.scale, 50 / data.canvas_info.scale).into(),
-        data.canvas_info.scale,
-    );
-
-    let _ = draw_text_box(
-        canvas,
-        &data.font_bmp,
-        "Use arrow keys to change tilemap size.",
-        (50 / data.canvas_info.scale, bottom_left.y).into(),
-        data.canvas_info.scale,
-    );
-
-    if let Some((text, pos)) = mouse_pos_textbox {
-        draw_text_box(canvas, &data.font_bmp, &text, pos, data.canvas_info.scale);
+    data.level.collision().draw_outline(canvas, &data.canvas_info);
+    if let Some(rect) = resized {
+        let rect = AABB { min: V2f::from(rect.min), max: V2f::from(rect.max) };
+        render::world::draw_rect(canvas, &data.canvas_info, rect, Color::WHITE, 1);
+    }
+    for (handle, pos) in resize_handles(data.level.dim()) {
+        let CanvasPos(p) = data.canvas_info.world_to_canvas(WorldPos(pos));
+        let active = data.editor_resize.or(handle_under_mouse) == Some(handle);
+        let color = if active { Color::WHITE } else { Color::YELLOW };
+        render::fill_rect(canvas, p - V2::diag(HANDLE_SIZE), p + V2::diag(HANDLE_SIZE + 1), color);
+    }
+
+    let layer_name = data.level.tile_layers().nth(data.editor_layer).map_or("", |layer| &layer.name);
+    let selected = match palette_selection(data) {
+        PaletteItem::Tile(tile) => format!("Tile: {}", data.tileset.get(tile).map_or("none", |def| &def.name)),
+        PaletteItem::Entity(kind) => format!("Entity: {}", kind),
+    };
+    let mut info = vec![
+        format!("{}x{}, zoom {}. F to fit, F5 to check.", data.level.width(), data.level.height(), data.editor_camera.zoom()),
+        format!("File: {}. Ctrl O to open, Ctrl N for new.", data.level_file.as_deref().unwrap_or("new")),
+        "Use arrow keys to change tilemap size. Ctrl K to play, Ctrl P to play from the cursor.".into(),
+        format!("Layer: {}. Tab to switch.", layer_name),
+        format!("{}. Q and E to switch.", selected),
+        format!("Tool: {}. 1 to 8 to switch.", data.editor_tool.name()),
+    ];
+    if let (Tool::Stamp, Some(stamp)) = (data.editor_tool, &data.clipboard) {
+        info.push(format!("Stamp: {} {}x{}. H, V to flip, R to rotate.", stamp.name, stamp.width(), stamp.height()));
+    }
+    if data.show_issues {
+        let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+        let color = if errors > 0 { "red" } else if issues.is_empty() { "" } else { "yellow" };
+        info.push(format!("Problems: {{{}}}{} errors, {} warnings{{}}. F5 to hide.", color, errors, issues.len() - errors));
+        // the rest are shown when the mouse is over their tiles
+        info.extend(issues.iter().filter(|issue| issue.area.is_none()).map(issue_text));
+    }
+    if let Some(world_level) = data.world.get(data.world_index) {
+        info.push(format!(
+            "Level: {} at {}, {}. Page Up and Page Down to switch.",
+            world_level.name, world_level.world_pos.x, world_level.world_pos.y,
+        ));
+    }
+
+    let thumbnail = |item| match item {
+        PaletteItem::Tile(tile) => data.tileset.frame(tile, 0xFF),
+        PaletteItem::Entity(kind) => entity_bmp(data, kind),
+    };
+    minimap.draw(canvas, &data.level, data.canvas_info.visible_rect());
+    palette::draw(canvas, &palette_layout, &palette_items, palette_selection(data), thumbnail);
+
+    // what the mouse paints or places, above the cursor
+    if let (true, false, Some(bmp)) = (mouse_on_canvas, over_ui, thumbnail(palette_selection(data))) {
+        let top_left = mouse_canvas + V2::new(10, -10 - palette::THUMB);
+        render::fill_rect(canvas, top_left, top_left + V2::diag(palette::THUMB), Color::BLACK);
+        palette::draw_thumbnail(canvas, bmp, top_left);
     }
 
+    let scale = data.canvas_info.scale;
+    let canvas_dim = canvas.dim();
+    let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse_canvas);
+    ui.begin_window("info", V2::diag(50 / scale), V2::new(0.0, 0.0));
+    for line in &info {
+        ui.label(line);
+    }
+    ui.end_window();
+
+    // properties of the selected spawn, buttons step their values
+    if let Some(i) = selected_spawn {
+        let props = spawns::kind(&data.level.spawns[i].kind).map_or(&[][..], |kind| kind.props);
+        ui.begin_window("spawn", V2::new(4, canvas_dim.y - 4), V2::new(0.0, 1.0));
+        ui.label(&data.level.spawns[i].kind);
+        for prop in props {
+            let value = spawns::prop(&data.level.spawns[i], prop.name).to_string();
+            ui.label(&format!("{}: {}", prop.name, value));
+            ui.same_line();
+            let less = ui.button(&format!("less##{}", prop.name));
+            ui.same_line();
+            let more = ui.button(&format!("more##{}", prop.name));
+            if less || more {
+                let before = data.level.spawns.clone();
+                data.level.spawns[i].props.insert(prop.name.into(), prop.step(&value, more));
+                data.history.spawns_changed(&data.level, before);
+            }
+        }
+        ui.end_window();
+    }
+
+    if mouse_on_canvas {
+        ui.tooltip(&mouse_text, mouse_canvas);
+    }
+    ui.finish();
+
     if data.text_timer > 0.0 {
         data.text_timer -= dt;
-        data.font_bmp.draw_string(canvas, V2::diag(8 / data.canvas_info.scale), &data.text);
+        let margin = 8 / data.canvas_info.scale;
+        let max_width = canvas.width() - margin * 2;
+        let style = TextStyle::default().outlined(Color::BLACK);
+        data.font_bmp.draw_text(canvas, V2::diag(margin), &data.text, Some(max_width), style);
     }
 
     // draw yellow outline
@@ -587,6 +1735,36 @@ enum Knockback {
     },
 }
 
+#[derive(Copy, Clone, Debug)]
+enum Item { Coin, Heart }
+
+#[derive(Copy, Clone, Debug)]
+struct Pickup {
+    pub pos: V2f,
+    pub item: Item,
+}
+
+impl Pickup {
+    pub fn aabb(&self) -> AABB<f32> {
+        AABB { min: self.pos - V2::diag(0.25), max: self.pos + V2::diag(0.25) }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum TriggerAction {
+    /// Player restarts at this position
+    Checkpoint(V2f),
+    Hazard,
+    /// Level is complete
+    Exit,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Trigger {
+    pub area: AABB<f32>,
+    pub action: TriggerAction,
+}
+
 #[derive(Copy, Clone, Debug)]
 struct Entity {
     pub pos: V2f,
@@ -634,7 +1812,22 @@ impl Entity {
         }
     }
 
-    pub fn mov(&mut self, tilemap: &Tilemap, command: Option<MovementCommand>, dt: f32) {
+    /// Tile the entity is standing on
+    fn tile_under(&self, tilemap: &Tilemap) -> Option<Tile> {
+        tilemap.get(self.pos.x.floor() as i32, self.pos.y.floor() as i32 - 1)
+    }
+
+    /// Whether any tile overlapping the collision box is a hazard
+    pub fn touches_hazard(&self, tilemap: &Tilemap, tileset: &TileSet) -> bool {
+        let aabb = self.collision_aabb();
+        let (min_x, min_y) = (aabb.min.x.floor() as i32, aabb.min.y.floor() as i32);
+        let (max_x, max_y) = (aabb.max.x.floor() as i32, aabb.max.y.floor() as i32);
+        (min_y..=max_y).any(|y| (min_x..=max_x).any(|x| {
+            tilemap.get(x, y).map_or(false, |tile| tileset.props(tile).hazard)
+        }))
+    }
+
+    pub fn mov(&mut self, tilemap: &Tilemap, tileset: &TileSet, command: Option<MovementCommand>, dt: f32) {
         use MovementCommand::{Platformer, Velocity};
         use MovementState::{Ground, Air};
         use Direction::{Left, Right};
@@ -642,14 +1835,10 @@ impl Entity {
         const MAX_VEL_X: f32 = 10.0;
         const MAX_VEL_Y: f32 = 30.0;
 
-        fn is_obstacle(tile0: Option<Tile>, tile1: Option<Tile>) -> bool {
-            use Tile::Empty;
-            if let (Some(Empty), Some(Empty)) = (tile0, tile1) {
-                false
-            } else {
-                true
-            }
-        }
+        // outside of the tilemap is always an obstacle
+        let is_solid = |tile: Option<Tile>| tile.map_or(true, |tile| tileset.props(tile).solid);
+        let is_obstacle = |tile0, tile1| is_solid(tile0) || is_solid(tile1);
+        let is_one_way = |tile: Option<Tile>| tile.map_or(false, |tile| tileset.props(tile).one_way);
 
         let (mut new_vel_x, mut new_vel_y) = match command {
             Some(Platformer { dir, jump }) => {
@@ -665,7 +1854,11 @@ impl Entity {
                     };
                     acc_x += match self.movement_state {
                         // friction
-                        Ground => -self.vel.x * 12.0,
+                        Ground => {
+                            let friction = self.tile_under(tilemap)
+                                .map_or(tileset::DEFAULT_FRICTION, |tile| tileset.props(tile).friction);
+                            -self.vel.x * friction
+                        },
                         // air movement penalty
                         Air { .. } => -acc_x * 0.8,
                     };
@@ -760,7 +1953,12 @@ impl Entity {
                 let bottom_left_tile = tilemap.get(left_x.floor() as i32, new_bottom_tile_y);
                 let bottom_right_tile = tilemap.get(right_x.floor() as i32, new_bottom_tile_y);
 
-                if is_obstacle(bottom_left_tile, bottom_right_tile) {
+                // one-way tiles only stop entities that were above them
+                let bottom_y = self.pos.y + self.origin_to_bottom_left.y;
+                let lands_on_one_way = bottom_y >= (new_bottom_tile_y + 1) as f32
+                    && (is_one_way(bottom_left_tile) || is_one_way(bottom_right_tile));
+
+                if is_obstacle(bottom_left_tile, bottom_right_tile) || lands_on_one_way {
                     new_vel_y = 0.0;
                     new_pos_y = (new_bottom_tile_y + 1) as f32 - self.origin_to_bottom_left.y + 0.01;
 
@@ -776,8 +1974,14 @@ impl Entity {
 
         // ground check //////////
         if let Ground = self.movement_state {
-            let tile_under = tilemap.get(self.pos.x.floor() as i32, self.pos.y.floor() as i32 - 1);
-            if let Some(Tile::Empty) = tile_under {
+            let stands = match self.tile_under(tilemap) {
+                Some(tile) => {
+                    let props = tileset.props(tile);
+                    props.solid || props.one_way
+                },
+                None => true,
+            };
+            if !stands {
                 self.movement_state = Air { jumped_again: false };
             }
         }
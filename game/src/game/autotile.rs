@@ -0,0 +1,122 @@
+/* Autotiling picks a variant of a tile from its 8 neighbors.
+
+   Neighbor mask bits (set if the neighbor is the same tile or outside of the map):
+    NW  N  NE       128   1  16
+     W  .  E    ->    8   .   2
+    SW  S  SE        64   4  32
+
+   4-bit mode only looks at N, E, S and W, a sheet has 16 variants in a 4x4 grid,
+   variant index is the mask itself.
+
+   8-bit (blob) mode ignores a corner unless both of its sides are set,
+   which leaves 47 different masks. A sheet has them in an 8x6 grid,
+   row by row in ascending order of the mask, the last cell is unused.
+*/
+
+use crate::{
+    render::Bitmap,
+    geom::vector::prelude::*,
+};
+
+pub const N: u8 = 1;
+pub const E: u8 = 2;
+pub const S: u8 = 4;
+pub const W: u8 = 8;
+pub const NE: u8 = 16;
+pub const SE: u8 = 32;
+pub const SW: u8 = 64;
+pub const NW: u8 = 128;
+
+/// Neighbor offsets in the order of mask bits
+pub const NEIGHBORS: [(i32, i32, u8); 8] = [
+    ( 0,  1, N),
+    ( 1,  0, E),
+    ( 0, -1, S),
+    (-1,  0, W),
+    ( 1,  1, NE),
+    ( 1, -1, SE),
+    (-1, -1, SW),
+    (-1,  1, NW),
+];
+
+pub const BLOB_VARIANTS: usize = 47;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AutotileMode {
+    Bits4,
+    Blob,
+}
+
+impl AutotileMode {
+    pub fn variant_count(self) -> usize {
+        match self {
+            AutotileMode::Bits4 => 16,
+            AutotileMode::Blob => BLOB_VARIANTS,
+        }
+    }
+
+    /// Columns and rows of a sheet
+    fn grid(self) -> (i32, i32) {
+        match self {
+            AutotileMode::Bits4 => (4, 4),
+            AutotileMode::Blob => (8, 6),
+        }
+    }
+
+    pub fn variant(self, mask: u8) -> usize {
+        match self {
+            AutotileMode::Bits4 => (mask & (N | E | S | W)) as usize,
+            AutotileMode::Blob => BLOB_MASKS.binary_search(&blob_mask(mask))
+                .expect("blob_mask returned a mask that isn't in BLOB_MASKS"),
+        }
+    }
+}
+
+/// Drops corners that don't have both sides set
+pub fn blob_mask(mask: u8) -> u8 {
+    let mut result = mask & (N | E | S | W);
+    for &(corner, side0, side1) in &[(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)] {
+        if mask & corner != 0 && mask & side0 != 0 && mask & side1 != 0 {
+            result |= corner;
+        }
+    }
+    result
+}
+
+/// All masks `blob_mask` can return, in ascending order.
+///
+/// Position in this array is the variant index
+const BLOB_MASKS: [u8; BLOB_VARIANTS] = [
+      0,   1,   2,   3,   4,   5,   6,   7,   8,   9,  10,  11,  12,  13,  14,  15,
+     19,  23,  27,  31,  38,  39,  46,  47,  55,  63,  76,  77,  78,  79,  95, 110,
+    111, 127, 137, 139, 141, 143, 155, 159, 175, 191, 205, 207, 223, 239, 255,
+];
+
+pub struct Autotile {
+    pub mode: AutotileMode,
+    pub variants: Vec<Bitmap>,
+}
+
+impl Autotile {
+    /// Cuts `sheet` into variants, its size must be divisible by the grid of `mode`
+    pub fn from_sheet(mode: AutotileMode, sheet: &Bitmap) -> Result<Self, String> {
+        let (columns, rows) = mode.grid();
+        if sheet.width() % columns != 0 || sheet.height() % rows != 0 {
+            return Err(format!(
+                "sheet is {}x{}, which isn't divisible into {}x{} cells",
+                sheet.width(), sheet.height(), columns, rows,
+            ));
+        }
+        let cell = V2::new(sheet.width() / columns, sheet.height() / rows);
+
+        let variants = (0..mode.variant_count() as i32)
+            .map(|i| sheet.sub_bitmap(V2::new(i % columns * cell.x, i / columns * cell.y), cell))
+            .collect();
+
+        Ok(Self { mode, variants })
+    }
+
+    pub fn get(&self, mask: u8) -> &Bitmap {
+        &self.variants[self.mode.variant(mask)]
+    }
+}
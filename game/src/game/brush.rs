@@ -0,0 +1,150 @@
+/* Level editor tools and the shapes they paint.
+
+   Line and rectangle tools paint when the mouse button is released, until then
+   the editor shows a preview of `shape`. Pencil paints every frame along the line
+   from the previous mouse position, so fast drags don't leave gaps.
+*/
+
+use std::collections::VecDeque;
+use crate::geom::vector::prelude::*;
+use super::tilemap::{Tilemap, Tile};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tool {
+    Pencil,
+    Line,
+    Rect,
+    RectOutline,
+    Fill,
+    /// Picks the tile under the cursor
+    Eyedropper,
+    /// Selects a rectangle, dragging the selection moves its tiles
+    Select,
+    /// Pastes the copied tiles, see `stamp`
+    Stamp,
+}
+
+impl Tool {
+    /// In the order of their keys, 1 to 8
+    pub const ALL: [Tool; 8] = [
+        Tool::Pencil,
+        Tool::Line,
+        Tool::Rect,
+        Tool::RectOutline,
+        Tool::Fill,
+        Tool::Eyedropper,
+        Tool::Select,
+        Tool::Stamp,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Pencil => "pencil",
+            Tool::Line => "line",
+            Tool::Rect => "rectangle",
+            Tool::RectOutline => "rectangle outline",
+            Tool::Fill => "fill",
+            Tool::Eyedropper => "eyedropper",
+            Tool::Select => "select",
+            Tool::Stamp => "stamp",
+        }
+    }
+
+    /// Tools that paint a shape from where the drag started to where it is now
+    pub fn is_shape(self) -> bool {
+        match self {
+            Tool::Line | Tool::Rect | Tool::RectOutline => true,
+            Tool::Pencil | Tool::Fill | Tool::Eyedropper | Tool::Select | Tool::Stamp => false,
+        }
+    }
+}
+
+/// Tiles a shape tool paints between `start` and `end`, empty for other tools
+pub fn shape(tool: Tool, start: V2i, end: V2i) -> Vec<V2i> {
+    match tool {
+        Tool::Line => line(start, end),
+        Tool::Rect => rect(start, end, false),
+        Tool::RectOutline => rect(start, end, true),
+        Tool::Pencil | Tool::Fill | Tool::Eyedropper | Tool::Select | Tool::Stamp => Vec::new(),
+    }
+}
+
+/// Tiles of a line from `start` to `end` inclusive, without diagonal gaps (Bresenham)
+pub fn line(start: V2i, end: V2i) -> Vec<V2i> {
+    let delta = V2::new((end.x - start.x).abs(), -(end.y - start.y).abs());
+    let step = V2::new((end.x - start.x).signum(), (end.y - start.y).signum());
+    let mut error = delta.x + delta.y;
+    let mut p = start;
+    let mut result = vec![p];
+    while p != end {
+        let error2 = error * 2;
+        if error2 >= delta.y {
+            error += delta.y;
+            p.x += step.x;
+        }
+        if error2 <= delta.x {
+            error += delta.x;
+            p.y += step.y;
+        }
+        result.push(p);
+    }
+    result
+}
+
+/// Tiles of a rectangle with corners `a` and `b`
+pub fn rect(a: V2i, b: V2i, outline: bool) -> Vec<V2i> {
+    let min = V2::new(a.x.min(b.x), a.y.min(b.y));
+    let max = V2::new(a.x.max(b.x), a.y.max(b.y));
+    let mut result = Vec::new();
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let on_edge = x == min.x || x == max.x || y == min.y || y == max.y;
+            if !outline || on_edge {
+                result.push(V2::new(x, y));
+            }
+        }
+    }
+    result
+}
+
+/// Tiles connected to `start` (not diagonally) that are the same as it
+pub fn flood_fill(tiles: &Tilemap, start: V2i) -> Vec<V2i> {
+    let target = match tiles.get(start.x, start.y) {
+        Some(tile) => tile,
+        None => return Vec::new(),
+    };
+    let mut visited = vec![false; (tiles.width() * tiles.height()) as usize];
+    let mut queue = VecDeque::new();
+    let mut result = Vec::new();
+
+    visited[(start.y * tiles.width() + start.x) as usize] = true;
+    queue.push_back(start);
+    while let Some(p) = queue.pop_front() {
+        result.push(p);
+        for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let next = V2::new(p.x + dx, p.y + dy);
+            if tiles.get(next.x, next.y) != Some(target) {
+                continue;
+            }
+            let index = (next.y * tiles.width() + next.x) as usize;
+            if !visited[index] {
+                visited[index] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    result
+}
+
+/// Mouse drag with the tool in use
+#[derive(Copy, Clone, Debug)]
+pub struct Drag {
+    pub start: V2i,
+    /// Mouse position in the previous frame
+    pub last: V2i,
+    /// What the drag paints with, depends on the button
+    pub tile: Tile,
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn line_has_no_gaps() {
+    assert_eq!(line(V2::new(2, 3), V2::new(2, 3)), [V2::new(2, 3)]);
+
+    for &end in &[V2::new(7, 2), V2::new(-3, 9), V2::new(-8, -8), V2::new(0, -5), V2::new(5, 1)] {
+        let start = V2::new(1, 1);
+        let points = line(start, end);
+        assert_eq!(points.first(), Some(&start));
+        assert_eq!(points.last(), Some(&end));
+        let steps = (end.x - start.x).abs().max((end.y - start.y).abs());
+        assert_eq!(points.len() as i32, steps + 1, "line to {:?}", end);
+        for pair in points.windows(2) {
+            let d = pair[1] - pair[0];
+            assert!(d.x.abs() <= 1 && d.y.abs() <= 1, "gap in line to {:?}", end);
+        }
+    }
+}
+
+#[test]
+fn rect_and_outline() {
+    assert_eq!(rect(V2::new(3, 2), V2::new(1, 0), false).len(), 9);
+    let outline = rect(V2::new(3, 2), V2::new(1, 0), true);
+    assert_eq!(outline.len(), 8);
+    assert!(!outline.contains(&V2::new(2, 1)));
+    assert_eq!(rect(V2::new(0, 0), V2::new(4, 0), true).len(), 5);
+}
+
+#[test]
+fn flood_fill_stays_inside_walls() {
+    // diagonal wall, tiles of it only touch at corners
+    let mut tiles = Tilemap::new(5, 5);
+    for i in 0..5 {
+        tiles.set(i, 4 - i, Tile(1));
+    }
+    tiles.set(1, 2, Tile(1));
+
+    let filled = flood_fill(&tiles, V2::new(0, 0));
+    assert_eq!(filled.len(), 9);
+    assert!(filled.iter().all(|p| tiles[(p.x, p.y)] == Tile::EMPTY && p.x + p.y < 4));
+
+    assert_eq!(flood_fill(&tiles, V2::new(0, 4)), [V2::new(0, 4)]);
+    assert!(flood_fill(&tiles, V2::new(5, 0)).is_empty());
+}
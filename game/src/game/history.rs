@@ -0,0 +1,186 @@
+/* Undo and redo of level editing.
+
+   Every change goes through `History`, which applies it to the level and remembers
+   how to take it back. Tile changes are grouped into one step until `end_group`,
+   so a whole paint stroke (or fill, or paste) is undone at once.
+*/
+
+use std::collections::VecDeque;
+use crate::geom::vector::prelude::*;
+use super::{
+    tilemap::{Tilemap, Tile},
+    level::{Level, Spawn},
+};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TileChange {
+    pub pos: V2i,
+    pub before: Tile,
+    pub after: Tile,
+}
+
+/// One undoable step
+pub enum Edit {
+    /// Tiles of one tile layer, index into `Level::tile_layers`.
+    /// Changes are in the order they were made, the same tile may change several times
+    Tiles { layer: usize, changes: Vec<TileChange> },
+    /// All tile layers, for changes that move tiles around or drop them, like resize.
+    /// Spawns and images were moved by `offset`
+    Layers { before: Vec<Tilemap>, after: Vec<Tilemap>, offset: V2i },
+    /// All spawns, they are few and small
+    Spawns { before: Vec<Spawn>, after: Vec<Spawn> },
+}
+
+impl Edit {
+    fn undo(&self, level: &mut Level) {
+        match self {
+            Edit::Tiles { layer, changes } => {
+                if let Some(layer) = level.tile_layer_mut(*layer) {
+                    for change in changes.iter().rev() {
+                        layer.tiles.set(change.pos.x, change.pos.y, change.before);
+                    }
+                }
+            },
+            Edit::Layers { before, offset, .. } => {
+                set_layers(level, before);
+                level.shift_objects(-*offset);
+            },
+            Edit::Spawns { before, .. } => level.spawns = before.clone(),
+        }
+    }
+
+    fn redo(&self, level: &mut Level) {
+        match self {
+            Edit::Tiles { layer, changes } => {
+                if let Some(layer) = level.tile_layer_mut(*layer) {
+                    for change in changes {
+                        layer.tiles.set(change.pos.x, change.pos.y, change.after);
+                    }
+                }
+            },
+            Edit::Layers { after, offset, .. } => {
+                set_layers(level, after);
+                level.shift_objects(*offset);
+            },
+            Edit::Spawns { after, .. } => level.spawns = after.clone(),
+        }
+    }
+}
+
+fn layers_of(level: &Level) -> Vec<Tilemap> {
+    level.tile_layers().map(|layer| layer.tiles.clone()).collect()
+}
+
+fn set_layers(level: &mut Level, tilemaps: &[Tilemap]) {
+    for (layer, tiles) in level.tile_layers_mut().zip(tilemaps) {
+        layer.tiles = tiles.clone();
+    }
+}
+
+pub struct History {
+    /// Most steps that can be undone, the oldest ones are forgotten
+    pub limit: usize,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    /// Tile changes that aren't a step yet
+    group: Option<Edit>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self { limit, undo: VecDeque::new(), redo: Vec::new(), group: None }
+    }
+
+    /// Forgets everything, for when the level is replaced
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group = None;
+    }
+
+    /// Sets a tile and adds the change to the current group,
+    /// changes of another layer start a new group
+    pub fn set_tile(&mut self, level: &mut Level, layer_index: usize, pos: V2i, tile: Tile) {
+        let layer = match level.tile_layer_mut(layer_index) {
+            Some(layer) => layer,
+            None => return,
+        };
+        let before = match layer.tiles.get(pos.x, pos.y) {
+            Some(before) if before != tile => before,
+            _ => return,
+        };
+        layer.tiles.set(pos.x, pos.y, tile);
+
+        match &self.group {
+            Some(Edit::Tiles { layer, .. }) if *layer == layer_index => (),
+            _ => {
+                self.end_group();
+                self.group = Some(Edit::Tiles { layer: layer_index, changes: Vec::new() });
+            },
+        }
+        if let Some(Edit::Tiles { changes, .. }) = &mut self.group {
+            changes.push(TileChange { pos, before, after: tile });
+        }
+    }
+
+    /// Makes tile changes since the last call one step
+    pub fn end_group(&mut self) {
+        if let Some(edit) = self.group.take() {
+            self.push(edit);
+        }
+    }
+
+    /// Resizes all tile layers, see `Level::resize_with_offset`.
+    /// Tiles cut off at the edges come back on undo
+    pub fn resize(&mut self, level: &mut Level, new_width: i32, new_height: i32, offset: V2i) {
+        self.end_group();
+        let before = layers_of(level);
+        level.resize_with_offset(new_width, new_height, offset);
+        self.push(Edit::Layers { before, after: layers_of(level), offset });
+    }
+
+    /// Makes changes of spawns since they were `before` one step
+    pub fn spawns_changed(&mut self, level: &Level, before: Vec<Spawn>) {
+        self.end_group();
+        if before != level.spawns {
+            self.push(Edit::Spawns { before, after: level.spawns.clone() });
+        }
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.undo.push_back(edit);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Returns false if there is nothing to undo
+    pub fn undo(&mut self, level: &mut Level) -> bool {
+        self.end_group();
+        match self.undo.pop_back() {
+            Some(edit) => {
+                edit.undo(level);
+                self.redo.push(edit);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns false if there is nothing to redo
+    pub fn redo(&mut self, level: &mut Level) -> bool {
+        self.end_group();
+        match self.redo.pop() {
+            Some(edit) => {
+                edit.redo(level);
+                self.undo.push_back(edit);
+                true
+            },
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::*;
+use super::super::level::Spawn;
+
+fn tiles(level: &Level) -> Vec<Vec<Tile>> {
+    level.tile_layers()
+        .map(|layer| {
+            let tiles = &layer.tiles;
+            (0..tiles.height())
+                .flat_map(|y| (0..tiles.width()).map(move |x| tiles[(x, y)]))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn stroke_is_one_step() {
+    let mut level = Level::new(4, 3);
+    let mut history = History::new(10);
+    let empty = tiles(&level);
+
+    for x in 0..4 {
+        history.set_tile(&mut level, 1, V2::new(x, 0), Tile(1));
+    }
+    // painting over the same tile twice
+    history.set_tile(&mut level, 1, V2::new(0, 0), Tile(2));
+    history.end_group();
+    let painted = tiles(&level);
+
+    assert!(history.undo(&mut level));
+    assert_eq!(tiles(&level), empty);
+    assert!(!history.undo(&mut level));
+
+    assert!(history.redo(&mut level));
+    assert_eq!(tiles(&level), painted);
+    assert!(!history.redo(&mut level));
+}
+
+#[test]
+fn resize_brings_back_cut_tiles() {
+    let mut level = Level::new(4, 3);
+    let mut history = History::new(10);
+    history.set_tile(&mut level, 0, V2::new(3, 2), Tile(1));
+    history.set_tile(&mut level, 2, V2::new(3, 0), Tile(2));
+    history.end_group();
+    let before = tiles(&level);
+    let spawn = Spawn { kind: "player".into(), pos: V2::new(1.5, 0.5), props: Default::default() };
+    level.spawns.push(spawn.clone());
+
+    history.resize(&mut level, 2, 2, V2::new(0, 0));
+    assert_eq!(level.dim(), V2::new(2, 2));
+    history.resize(&mut level, 5, 5, V2::new(1, 2));
+    let after = tiles(&level);
+
+    assert!(history.undo(&mut level));
+    assert!(history.undo(&mut level));
+    assert_eq!(level.dim(), V2::new(4, 3));
+    assert_eq!(tiles(&level), before);
+    assert_eq!(level.spawns, [spawn.clone()]);
+
+    assert!(history.redo(&mut level));
+    assert!(history.redo(&mut level));
+    assert_eq!(tiles(&level), after);
+    assert_eq!(level.spawns[0].pos, V2::new(2.5, 2.5));
+}
+
+#[test]
+fn new_edit_drops_redo_and_limit_drops_oldest() {
+    let mut level = Level::new(4, 1);
+    let mut history = History::new(2);
+    for x in 0..4 {
+        history.set_tile(&mut level, 1, V2::new(x, 0), Tile(1));
+        history.end_group();
+    }
+    assert!(history.undo(&mut level));
+    assert!(history.undo(&mut level));
+    assert!(!history.undo(&mut level));
+    assert_eq!(level.collision()[(1, 0)], Tile(1));
+    assert_eq!(level.collision()[(2, 0)], Tile::EMPTY);
+
+    history.set_tile(&mut level, 1, V2::new(3, 0), Tile(2));
+    assert!(!history.redo(&mut level));
+    assert_eq!(level.collision()[(3, 0)], Tile(2));
+}
+
+#[test]
+fn spawn_changes_are_steps() {
+    let mut level = Level::new(4, 4);
+    let mut history = History::new(10);
+    let spawn = Spawn { kind: "enemy".into(), pos: V2::new(1.5, 1.5), props: Default::default() };
+
+    let before = level.spawns.clone();
+    history.spawns_changed(&level, before.clone());
+    assert!(!history.undo(&mut level));
+
+    level.spawns.push(spawn.clone());
+    history.spawns_changed(&level, before);
+    assert!(history.undo(&mut level));
+    assert!(level.spawns.is_empty());
+    assert!(history.redo(&mut level));
+    assert_eq!(level.spawns, [spawn]);
+}
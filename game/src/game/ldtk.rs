@@ -0,0 +1,470 @@
+/* Import of LDtk projects (.ldtk JSON, with levels inside or in separate .ldtkl files).
+
+   Every LDtk level becomes a `Level`, all of them share one `TileSet`:
+    - IntGrid values get tile ids first, in the order of layer definitions.
+      Identifier "one_way" makes a value one-way, "hazard" makes it a hazard,
+      anything else is solid. Values of layers without auto-tiles are drawn
+      as squares of the value's color, otherwise they are invisible and
+      auto-tiles are drawn by a separate layer right behind it.
+    - Tiles of tile and auto layers get the next ids, in the order they are first used.
+      Tiles stacked in one cell go to extra layers in front of the first one.
+    - All ids must fit into 1..=255.
+
+   The IntGrid layer named "collision" (or the top-most IntGrid layer if there is no such layer)
+   is the collision layer, layers behind it are background and in front of it foreground.
+
+   Entities become spawns: kind is the lowercase identifier, position is the center
+   of the entity in tiles, fields with number, bool and string values become properties.
+   Level fields become level metadata, along with the level's identifier as `name`.
+
+   Flipped tiles, layer offsets, layers with different grid sizes and projects
+   with several worlds are errors.
+*/
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+};
+use crate::{
+    render::{Bitmap, Color},
+    geom::vector::prelude::*,
+    file::{prelude::*, invalid_data, json::{self, Value}},
+};
+use super::{
+    tilemap::{Tilemap, Tile, check_size},
+    tileset::{TileSet, TileDef, TileProperties, DEFAULT_FRAME_TIME},
+    level::{Level, Layer, TileLayer, TileLayerRole, Spawn},
+};
+
+#[cfg(test)]
+mod tests;
+
+pub struct LdtkLevel {
+    pub name: String,
+    pub level: Level,
+    /// Bottom left corner of the level in the world, in tiles
+    pub world_pos: V2i,
+}
+
+pub struct LdtkProject {
+    pub levels: Vec<LdtkLevel>,
+    pub tileset: TileSet,
+}
+
+pub fn import(filepath: impl AsRef<Path>) -> io::Result<LdtkProject> {
+    let filepath = filepath.as_ref();
+    let folder = filepath.parent().map(PathBuf::from).unwrap_or_default();
+    let root = read_json(filepath)?;
+    import_project(&root, &folder)
+        .map_err(|e| invalid_data(format!("{}: {}", filepath.display(), e)))
+}
+
+fn read_json(filepath: &Path) -> io::Result<Value> {
+    let text = String::from_utf8(crate::file::read_all(filepath)?)
+        .map_err(|e| invalid_data(format!("{}: not valid UTF-8: {}", filepath.display(), e)))?;
+    json::parse(&text).map_err(|e| invalid_data(format!("{}: {}", filepath.display(), e)))
+}
+
+fn unsupported(what: &str) -> io::Error {
+    invalid_data(format!("{} is not supported", what))
+}
+
+fn int_field(value: &Value, key: &str) -> io::Result<i64> {
+    value.field(key)?.as_i64().map_err(|e| invalid_data(format!("`{}`: {}", key, e)))
+}
+
+/// Biggest grid cell in pixels, tiles of this size are cut from images and filled for IntGrid values
+const MAX_GRID: i64 = 1024;
+
+/// Grid size in pixels, from 1 to `MAX_GRID`
+fn grid_field(value: &Value, key: &str) -> io::Result<i32> {
+    let grid = int_field(value, key)?;
+    if !(1..=MAX_GRID).contains(&grid) {
+        return Err(invalid_data(format!("`{}`: invalid grid size {}", key, grid)));
+    }
+    Ok(grid as i32)
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> io::Result<&'a str> {
+    value.field(key)?.as_str().map_err(|e| invalid_data(format!("`{}`: {}", key, e)))
+}
+
+/// Array field, missing and null fields are empty
+fn array_field<'a>(value: &'a Value, key: &str) -> io::Result<&'a [Value]> {
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(&[]),
+        Some(array) => array.as_array().map_err(|e| invalid_data(format!("`{}`: {}", key, e))),
+    }
+}
+
+/// Two numbers, like `px` or `__grid`
+fn pair(value: &Value, key: &str) -> io::Result<(f64, f64)> {
+    match array_field(value, key)? {
+        [x, y] => Ok((x.as_f64()?, y.as_f64()?)),
+        _ => Err(invalid_data(format!("`{}` must have two numbers", key))),
+    }
+}
+
+/// Fields with simple values as strings
+fn fields(value: &Value) -> io::Result<BTreeMap<String, String>> {
+    let mut result = BTreeMap::new();
+    for field in array_field(value, "fieldInstances")? {
+        let name = str_field(field, "__identifier")?;
+        let value = match field.field("__value")? {
+            Value::Null => continue,
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.clone(),
+            other => return Err(invalid_data(format!(
+                "field {}: {} values are not supported", name, other.type_name(),
+            ))),
+        };
+        result.insert(name.to_string(), value);
+    }
+    Ok(result)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum TileKey {
+    IntGrid { layer_uid: i64, value: i64 },
+    Tile { tileset_uid: i64, id: i64 },
+}
+
+/// Gives out tile ids and defines tiles in the tile set
+struct Palette<'a> {
+    folder: &'a Path,
+    tileset_defs: &'a [Value],
+    ids: BTreeMap<TileKey, Tile>,
+    /// Images of LDtk tilesets by uid
+    images: BTreeMap<i64, Bitmap>,
+    tileset: TileSet,
+}
+
+impl<'a> Palette<'a> {
+    fn next_id(&self) -> io::Result<Tile> {
+        let count = self.ids.len() + 1;
+        if count > u8::max_value() as usize {
+            return Err(invalid_data("project uses more than 255 different tiles".into()));
+        }
+        Ok(Tile(count as u8))
+    }
+
+    fn define_int_grid(&mut self, layer_def: &Value) -> io::Result<()> {
+        let layer_uid = int_field(layer_def, "uid")?;
+        let layer_name = str_field(layer_def, "identifier")?;
+        let grid = grid_field(layer_def, "gridSize")?;
+        let has_auto_tiles = [layer_def.get("autoTilesetDefUid"), layer_def.get("tilesetDefUid")].iter()
+            .any(|uid| uid.map_or(false, |uid| !uid.is_null()));
+
+        for value_def in array_field(layer_def, "intGridValues")? {
+            let value = int_field(value_def, "value")?;
+            let name = match value_def.get("identifier") {
+                Some(Value::String(name)) => name.clone(),
+                _ => format!("{}_{}", layer_name, value),
+            };
+            let props = match name.as_str() {
+                "one_way" => TileProperties { one_way: true, ..TileProperties::default() },
+                "hazard" => TileProperties { hazard: true, ..TileProperties::default() },
+                _ => TileProperties { solid: true, ..TileProperties::default() },
+            };
+            let frames = if has_auto_tiles {
+                Vec::new()
+            } else {
+                vec![Bitmap::with_dimensions(grid, grid).filled(parse_color(str_field(value_def, "color")?)?)]
+            };
+
+            let tile = self.next_id()?;
+            self.ids.insert(TileKey::IntGrid { layer_uid, value }, tile);
+            self.tileset.define(tile, TileDef { name, frames, frame_time: DEFAULT_FRAME_TIME, autotile: None, props });
+        }
+        Ok(())
+    }
+
+    fn int_grid(&self, layer_uid: i64, value: i64) -> io::Result<Tile> {
+        self.ids.get(&TileKey::IntGrid { layer_uid, value }).copied()
+            .ok_or_else(|| invalid_data(format!("IntGrid value {} is not defined", value)))
+    }
+
+    /// Id of a tile from an LDtk tileset, defines it on first use
+    fn tile(&mut self, tileset_uid: i64, id: i64, src: (i32, i32)) -> io::Result<Tile> {
+        let key = TileKey::Tile { tileset_uid, id };
+        if let Some(&tile) = self.ids.get(&key) {
+            return Ok(tile);
+        }
+
+        let def = self.tileset_defs.iter()
+            .find(|def| def.get("uid").and_then(|uid| uid.as_i64().ok()) == Some(tileset_uid))
+            .ok_or_else(|| invalid_data(format!("tileset {} is not defined", tileset_uid)))?;
+        let name = str_field(def, "identifier")?;
+        let grid = grid_field(def, "tileGridSize")?;
+
+        if !self.images.contains_key(&tileset_uid) {
+            let rel_path = def.get("relPath")
+                .and_then(|path| path.as_str().ok())
+                .ok_or_else(|| unsupported(&format!("tileset {} without an image", name)))?;
+            let path = self.folder.join(rel_path);
+            let image = Bitmap::load(&path)
+                .map_err(|e| invalid_data(format!("can't load {}: {:?}", path.display(), e)))?;
+            self.images.insert(tileset_uid, image);
+        }
+        let image = &self.images[&tileset_uid];
+        if src.0 < 0 || src.1 < 0 || src.0 + grid > image.width() || src.1 + grid > image.height() {
+            return Err(invalid_data(format!("tile {} is outside of the image of tileset {}", id, name)));
+        }
+        let bmp = image.sub_bitmap(V2::new(src.0, src.1), V2::new(grid, grid));
+
+        let tile = self.next_id()?;
+        self.ids.insert(key, tile);
+        self.tileset.define(tile, TileDef {
+            name: format!("{}_{}", name, id),
+            frames: vec![bmp],
+            frame_time: DEFAULT_FRAME_TIME,
+            autotile: None,
+            props: TileProperties::default(),
+        });
+        Ok(tile)
+    }
+}
+
+/// "#RRGGBB"
+fn parse_color(color: &str) -> io::Result<Color> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6);
+    let rgb = hex.and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| invalid_data(format!("invalid color {}", color)))?;
+    Ok(Color { r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: rgb as u8, a: 0xFF })
+}
+
+fn import_project(root: &Value, folder: &Path) -> io::Result<LdtkProject> {
+    // projects made with "multi-worlds" have levels inside of worlds
+    let (levels, layout) = match array_field(root, "worlds")? {
+        [] => (array_field(root, "levels")?, root.get("worldLayout")),
+        [world] => (array_field(world, "levels")?, world.get("worldLayout")),
+        _ => return Err(unsupported("project with several worlds")),
+    };
+    let layout = match layout {
+        Some(Value::String(layout)) => layout.as_str(),
+        _ => "Free",
+    };
+
+    let defs = root.field("defs")?;
+    let mut palette = Palette {
+        folder,
+        tileset_defs: array_field(defs, "tilesets")?,
+        ids: BTreeMap::new(),
+        images: BTreeMap::new(),
+        tileset: TileSet::new(),
+    };
+    for layer_def in array_field(defs, "layers")? {
+        if str_field(layer_def, "type")? == "IntGrid" {
+            palette.define_int_grid(layer_def)?;
+        }
+    }
+
+    let mut result = Vec::new();
+    // position of the next level for linear layouts, in tiles
+    let mut linear_pos = V2::new(0, 0);
+    for level in levels {
+        let name = str_field(level, "identifier")?.to_string();
+        let context = |e: io::Error| invalid_data(format!("level {}: {}", name, e));
+
+        // levels saved in separate files
+        let external;
+        let level = match level.get("externalRelPath") {
+            Some(Value::String(path)) if level.get("layerInstances").map_or(true, Value::is_null) => {
+                external = read_json(&folder.join(path)).map_err(context)?;
+                &external
+            },
+            _ => level,
+        };
+
+        let (imported, grid) = import_level(level, &mut palette).map_err(context)?;
+        let size = imported.dim();
+
+        let world_pos = match layout {
+            "LinearHorizontal" => {
+                let pos = linear_pos;
+                linear_pos.x += size.x;
+                pos
+            },
+            "LinearVertical" => {
+                // levels go down
+                linear_pos.y -= size.y;
+                linear_pos
+            },
+            _ => {
+                let world_x = int_field(level, "worldX").map_err(context)?;
+                let world_y = int_field(level, "worldY").map_err(context)?;
+                let grid = i64::from(grid);
+                // world y goes down and points to the top of the level
+                V2::new(world_x.div_euclid(grid) as i32, -(world_y.div_euclid(grid) as i32) - size.y)
+            },
+        };
+
+        result.push(LdtkLevel { name, level: imported, world_pos });
+    }
+
+    Ok(LdtkProject { levels: result, tileset: palette.tileset })
+}
+
+/// Level and its grid size in pixels
+fn import_level(level: &Value, palette: &mut Palette) -> io::Result<(Level, i32)> {
+    let layer_instances = array_field(level, "layerInstances")?;
+    if layer_instances.is_empty() {
+        return Err(invalid_data("level has no layers".into()));
+    }
+
+    let first = &layer_instances[0];
+    let grid = grid_field(first, "__gridSize")?;
+    let (width, height) = check_size(int_field(first, "__cWid")?, int_field(first, "__cHei")?)?;
+
+    // the top-most layer named "collision", or just the top-most one
+    let int_grid_names: Vec<&str> = layer_instances.iter()
+        .filter(|layer| layer.get("__type") == Some(&Value::String("IntGrid".into())))
+        .filter_map(|layer| layer.get("__identifier").and_then(|name| name.as_str().ok()))
+        .collect();
+    let collision_name = int_grid_names.iter()
+        .find(|name| name.eq_ignore_ascii_case("collision"))
+        .or_else(|| int_grid_names.first())
+        .copied()
+        .ok_or_else(|| invalid_data("level has no IntGrid layer for collision".into()))?;
+
+    let mut layers = Vec::new();
+    let mut spawns = Vec::new();
+    let mut collision_found = false;
+
+    // LDtk layers go from front to back
+    for layer in layer_instances.iter().rev() {
+        let name = str_field(layer, "__identifier")?;
+        let context = |e: io::Error| invalid_data(format!("layer {}: {}", name, e));
+
+        if int_field(layer, "__gridSize")? != i64::from(grid) {
+            return Err(context(unsupported("layers with different grid sizes")));
+        }
+        if int_field(layer, "__cWid")? != i64::from(width) || int_field(layer, "__cHei")? != i64::from(height) {
+            return Err(context(invalid_data("layer size is different from the level".into())));
+        }
+        let offset = (int_field(layer, "pxOffsetX").unwrap_or(0), int_field(layer, "pxOffsetY").unwrap_or(0));
+        if offset != (0, 0) {
+            return Err(context(unsupported("layer offset")));
+        }
+
+        let role = if collision_found { TileLayerRole::Foreground } else { TileLayerRole::Background };
+        let tile_layers = |palette: &mut Palette, key: &str| -> io::Result<Vec<Layer>> {
+            let tilemaps = import_tiles(layer, key, palette, width, height, grid)?;
+            Ok(tilemaps.into_iter()
+                .enumerate()
+                .map(|(i, tiles)| {
+                    let name = if i == 0 { name.to_string() } else { format!("{}_{}", name, i) };
+                    Layer::Tiles(TileLayer { name, role, tiles })
+                })
+                .collect())
+        };
+
+        match str_field(layer, "__type")? {
+            "IntGrid" => {
+                layers.extend(tile_layers(palette, "autoLayerTiles").map_err(context)?);
+
+                let layer_uid = int_field(layer, "layerDefUid").map_err(context)?;
+                let values = array_field(layer, "intGridCsv").map_err(context)?;
+                if values.len() != (width * height) as usize {
+                    return Err(context(invalid_data(format!(
+                        "expected {} IntGrid values, found {}", width * height, values.len(),
+                    ))));
+                }
+                let mut tiles = Tilemap::new(width, height);
+                for (i, value) in values.iter().enumerate() {
+                    let value = value.as_i64().map_err(context)?;
+                    if value != 0 {
+                        // LDtk rows go from the top
+                        let (x, row) = (i as i32 % width, i as i32 / width);
+                        tiles.set(x, height - 1 - row, palette.int_grid(layer_uid, value).map_err(context)?);
+                    }
+                }
+
+                let is_collision = !collision_found && name == collision_name;
+                collision_found |= is_collision;
+                let role = if is_collision { TileLayerRole::Collision } else { role };
+                layers.push(Layer::Tiles(TileLayer { name: name.into(), role, tiles }));
+            },
+            "Tiles" => layers.extend(tile_layers(palette, "gridTiles").map_err(context)?),
+            "AutoLayer" => layers.extend(tile_layers(palette, "autoLayerTiles").map_err(context)?),
+            "Entities" => {
+                for entity in array_field(layer, "entityInstances")? {
+                    spawns.push(import_entity(entity, grid, height).map_err(context)?);
+                }
+            },
+            other => return Err(context(unsupported(&format!("{} layer", other)))),
+        }
+    }
+
+    let mut meta = fields(level)?;
+    meta.insert("name".into(), str_field(level, "identifier")?.into());
+
+    let level = Level { layers, spawns, meta }.validate()?;
+    Ok((level, grid))
+}
+
+/// Tiles from `key` (gridTiles or autoLayerTiles), stacked tiles go to the next tilemaps
+fn import_tiles(
+    layer: &Value,
+    key: &str,
+    palette: &mut Palette,
+    width: i32,
+    height: i32,
+    grid: i32,
+) -> io::Result<Vec<Tilemap>> {
+    let tiles = array_field(layer, key)?;
+    if tiles.is_empty() {
+        return Ok(Vec::new());
+    }
+    let tileset_uid = match layer.get("__tilesetDefUid") {
+        Some(Value::Number(uid)) => *uid as i64,
+        _ => return Err(invalid_data("layer with tiles has no tileset".into())),
+    };
+
+    let mut tilemaps: Vec<Tilemap> = Vec::new();
+    for tile in tiles {
+        if int_field(tile, "f")? != 0 {
+            return Err(unsupported("flipped tile"));
+        }
+        let (px_x, px_y) = pair(tile, "px")?;
+        let (src_x, src_y) = pair(tile, "src")?;
+        let id = int_field(tile, "t")?;
+
+        let (x, row) = ((px_x as i32).div_euclid(grid), (px_y as i32).div_euclid(grid));
+        if !(0..width).contains(&x) || !(0..height).contains(&row) {
+            return Err(invalid_data(format!("tile at {} {} is outside of the layer", px_x, px_y)));
+        }
+        let y = height - 1 - row;
+
+        let tile = palette.tile(tileset_uid, id, (src_x as i32, src_y as i32))?;
+        let free = tilemaps.iter().position(|tilemap| tilemap[(x, y)] == Tile::EMPTY);
+        let tilemap = match free {
+            Some(i) => &mut tilemaps[i],
+            None => {
+                tilemaps.push(Tilemap::new(width, height));
+                tilemaps.last_mut().unwrap()
+            },
+        };
+        tilemap.set(x, y, tile);
+    }
+    Ok(tilemaps)
+}
+
+fn import_entity(entity: &Value, grid: i32, height: i32) -> io::Result<Spawn> {
+    let kind = str_field(entity, "__identifier")?.to_lowercase();
+    let (px_x, px_y) = pair(entity, "px")?;
+    let (pivot_x, pivot_y) = pair(entity, "__pivot")?;
+    let (w, h) = (entity.get("width").map_or(Ok(0.0), Value::as_f64)?, entity.get("height").map_or(Ok(0.0), Value::as_f64)?);
+
+    // px is where the pivot is
+    let center_x = px_x - pivot_x * w + w * 0.5;
+    let center_y = px_y - pivot_y * h + h * 0.5;
+    let grid = f64::from(grid);
+
+    Ok(Spawn {
+        kind,
+        pos: V2::new((center_x / grid) as f32, (f64::from(height) - center_y / grid) as f32),
+        props: fields(entity)?,
+    })
+}
@@ -0,0 +1,125 @@
+use super::*;
+
+/// Two 3x2 levels side by side, grid size 8
+const PROJECT: &str = r##"{
+    "jsonVersion": "1.5.3",
+    "worldLayout": "Free",
+    "defs": {
+        "layers": [
+            {
+                "uid": 1, "identifier": "Collision", "type": "IntGrid", "gridSize": 8,
+                "autoTilesetDefUid": null, "tilesetDefUid": null,
+                "intGridValues": [
+                    { "value": 1, "identifier": "ground", "color": "#804020" },
+                    { "value": 2, "identifier": "hazard", "color": "#FF0000" }
+                ]
+            },
+            { "uid": 2, "identifier": "Entities", "type": "Entities", "gridSize": 8, "intGridValues": [] }
+        ],
+        "tilesets": []
+    },
+    "levels": [
+        {
+            "identifier": "Start", "worldX": 0, "worldY": 0,
+            "fieldInstances": [ { "__identifier": "music", "__value": "calm" } ],
+            "layerInstances": [
+                {
+                    "__identifier": "Entities", "__type": "Entities", "__gridSize": 8, "__cWid": 3, "__cHei": 2,
+                    "layerDefUid": 2, "pxOffsetX": 0, "pxOffsetY": 0,
+                    "entityInstances": [
+                        {
+                            "__identifier": "Player", "__pivot": [0.5, 1], "px": [4, 16], "width": 8, "height": 16,
+                            "fieldInstances": [
+                                { "__identifier": "hp", "__value": 3 },
+                                { "__identifier": "key", "__value": null }
+                            ]
+                        }
+                    ]
+                },
+                {
+                    "__identifier": "Collision", "__type": "IntGrid", "__gridSize": 8, "__cWid": 3, "__cHei": 2,
+                    "layerDefUid": 1, "pxOffsetX": 0, "pxOffsetY": 0,
+                    "intGridCsv": [0, 0, 2, 1, 1, 1]
+                }
+            ]
+        },
+        {
+            "identifier": "Next", "worldX": 24, "worldY": -8,
+            "layerInstances": [
+                {
+                    "__identifier": "Collision", "__type": "IntGrid", "__gridSize": 8, "__cWid": 3, "__cHei": 2,
+                    "layerDefUid": 1, "pxOffsetX": 0, "pxOffsetY": 0,
+                    "intGridCsv": [0, 0, 0, 1, 1, 1]
+                }
+            ]
+        }
+    ]
+}"##;
+
+fn import_str(text: &str) -> io::Result<LdtkProject> {
+    import_project(&json::parse(text)?, Path::new(""))
+}
+
+#[test]
+fn import_levels_int_grid_and_entities() {
+    let project = import_str(PROJECT).unwrap();
+    assert_eq!(project.levels.len(), 2);
+
+    let start = &project.levels[0];
+    assert_eq!(start.name, "Start");
+    assert_eq!(start.world_pos, V2::new(0, -2));
+    assert_eq!(start.level.meta.get("name").map(String::as_str), Some("Start"));
+    assert_eq!(start.level.meta.get("music").map(String::as_str), Some("calm"));
+
+    // LDtk rows go from the top, ours from the bottom
+    let collision = start.level.collision();
+    assert_eq!(collision[(0, 0)], Tile(1));
+    assert_eq!(collision[(0, 1)], Tile::EMPTY);
+    assert_eq!(collision[(2, 1)], Tile(2));
+    assert!(project.tileset.props(Tile(1)).solid);
+    assert!(project.tileset.props(Tile(2)).hazard);
+
+    let player = start.level.spawn("player").unwrap();
+    assert_eq!(player.pos, V2::new(0.5, 1.0));
+    assert_eq!(player.props.get("hp").map(String::as_str), Some("3"));
+    assert!(!player.props.contains_key("key"));
+
+    let next = &project.levels[1];
+    assert_eq!(next.world_pos, V2::new(3, -1));
+    assert_eq!(next.level.collision()[(1, 0)], Tile(1));
+}
+
+#[test]
+fn linear_layout() {
+    let project = import_str(&PROJECT.replace("\"Free\"", "\"LinearHorizontal\"")).unwrap();
+    assert_eq!(project.levels[0].world_pos, V2::new(0, 0));
+    assert_eq!(project.levels[1].world_pos, V2::new(3, 0));
+
+    let project = import_str(&PROJECT.replace("\"Free\"", "\"LinearVertical\"")).unwrap();
+    assert_eq!(project.levels[0].world_pos, V2::new(0, -2));
+    assert_eq!(project.levels[1].world_pos, V2::new(0, -4));
+}
+
+#[test]
+fn unsupported_features_are_errors() {
+    let cases = [
+        ("\"pxOffsetX\": 0, \"pxOffsetY\": 0,\n                    \"intGridCsv\": [0, 0, 2", "\"pxOffsetX\": 4, \"pxOffsetY\": 0,\n                    \"intGridCsv\": [0, 0, 2"),
+        ("[0, 0, 2, 1, 1, 1]", "[0, 0, 2, 1, 1]"),
+        ("[0, 0, 2, 1, 1, 1]", "[0, 0, 7, 1, 1, 1]"),
+        ("\"__value\": 3", "\"__value\": [3]"),
+        ("\"#FF0000\"", "\"red\""),
+        ("\"worldLayout\": \"Free\",", "\"worlds\": [{}, {}],"),
+        ("\"__cWid\": 3", "\"__cWid\": 4294967299"),
+        ("\"__cWid\": 3, \"__cHei\": 2", "\"__cWid\": 65536, \"__cHei\": 65536"),
+        ("\"__gridSize\": 8", "\"__gridSize\": 4294967304"),
+        ("\"__gridSize\": 8", "\"__gridSize\": 0"),
+        ("\"type\": \"IntGrid\", \"gridSize\": 8", "\"type\": \"IntGrid\", \"gridSize\": 4294967304"),
+        ("\"type\": \"IntGrid\", \"gridSize\": 8", "\"type\": \"IntGrid\", \"gridSize\": -8"),
+        ("\"type\": \"IntGrid\", \"gridSize\": 8", "\"type\": \"IntGrid\", \"gridSize\": 1000000"),
+    ];
+    for (from, to) in cases.iter() {
+        let project = PROJECT.replacen(from, to, 1);
+        assert_ne!(project, PROJECT, "{:?} is not in the project", from);
+        assert!(import_str(&project).is_err(), "{:?} is not an error", to);
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use crate::{
+    render::{
+        self,
+        Bitmap,
+        canvas_info::CanvasInfo,
+        space::{WorldPos, CanvasPos},
+    },
+    geom::vector::prelude::*,
+    file::{prelude::*, BinaryReader, BinaryWriter, invalid_data},
+};
+use super::{
+    tilemap::Tilemap,
+    tileset::TileSet,
+};
+
+mod text;
+#[cfg(test)]
+mod tests;
+
+// Layers
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TileLayerRole {
+    /// Drawn behind entities, doesn't collide
+    Background,
+    /// Drawn behind entities, the only layer entities collide with
+    Collision,
+    /// Drawn in front of entities, doesn't collide
+    Foreground,
+}
+
+pub struct TileLayer {
+    pub name: String,
+    pub role: TileLayerRole,
+    pub tiles: Tilemap,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Repeat {
+    No,
+    X,
+    Y,
+    Both,
+}
+
+pub struct ImageLayer {
+    pub name: String,
+    pub path: String,
+    pub bmp: Bitmap,
+    /// 0.0 - doesn't move with the camera, 1.0 - moves with the world
+    pub parallax: V2f,
+    /// Bottom left corner of the image in world units (at parallax 1.0)
+    pub offset: V2f,
+    pub repeat: Repeat,
+    pub in_front: bool,
+}
+
+pub enum Layer {
+    Tiles(TileLayer),
+    Image(ImageLayer),
+}
+
+impl Layer {
+    pub fn is_in_front(&self) -> bool {
+        match self {
+            Layer::Tiles(layer) => layer.role == TileLayerRole::Foreground,
+            Layer::Image(layer) => layer.in_front,
+        }
+    }
+
+    fn draw(&self, canvas: &Bitmap, canvas_info: &CanvasInfo, tileset: &TileSet) {
+        match self {
+            Layer::Tiles(layer) => layer.tiles.draw(canvas, canvas_info, tileset),
+            Layer::Image(layer) => layer.draw(canvas, canvas_info),
+        }
+    }
+}
+
+impl ImageLayer {
+    pub fn draw(&self, canvas: &Bitmap, canvas_info: &CanvasInfo) {
+        // position that is `offset` on screen when camera is at the origin,
+        // moved by the part of camera movement that this layer doesn't follow
+        let parallax = V2::new(1.0 - self.parallax.x, 1.0 - self.parallax.y);
+        let anchor = self.offset + V2::new(canvas_info.camera.x * parallax.x, canvas_info.camera.y * parallax.y);
+        let CanvasPos(bottom_left) = canvas_info.world_to_canvas(WorldPos(anchor));
+
+        let size: V2i = (V2f::from(self.bmp.dim()) * canvas_info.zoom).round().into();
+        if size.x <= 0 || size.y <= 0 {
+            return
+        }
+        let top_left = V2::new(bottom_left.x, bottom_left.y - size.y);
+
+        let (repeat_x, repeat_y) = match self.repeat {
+            Repeat::No => (false, false),
+            Repeat::X => (true, false),
+            Repeat::Y => (false, true),
+            Repeat::Both => (true, true),
+        };
+
+        let axis = |repeat: bool, start: i32, size: i32, canvas_size: i32| if repeat {
+            repeated_copies(start, size, canvas_size)
+        } else {
+            (start, 1)
+        };
+        let (first_x, count_x) = axis(repeat_x, top_left.x, size.x, canvas.width());
+        let (first_y, count_y) = axis(repeat_y, top_left.y, size.y, canvas.height());
+
+        for j in 0..count_y {
+            for i in 0..count_x {
+                let p = V2::new(first_x + i * size.x, first_y + j * size.y);
+                render::draw_bmp_scaled(canvas, &self.bmp, p, canvas_info.zoom);
+            }
+        }
+    }
+}
+
+/// Start of the first copy of an image repeated along an axis, with a copy at `start`,
+/// that begins before the canvas edge, and how many copies cover the canvas
+fn repeated_copies(start: i32, size: i32, canvas_size: i32) -> (i32, i32) {
+    let first = start.rem_euclid(size) - size;
+    (first, (canvas_size - first) / size + 1)
+}
+
+// Spawns
+
+/// Where an entity appears when the level starts
+#[derive(Clone, PartialEq, Debug)]
+pub struct Spawn {
+    /// e.g. "player", "enemy"
+    pub kind: String,
+    /// Entity origin in world units
+    pub pos: V2f,
+    /// Kind specific, e.g. hp of an enemy
+    pub props: BTreeMap<String, String>,
+}
+
+// Level
+
+/// Tiles, spawns and meta of a level. Image layers aren't copied, nothing changes them
+pub struct LevelSnapshot {
+    tiles: Vec<Tilemap>,
+    spawns: Vec<Spawn>,
+    meta: BTreeMap<String, String>,
+}
+
+/// Layers are ordered from back to front.
+///
+/// All tile layers have the same size and there is always exactly one collision layer
+pub struct Level {
+    pub layers: Vec<Layer>,
+    pub spawns: Vec<Spawn>,
+    /// Free-form, e.g. name or author
+    pub meta: BTreeMap<String, String>,
+}
+
+impl Level {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self::from_collision(Tilemap::new(width, height))
+    }
+
+    /// Level with empty background and foreground around `tiles`
+    pub fn from_collision(tiles: Tilemap) -> Self {
+        let (width, height) = (tiles.width(), tiles.height());
+        let layer = |name: &str, role, tiles| Layer::Tiles(TileLayer { name: name.into(), role, tiles });
+        Self {
+            layers: vec![
+                layer("background", TileLayerRole::Background, Tilemap::new(width, height)),
+                layer("collision", TileLayerRole::Collision, tiles),
+                layer("foreground", TileLayerRole::Foreground, Tilemap::new(width, height)),
+            ],
+            spawns: Vec::new(),
+            meta: BTreeMap::new(),
+        }
+    }
+
+    /// First spawn of `kind`
+    pub fn spawn(&self, kind: &str) -> Option<&Spawn> {
+        self.spawns.iter().find(|spawn| spawn.kind == kind)
+    }
+
+    /// Checks invariants that the rest of the code relies on,
+    /// for levels that were put together from parts
+    pub fn validate(self) -> io::Result<Self> {
+        let collision_layers = self.tile_layers().filter(|l| l.role == TileLayerRole::Collision).count();
+        if collision_layers != 1 {
+            return Err(invalid_data(format!("expected 1 collision layer, found {}", collision_layers)));
+        }
+        let dim = self.dim();
+        if self.tile_layers().any(|layer| layer.tiles.dim() != dim) {
+            return Err(invalid_data("tile layers have different sizes".into()));
+        }
+        Ok(self)
+    }
+
+    pub fn width(&self) -> i32 { self.collision().width() }
+    pub fn height(&self) -> i32 { self.collision().height() }
+    pub fn dim(&self) -> V2i { self.collision().dim() }
+
+    pub fn collision(&self) -> &Tilemap {
+        self.tile_layers()
+            .find(|layer| layer.role == TileLayerRole::Collision)
+            .map(|layer| &layer.tiles)
+            .expect("Level without collision layer")
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Tiles(layer) => Some(layer),
+            Layer::Image(_) => None,
+        })
+    }
+
+    pub fn tile_layers_mut(&mut self) -> impl Iterator<Item = &mut TileLayer> {
+        self.layers.iter_mut().filter_map(|layer| match layer {
+            Layer::Tiles(layer) => Some(layer),
+            Layer::Image(_) => None,
+        })
+    }
+
+    /// Index into `tile_layers`
+    pub fn collision_layer_index(&self) -> usize {
+        self.tile_layers()
+            .position(|layer| layer.role == TileLayerRole::Collision)
+            .expect("Level without collision layer")
+    }
+
+    pub fn tile_layer_mut(&mut self, index: usize) -> Option<&mut TileLayer> {
+        self.tile_layers_mut().nth(index)
+    }
+
+    /// Resizes all tile layers, tile at `(x, y)` moves to `(x, y) + offset`.
+    /// Spawns and images move with the tiles
+    pub fn resize_with_offset(&mut self, new_width: i32, new_height: i32, offset: V2i) {
+        for layer in self.tile_layers_mut() {
+            layer.tiles.resize_with_offset(new_width, new_height, offset);
+        }
+        self.shift_objects(offset);
+    }
+
+    /// Moves spawns and images by `offset` tiles
+    pub fn shift_objects(&mut self, offset: V2i) {
+        let offset = V2f::from(offset);
+        for spawn in &mut self.spawns {
+            spawn.pos = spawn.pos + offset;
+        }
+        for layer in &mut self.layers {
+            if let Layer::Image(image) = layer {
+                image.offset = image.offset + offset;
+            }
+        }
+    }
+
+    /// Copy of what playing could change, see `restore`
+    pub fn snapshot(&self) -> LevelSnapshot {
+        LevelSnapshot {
+            tiles: self.tile_layers().map(|layer| layer.tiles.clone()).collect(),
+            spawns: self.spawns.clone(),
+            meta: self.meta.clone(),
+        }
+    }
+
+    /// Puts back what was in `snapshot`, the level must have the same layers as when it was taken
+    pub fn restore(&mut self, snapshot: LevelSnapshot) {
+        for (layer, tiles) in self.tile_layers_mut().zip(snapshot.tiles) {
+            layer.tiles = tiles;
+        }
+        self.spawns = snapshot.spawns;
+        self.meta = snapshot.meta;
+    }
+
+    /// Layers behind entities
+    pub fn draw_background(&self, canvas: &Bitmap, canvas_info: &CanvasInfo, tileset: &TileSet) {
+        for layer in self.layers.iter().filter(|layer| !layer.is_in_front()) {
+            layer.draw(canvas, canvas_info, tileset);
+        }
+    }
+
+    /// Layers in front of entities
+    pub fn draw_foreground(&self, canvas: &Bitmap, canvas_info: &CanvasInfo, tileset: &TileSet) {
+        for layer in self.layers.iter().filter(|layer| layer.is_in_front()) {
+            layer.draw(canvas, canvas_info, tileset);
+        }
+    }
+}
+
+/* File format, see `file::write_versioned` for the header:
+    magic           b"LEVL"
+    version         2
+    layer count     u32
+    layers          back to front
+
+    layer:
+        kind        u8      0 - tiles, 1 - image
+        name        str     (u32 length + UTF-8)
+
+        tiles:
+            role    u8      0 - background, 1 - collision, 2 - foreground
+            tilemap         see Tilemap::write_to
+
+        image:
+            path        str
+            parallax    f32 x, f32 y
+            offset      f32 x, f32 y
+            repeat      u8      0 - no, 1 - x, 2 - y, 3 - both
+            in_front    u8      0 - false, 1 - true
+
+    spawn count     u32
+    spawns:
+        kind        str
+        pos         f32 x, f32 y
+        props       map
+
+    meta            map
+
+    map:            u32 count, then str key and str value for each entry,
+                    keys are sorted
+
+   Version 1 had no spawns and meta.
+
+   Files without the magic are treated as a single tilemap (see `Tilemap::from_bytes`),
+   which becomes the collision layer.
+
+   When the layout changes, bump VERSION and keep reading the old versions in `from_bytes`.
+*/
+const MAGIC: &[u8; 4] = b"LEVL";
+const VERSION: u32 = 2;
+
+impl Level {
+    pub fn from_bytes(file: &[u8]) -> io::Result<Self> {
+        match crate::file::read_versioned(file, MAGIC)? {
+            Some((version, payload)) if (1..=VERSION).contains(&version) => Self::read_from(payload, version),
+            Some((version, _)) => Err(invalid_data(format!(
+                "unsupported level version {}, newest supported is {}", version, VERSION,
+            ))),
+            None => Tilemap::from_bytes(file).map(Self::from_collision),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BinaryWriter::new();
+        writer.write_u32(self.layers.len() as u32);
+        for layer in &self.layers {
+            match layer {
+                Layer::Tiles(layer) => {
+                    writer.write_u8(0);
+                    writer.write_str(&layer.name);
+                    writer.write_u8(match layer.role {
+                        TileLayerRole::Background => 0,
+                        TileLayerRole::Collision => 1,
+                        TileLayerRole::Foreground => 2,
+                    });
+                    layer.tiles.write_to(&mut writer);
+                },
+                Layer::Image(layer) => {
+                    writer.write_u8(1);
+                    writer.write_str(&layer.name);
+                    writer.write_str(&layer.path);
+                    writer.write_f32(layer.parallax.x);
+                    writer.write_f32(layer.parallax.y);
+                    writer.write_f32(layer.offset.x);
+                    writer.write_f32(layer.offset.y);
+                    writer.write_u8(match layer.repeat {
+                        Repeat::No => 0,
+                        Repeat::X => 1,
+                        Repeat::Y => 2,
+                        Repeat::Both => 3,
+                    });
+                    writer.write_u8(layer.in_front as u8);
+                },
+            }
+        }
+
+        writer.write_u32(self.spawns.len() as u32);
+        for spawn in &self.spawns {
+            writer.write_str(&spawn.kind);
+            writer.write_f32(spawn.pos.x);
+            writer.write_f32(spawn.pos.y);
+            write_map(&mut writer, &spawn.props);
+        }
+        write_map(&mut writer, &self.meta);
+
+        crate::file::write_versioned(MAGIC, VERSION, &writer.into_bytes())
+    }
+
+    fn read_from(payload: &[u8], version: u32) -> io::Result<Self> {
+        let mut reader = BinaryReader::new(payload);
+        let layer_count = reader.read_u32()?;
+        let mut layers = Vec::new();
+        for _ in 0..layer_count {
+            let kind = reader.read_u8()?;
+            let name = reader.read_str()?;
+            let layer = match kind {
+                0 => Layer::Tiles(TileLayer {
+                    name,
+                    role: match reader.read_u8()? {
+                        0 => TileLayerRole::Background,
+                        1 => TileLayerRole::Collision,
+                        2 => TileLayerRole::Foreground,
+                        role => return Err(invalid_data(format!("invalid tile layer role: {}", role))),
+                    },
+                    tiles: Tilemap::read_from(&mut reader)?,
+                }),
+                1 => {
+                    let path = reader.read_str()?;
+                    let parallax = V2::new(reader.read_f32()?, reader.read_f32()?);
+                    let offset = V2::new(reader.read_f32()?, reader.read_f32()?);
+                    let repeat = match reader.read_u8()? {
+                        0 => Repeat::No,
+                        1 => Repeat::X,
+                        2 => Repeat::Y,
+                        3 => Repeat::Both,
+                        repeat => return Err(invalid_data(format!("invalid repeat mode: {}", repeat))),
+                    };
+                    let in_front = reader.read_u8()? != 0;
+                    let bmp = load_image(&path)?;
+
+                    Layer::Image(ImageLayer { name, path, bmp, parallax, offset, repeat, in_front })
+                },
+                kind => return Err(invalid_data(format!("invalid layer kind: {}", kind))),
+            };
+            layers.push(layer);
+        }
+
+        let mut spawns = Vec::new();
+        let mut meta = BTreeMap::new();
+        if version >= 2 {
+            let spawn_count = reader.read_u32()?;
+            for _ in 0..spawn_count {
+                let kind = reader.read_str()?;
+                let pos = V2::new(reader.read_f32()?, reader.read_f32()?);
+                let props = read_map(&mut reader)?;
+                spawns.push(Spawn { kind, pos, props });
+            }
+            meta = read_map(&mut reader)?;
+        }
+
+        if !reader.is_empty() {
+            return Err(invalid_data("unexpected data after the end of the level".into()));
+        }
+
+        Self { layers, spawns, meta }.validate()
+    }
+}
+
+fn load_image(path: &str) -> io::Result<Bitmap> {
+    Bitmap::load(path).map_err(|e| invalid_data(format!("can't load image layer {}: {:?}", path, e)))
+}
+
+fn write_map(writer: &mut BinaryWriter, map: &BTreeMap<String, String>) {
+    writer.write_u32(map.len() as u32);
+    for (key, value) in map {
+        writer.write_str(key);
+        writer.write_str(value);
+    }
+}
+
+fn read_map(reader: &mut BinaryReader) -> io::Result<BTreeMap<String, String>> {
+    let count = reader.read_u32()?;
+    let mut map = BTreeMap::new();
+    for _ in 0..count {
+        let key = reader.read_str()?;
+        map.insert(key, reader.read_str()?);
+    }
+    Ok(map)
+}
+
+/// Levels with this extension are saved and loaded as text, see `text`
+pub const TEXT_EXTENSION: &str = "txt";
+
+fn is_text(filepath: &Path) -> bool {
+    filepath.extension().map_or(false, |ext| ext == TEXT_EXTENSION)
+}
+
+impl Load for Level {
+    fn load(filepath: impl AsRef<Path>) -> io::Result<Self> {
+        let file = crate::file::read_all(&filepath)?;
+        if is_text(filepath.as_ref()) {
+            let file = String::from_utf8(file)
+                .map_err(|e| invalid_data(format!("level is not valid UTF-8: {}", e)))?;
+            text::from_text(&file)
+        } else {
+            Self::from_bytes(&file)
+        }
+    }
+}
+
+impl Save for Level {
+    fn save(&self, filepath: impl AsRef<Path>) -> io::Result<()> {
+        if is_text(filepath.as_ref()) {
+            text::check(self)?;
+            crate::file::write_all(filepath, text::to_text(self).as_bytes())
+        } else {
+            crate::file::write_all(filepath, &self.to_bytes())
+        }
+    }
+}
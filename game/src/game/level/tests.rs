@@ -0,0 +1,255 @@
+use super::*;
+use super::super::tilemap::Tile;
+use crate::{render::Color, geom::matrix::Mat2, test_utils::write_bmp};
+
+fn test_level() -> Level {
+    let mut level = Level::new(5, 3);
+    for (i, layer) in level.tile_layers_mut().enumerate() {
+        for x in 0..5 {
+            layer.tiles.set(x, i as i32, Tile(x as u8 * 60 + i as u8));
+        }
+    }
+    level.spawns.push(Spawn { kind: "player".into(), pos: V2::new(2.5, 1.25), props: BTreeMap::new() });
+    level.spawns.push(Spawn {
+        kind: "enemy".into(),
+        pos: V2::new(0.1, -3.0e-7),
+        props: vec![("hp".to_string(), "5".to_string())].into_iter().collect(),
+    });
+    level.meta.insert("name".into(), "Test level: one".into());
+    level
+}
+
+#[test]
+fn text_escapes_values() {
+    let mut level = test_level();
+    let odd = "a b=c\nd";
+    level.meta.insert(odd.into(), odd.into());
+    level.meta.insert("edges".into(), " \\ \u{a0}".into());
+    level.spawns.push(Spawn {
+        kind: odd.into(),
+        pos: V2::new(1.0, 1.0),
+        props: vec![(odd.to_string(), odd.to_string()), ("empty".to_string(), String::new())].into_iter().collect(),
+    });
+
+    let text = text::to_text(&level);
+    let loaded = text::from_text(&text).unwrap();
+    assert_eq!(loaded.meta, level.meta);
+    assert_eq!(loaded.spawns, level.spawns);
+
+    // unescaped backslash, and version 1 without escapes is gone
+    let path = "version 2\nsize 1 1\nmeta path C:\\levels\ntiles collision c\n..\n";
+    assert!(text::from_text(path).is_err());
+    assert!(text::from_text(&path.replacen("version 2", "version 1", 1)).is_err());
+}
+
+#[test]
+fn text_escapes_names_and_paths() {
+    let path = std::env::temp_dir().join(" level_tests odd\tname= .bmp");
+    write_bmp(&path, 2, 2);
+    let mut level = test_level();
+    let names = ["back ground\nlayer", " collision ", ""];
+    for (layer, name) in level.tile_layers_mut().zip(names.iter()) {
+        layer.name = name.to_string();
+    }
+    level.layers.push(Layer::Image(ImageLayer {
+        name: "\\ sky\r".into(),
+        path: path.to_string_lossy().into_owned(),
+        bmp: Bitmap::load(&path).unwrap(),
+        parallax: V2::new(1.0, 1.0),
+        offset: V2::new(0.0, 0.0),
+        repeat: Repeat::No,
+        in_front: false,
+    }));
+
+    let text = text::to_text(&level);
+    let loaded = text::from_text(&text).unwrap();
+    let layer_names = |level: &Level| level.layers.iter()
+        .map(|layer| match layer {
+            Layer::Tiles(layer) => layer.name.clone(),
+            Layer::Image(layer) => format!("{}|{}", layer.name, layer.path),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(layer_names(&loaded), layer_names(&level));
+    assert_eq!(text::to_text(&loaded), text);
+}
+
+#[test]
+fn text_that_cant_be_read_back_isnt_saved() {
+    let mut no_kind = test_level();
+    no_kind.spawns[0].kind.clear();
+    let mut no_key = test_level();
+    no_key.meta.insert(String::new(), "value".into());
+    let mut no_prop_key = test_level();
+    no_prop_key.spawns[1].props.insert(String::new(), "1".into());
+
+    assert!(text::check(&test_level()).is_ok());
+    let path = std::env::temp_dir().join("level_tests_text_that_cant_be_read_back.txt");
+    for level in &[no_kind, no_key, no_prop_key] {
+        let read_back = text::from_text(&text::to_text(level));
+        assert!(read_back.map_or(true, |loaded| loaded.spawns != level.spawns || loaded.meta != level.meta));
+        assert!(text::check(level).is_err());
+        let _ = std::fs::remove_file(&path);
+        assert!(level.save(&path).is_err());
+        assert!(!path.exists());
+    }
+}
+
+#[test]
+fn text_round_trip() {
+    let level = test_level();
+    let text = text::to_text(&level);
+    let loaded = text::from_text(&text).unwrap();
+
+    assert_eq!(text::to_text(&loaded), text);
+    assert_eq!(loaded.to_bytes(), level.to_bytes());
+    assert_eq!(loaded.spawns, level.spawns);
+    assert_eq!(loaded.meta, level.meta);
+}
+
+#[test]
+fn binary_round_trip() {
+    let level = test_level();
+    let loaded = Level::from_bytes(&level.to_bytes()).unwrap();
+    assert_eq!(text::to_text(&loaded), text::to_text(&level));
+}
+
+#[test]
+fn version_1_is_migrated() {
+    let level = test_level();
+    let mut writer = BinaryWriter::new();
+    writer.write_u32(1);
+    writer.write_u8(0);
+    writer.write_str("collision");
+    writer.write_u8(1);
+    level.collision().write_to(&mut writer);
+    let bytes = crate::file::write_versioned(MAGIC, 1, &writer.into_bytes());
+
+    let loaded = Level::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.collision().to_bytes(), level.collision().to_bytes());
+    assert!(loaded.spawns.is_empty() && loaded.meta.is_empty());
+}
+
+#[test]
+fn damaged_file_is_an_error() {
+    let mut damaged = test_level().to_bytes();
+    let last = damaged.len() - 1;
+    damaged[last] ^= 1;
+    let error = Level::from_bytes(&damaged).err().unwrap();
+    assert!(error.to_string().contains("checksum"), "{}", error);
+}
+
+#[test]
+fn bad_text_is_an_error() {
+    let text = text::to_text(&test_level());
+    let bad = [
+        text.replacen("version 2", "version 3", 1),
+        text.replacen("size 5 3", "size 5 4", 1),
+        text.replacen("size 5 3", "size 0 3", 1),
+        text.replacen("size 5 3", "size 5 16385", 1),
+        text.replacen("size 5 3", "size 65536 65536", 1),
+        text.replacen("tiles collision", "tiles sideways", 1),
+        text.replacen("tiles collision", "tiles background", 1),
+        text.replacen("3c", "3x", 1),
+        text.replacen("3c", "3c 00", 1),
+        text.replacen("hp=5", "hp", 1),
+        text.replacen("spawn player 2.5", "spawn player two", 1),
+        text.lines().skip(1).collect::<Vec<_>>().join("\n"),
+        String::new(),
+    ];
+    for text in &bad {
+        assert!(text::from_text(text).is_err(), "accepted:\n{}", text);
+    }
+}
+
+#[test]
+fn restore_drops_changes() {
+    let mut level = test_level();
+    let snapshot = level.snapshot();
+    level.tile_layer_mut(1).unwrap().tiles.set(0, 0, Tile(9));
+    level.spawns.clear();
+    level.meta.clear();
+
+    level.restore(snapshot);
+    assert_eq!(level.to_bytes(), test_level().to_bytes());
+}
+
+#[test]
+fn layered_round_trip() {
+    let path = std::env::temp_dir().join("level_tests_layered_round_trip.bmp");
+    write_bmp(&path, 4, 2);
+    let image = |name: &str, repeat, in_front| Layer::Image(ImageLayer {
+        name: name.into(),
+        path: path.to_string_lossy().into_owned(),
+        bmp: Bitmap::load(&path).unwrap(),
+        parallax: V2::new(0.5, 0.25),
+        offset: V2::new(-3.5, 2.0),
+        repeat,
+        in_front,
+    });
+    let mut level = test_level();
+    level.layers.insert(0, image("sky", Repeat::Both, false));
+    level.layers.push(image("fog", Repeat::X, true));
+
+    let loaded = Level::from_bytes(&level.to_bytes()).unwrap();
+    assert_eq!(loaded.to_bytes(), level.to_bytes());
+    let text = text::to_text(&level);
+    assert_eq!(text::to_text(&text::from_text(&text).unwrap()), text);
+
+    let names: Vec<(&str, bool)> = loaded.layers.iter()
+        .map(|layer| match layer {
+            Layer::Tiles(layer) => (layer.name.as_str(), false),
+            Layer::Image(layer) => (layer.name.as_str(), layer.bmp.dim() == V2::new(4, 2)),
+        })
+        .collect();
+    assert_eq!(names, vec![
+        ("sky", true), ("background", false), ("collision", false), ("foreground", false), ("fog", true),
+    ]);
+    assert!(loaded.layers[4].is_in_front() && !loaded.layers[0].is_in_front());
+}
+
+#[test]
+fn repeated_copies_cover_the_canvas() {
+    for &size in &[1, 7, 16] {
+        for start in -100..100 {
+            let (first, count) = repeated_copies(start, size, 40);
+            assert!(first < 0 && first >= -size, "start {}, size {}: first {}", start, size, first);
+            assert!(first + count * size >= 40, "start {}, size {}: ends at {}", start, size, first + count * size);
+            assert_eq!((start - first) % size, 0, "start {}, size {}: copies aren't aligned", start, size);
+        }
+    }
+    assert_eq!(repeated_copies(-5, 16, 40), (-5, 3));
+    assert_eq!(repeated_copies(-37, 16, 40), (-5, 3));
+}
+
+#[test]
+fn repeated_image_covers_the_canvas_at_negative_camera() {
+    let canvas = Bitmap::with_dimensions(40, 30).filled(Color::BLACK);
+    let info = CanvasInfo {
+        width: 40,
+        height: 30,
+        scale: 1,
+        game_to_screen_matrix: Mat2::from([[1.0, 0.0], [0.0, -1.0]]),
+        screen_to_game_matrix: Mat2::from([[1.0, 0.0], [0.0, -1.0]]),
+        camera: V2::new(-37.3, -11.8),
+        zoom: 1.0,
+    };
+    let layer = ImageLayer {
+        name: "sky".into(),
+        path: String::new(),
+        bmp: Bitmap::with_dimensions(16, 8).filled(Color::WHITE),
+        parallax: V2::new(0.5, 0.5),
+        offset: V2::new(3.0, -2.0),
+        repeat: Repeat::Both,
+        in_front: false,
+    };
+    layer.draw(&canvas, &info);
+    let black = u32::from(Color::BLACK);
+    assert!((0..30).all(|y| (0..40).all(|x| canvas[(x, y)] != black)));
+
+    // repeated along x only, one band as high as the image
+    let canvas = Bitmap::with_dimensions(40, 30).filled(Color::BLACK);
+    ImageLayer { repeat: Repeat::X, ..layer }.draw(&canvas, &info);
+    let covered_rows: Vec<i32> = (0..30).filter(|&y| canvas[(0, y)] != black).collect();
+    assert_eq!(covered_rows.len(), 8);
+    assert!(covered_rows.iter().all(|&y| (0..40).all(|x| canvas[(x, y)] != black)));
+}
@@ -0,0 +1,406 @@
+/* Text level format, meant to be readable in diffs.
+
+   Lines starting with `#` are comments, blank lines are ignored:
+
+    version 2
+    size 4 3
+    meta name First level
+
+    tiles collision main
+    .. .. .. ..
+    .. .. 01 ..
+    01 01 01 01
+
+    image sky
+        path data/backgrounds/sky.png
+        parallax 0.5 0.25
+        offset 0 0
+        repeat x
+        in_front
+
+    spawn player 2.5 1.5
+    spawn enemy 3.5 1.5 hp=5
+
+   `version` must be the first line, `size` must come before layers.
+   Layers are in the same order as in the file (back to front).
+
+    meta <key> <value...>           value is the rest of the line
+    tiles <role> <name...>          role is background, collision or foreground,
+                                    followed by `height` rows, top row first,
+                                    `..` is an empty tile, otherwise two hex digits of the id
+    image <name...>                 followed by its properties, only `path` is required:
+        path <path>
+        parallax <x> <y>            default 1 1
+        offset <x> <y>              default 0 0
+        repeat <no|x|y|both>        default no
+        in_front
+    spawn <kind> <x> <y> [key=value...]
+
+   Layer names, image paths, meta keys and values, spawn kinds, keys and values are escaped:
+   `\\` for a backslash, `\n`, `\r`, `\t`, `\s` for a space, `\e` for `=` and `\u{hex}`
+   for other whitespace. Names, paths and meta values are the rest of the line, so there
+   only spaces at the ends are escaped and `=` isn't.
+   Meta keys, spawn kinds and keys can't be empty and neither can image paths,
+   levels with them aren't saved, see `check`.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+};
+use super::*;
+use super::super::tilemap::{Tile, check_size};
+
+const VERSION: u32 = 2;
+
+pub fn to_text(level: &Level) -> String {
+    let mut out = String::new();
+    // writing to a String can't fail
+    let _ = write_level(&mut out, level);
+    out
+}
+
+fn write_level(out: &mut String, level: &Level) -> std::fmt::Result {
+    writeln!(out, "version {}", VERSION)?;
+    writeln!(out, "size {} {}", level.width(), level.height())?;
+    for (key, value) in &level.meta {
+        writeln!(out, "meta {} {}", escape(key, true), escape(value, false))?;
+    }
+
+    for layer in &level.layers {
+        writeln!(out)?;
+        match layer {
+            Layer::Tiles(layer) => {
+                let role = match layer.role {
+                    TileLayerRole::Background => "background",
+                    TileLayerRole::Collision => "collision",
+                    TileLayerRole::Foreground => "foreground",
+                };
+                writeln!(out, "tiles {} {}", role, escape(&layer.name, false))?;
+
+                let tiles = &layer.tiles;
+                for y in (0..tiles.height()).rev() {
+                    let row: Vec<String> = (0..tiles.width())
+                        .map(|x| match tiles[(x, y)] {
+                            Tile::EMPTY => "..".into(),
+                            Tile(id) => format!("{:02x}", id),
+                        })
+                        .collect();
+                    writeln!(out, "{}", row.join(" "))?;
+                }
+            },
+            Layer::Image(layer) => {
+                writeln!(out, "image {}", escape(&layer.name, false))?;
+                writeln!(out, "    path {}", escape(&layer.path, false))?;
+                writeln!(out, "    parallax {} {}", layer.parallax.x, layer.parallax.y)?;
+                writeln!(out, "    offset {} {}", layer.offset.x, layer.offset.y)?;
+                let repeat = match layer.repeat {
+                    Repeat::No => "no",
+                    Repeat::X => "x",
+                    Repeat::Y => "y",
+                    Repeat::Both => "both",
+                };
+                writeln!(out, "    repeat {}", repeat)?;
+                if layer.in_front {
+                    writeln!(out, "    in_front")?;
+                }
+            },
+        }
+    }
+
+    if !level.spawns.is_empty() {
+        writeln!(out)?;
+    }
+    for spawn in &level.spawns {
+        write!(out, "spawn {} {} {}", escape(&spawn.kind, true), spawn.pos.x, spawn.pos.y)?;
+        for (key, value) in &spawn.props {
+            write!(out, " {}={}", escape(key, true), escape(value, true))?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Fields that `to_text` would write so that `from_text` can't read them back
+pub fn check(level: &Level) -> io::Result<()> {
+    let empty = |what: &str| Err(invalid_data(format!("{} is empty, the level can't be saved as text", what)));
+    if level.meta.keys().any(String::is_empty) {
+        return empty("a meta key");
+    }
+    for spawn in &level.spawns {
+        if spawn.kind.is_empty() {
+            return empty("a spawn kind");
+        }
+        if spawn.props.keys().any(String::is_empty) {
+            return empty(&format!("a property key of {}", spawn.kind));
+        }
+    }
+    for layer in &level.layers {
+        if let Layer::Image(layer) = layer {
+            if layer.path.is_empty() {
+                return empty(&format!("the path of image layer {}", layer.name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Image layer that is being read, `path` is required and comes at any point of the block
+struct ImageBlock {
+    name: String,
+    path: Option<String>,
+    parallax: V2f,
+    offset: V2f,
+    repeat: Repeat,
+    in_front: bool,
+}
+
+impl ImageBlock {
+    fn finish(self) -> io::Result<Layer> {
+        let path = match self.path {
+            Some(path) => path,
+            None => return Err(invalid_data(format!("image layer {} has no path", self.name))),
+        };
+        let bmp = load_image(&path)?;
+        Ok(Layer::Image(ImageLayer {
+            name: self.name,
+            path,
+            bmp,
+            parallax: self.parallax,
+            offset: self.offset,
+            repeat: self.repeat,
+            in_front: self.in_front,
+        }))
+    }
+}
+
+pub fn from_text(text: &str) -> io::Result<Level> {
+    let mut lines = text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let mut version = None;
+    let mut size: Option<V2i> = None;
+    let mut layers = Vec::new();
+    let mut image: Option<ImageBlock> = None;
+    let mut spawns = Vec::new();
+    let mut meta = BTreeMap::new();
+
+    while let Some((line_number, line)) = lines.next() {
+        let error = |msg: String| invalid_data(format!("level line {}: {}", line_number, msg));
+
+        let (key, rest) = split_word(line);
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let parse_f32 = |s: &str| s.parse::<f32>().map_err(|e| error(format!("{}: {}", s, e)));
+        let parse_v2 = |args: &[&str]| match args {
+            [x, y] => Ok(V2::new(parse_f32(x)?, parse_f32(y)?)),
+            _ => Err(error(format!("{} expects two numbers", key))),
+        };
+
+        if version.is_none() {
+            version = match (key, args.as_slice()) {
+                ("version", [v]) => Some(v.parse::<u32>().map_err(|e| error(format!("version: {}", e)))?),
+                _ => return Err(error("expected `version <number>` as the first line".into())),
+            };
+            if version != Some(VERSION) {
+                return Err(error(format!("unsupported version, expected {}", VERSION)));
+            }
+            continue;
+        }
+        let unescape = |s: &str| unescape(s).ok_or_else(|| error(format!("invalid escape in {}", s)));
+
+        // image layer properties
+        if let Some(block) = image.as_mut() {
+            let handled = match key {
+                "path" => {
+                    if rest.is_empty() {
+                        return Err(error("path is empty".into()));
+                    }
+                    block.path = Some(unescape(rest)?);
+                    true
+                },
+                "parallax" => { block.parallax = parse_v2(&args)?; true },
+                "offset" => { block.offset = parse_v2(&args)?; true },
+                "repeat" => {
+                    block.repeat = match args.as_slice() {
+                        ["no"] => Repeat::No,
+                        ["x"] => Repeat::X,
+                        ["y"] => Repeat::Y,
+                        ["both"] => Repeat::Both,
+                        _ => return Err(error("expected `repeat <no|x|y|both>`".into())),
+                    };
+                    true
+                },
+                "in_front" => { block.in_front = true; true },
+                _ => false,
+            };
+            if handled {
+                continue;
+            }
+            // anything else ends the block
+            layers.push(image.take().unwrap().finish().map_err(|e| error(e.to_string()))?);
+        }
+
+        match key {
+            "size" => {
+                if size.is_some() {
+                    return Err(error("size is given twice".into()));
+                }
+                let parse_side = |s: &str| s.parse::<i64>().map_err(|e| error(format!("invalid size {}: {}", s, e)));
+                size = match args.as_slice() {
+                    [w, h] => {
+                        let (w, h) = check_size(parse_side(w)?, parse_side(h)?).map_err(|e| error(e.to_string()))?;
+                        Some(V2::new(w, h))
+                    },
+                    _ => return Err(error("expected `size <width> <height>`".into())),
+                };
+            },
+            "meta" => {
+                let (key, value) = split_word(rest);
+                if key.is_empty() {
+                    return Err(error("expected `meta <key> <value>`".into()));
+                }
+                meta.insert(unescape(key)?, unescape(value)?);
+            },
+            "tiles" => {
+                let size = size.ok_or_else(|| error("tiles before size".into()))?;
+                let (role, name) = split_word(rest);
+                let role = match role {
+                    "background" => TileLayerRole::Background,
+                    "collision" => TileLayerRole::Collision,
+                    "foreground" => TileLayerRole::Foreground,
+                    _ => return Err(error(format!("invalid tile layer role: {}", role))),
+                };
+
+                let mut tiles = Tilemap::new(size.x, size.y);
+                for y in (0..size.y).rev() {
+                    let (line_number, row) = lines.next()
+                        .ok_or_else(|| error(format!("layer {} has less than {} rows", name, size.y)))?;
+                    let row_error = |msg: String| invalid_data(format!("level line {}: {}", line_number, msg));
+
+                    let cells: Vec<&str> = row.split_whitespace().collect();
+                    if cells.len() != size.x as usize {
+                        return Err(row_error(format!("expected {} tiles, found {}", size.x, cells.len())));
+                    }
+                    for (x, cell) in cells.into_iter().enumerate() {
+                        let tile = match cell {
+                            ".." => Tile::EMPTY,
+                            _ if cell.len() == 2 => u8::from_str_radix(cell, 16)
+                                .map(Tile)
+                                .map_err(|_| row_error(format!("invalid tile: {}", cell)))?,
+                            _ => return Err(row_error(format!("invalid tile: {}", cell))),
+                        };
+                        tiles.set(x as i32, y, tile);
+                    }
+                }
+
+                layers.push(Layer::Tiles(TileLayer { name: unescape(name)?, role, tiles }));
+            },
+            "image" => {
+                image = Some(ImageBlock {
+                    name: unescape(rest)?,
+                    path: None,
+                    parallax: V2::new(1.0, 1.0),
+                    offset: V2::new(0.0, 0.0),
+                    repeat: Repeat::No,
+                    in_front: false,
+                });
+            },
+            "spawn" => {
+                let (kind, x, y, props) = match args.as_slice() {
+                    [kind, x, y, props @ ..] => (kind, x, y, props),
+                    _ => return Err(error("expected `spawn <kind> <x> <y> [key=value...]`".into())),
+                };
+                let props = props.iter()
+                    .map(|prop| {
+                        let mut parts = prop.splitn(2, '=');
+                        match (parts.next(), parts.next()) {
+                            (Some(key), Some(value)) if !key.is_empty() => Ok((unescape(key)?, unescape(value)?)),
+                            _ => Err(error(format!("expected key=value, found {}", prop))),
+                        }
+                    })
+                    .collect::<io::Result<_>>()?;
+                spawns.push(Spawn {
+                    kind: unescape(kind)?,
+                    pos: V2::new(parse_f32(x)?, parse_f32(y)?),
+                    props,
+                });
+            },
+            _ => return Err(error(format!("unknown keyword `{}`", key))),
+        }
+    }
+    if let Some(block) = image.take() {
+        layers.push(block.finish()?);
+    }
+
+    if version.is_none() {
+        return Err(invalid_data("level is empty".into()));
+    }
+    Level { layers, spawns, meta }.validate()
+}
+
+/// First word and the rest of the line without leading whitespace
+fn split_word(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim_start()),
+        None => (line, ""),
+    }
+}
+
+/// Escapes whitespace and backslashes in `s`, see the format description.
+/// A `word` has no unescaped whitespace or `=`, otherwise only the edges are escaped
+fn escape(s: &str, word: bool) -> String {
+    let last = s.chars().count().saturating_sub(1);
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        let edge = i == 0 || i == last;
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ' ' if word || edge => out.push_str("\\s"),
+            '=' if word => out.push_str("\\e"),
+            c if c.is_whitespace() && c != ' ' => {
+                let _ = write!(out, "\\u{{{:x}}}", u32::from(c));
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverses `escape`, `None` for an unknown or unfinished escape
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            's' => ' ',
+            'e' => '=',
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let rest = chars.as_str();
+                let end = rest.find('}')?;
+                let c = std::char::from_u32(u32::from_str_radix(&rest[..end], 16).ok()?)?;
+                chars = rest[end + 1..].chars();
+                c
+            },
+            _ => return None,
+        });
+    }
+    Some(out)
+}
@@ -0,0 +1,186 @@
+/* Index of the levels folder: the order levels are played in and how their edges connect.
+
+    # comment
+    level map_00.txt
+    level cave.txt
+    connect map_00.txt right cave.txt
+
+    level <file>                    file name in the levels folder, in play order
+    connect <file> <side> <file>    leaving the first level through `side` (left, right, up or down)
+                                    enters the second one
+
+   Both levels of a connection must be listed, a side of a level connects to one level at most.
+*/
+
+use std::fmt::Write;
+use crate::file::{prelude::*, invalid_data};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Side { Left, Right, Up, Down }
+
+impl Side {
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Up, Side::Down];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+            Side::Up => "up",
+            Side::Down => "down",
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Up => Side::Down,
+            Side::Down => Side::Up,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Connection {
+    pub from: String,
+    pub side: Side,
+    pub to: String,
+}
+
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct LevelIndex {
+    /// File names in play order
+    pub levels: Vec<String>,
+    pub connections: Vec<Connection>,
+}
+
+impl LevelIndex {
+    /// Adds `file` to the end of the order, if it isn't there yet
+    pub fn add(&mut self, file: &str) {
+        if !self.levels.iter().any(|level| level == file) {
+            self.levels.push(file.into());
+        }
+    }
+
+    /// Moves `file` one place earlier or later in the order
+    pub fn move_level(&mut self, file: &str, later: bool) {
+        if let Some(i) = self.levels.iter().position(|level| level == file) {
+            if later && i + 1 < self.levels.len() {
+                self.levels.swap(i, i + 1);
+            } else if !later && i > 0 {
+                self.levels.swap(i, i - 1);
+            }
+        }
+    }
+
+    /// Level entered by leaving `from` through `side`
+    pub fn neighbour(&self, from: &str, side: Side) -> Option<&str> {
+        self.connections.iter()
+            .find(|connection| connection.from == from && connection.side == side)
+            .map(|connection| connection.to.as_str())
+    }
+
+    /// Connects `side` of `from` to `to` and the opposite side of `to` back to `from`,
+    /// old connections of those sides are dropped. Both levels are added if they aren't listed
+    pub fn connect(&mut self, from: &str, side: Side, to: &str) {
+        self.add(from);
+        self.add(to);
+        self.connections.retain(|connection| {
+            !(connection.from == from && connection.side == side)
+                && !(connection.from == to && connection.side == side.opposite())
+        });
+        self.connections.push(Connection { from: from.into(), side, to: to.into() });
+        self.connections.push(Connection { from: to.into(), side: side.opposite(), to: from.into() });
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for level in &self.levels {
+            // writing to a String can't fail
+            let _ = writeln!(out, "level {}", level);
+        }
+        for connection in &self.connections {
+            let _ = writeln!(out, "connect {} {} {}", connection.from, connection.side.name(), connection.to);
+        }
+        out
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Self> {
+        let mut index = Self::default();
+        let lines = text.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        for (line_number, line) in lines {
+            let error = |msg: String| invalid_data(format!("level index line {}: {}", line_number, msg));
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["level", file] => index.levels.push((*file).into()),
+                ["connect", from, side, to] => {
+                    let side = Side::ALL.iter()
+                        .copied()
+                        .find(|s| s.name() == *side)
+                        .ok_or_else(|| error(format!("invalid side: {}", side)))?;
+                    for file in &[from, to] {
+                        if !index.levels.iter().any(|level| level == **file) {
+                            return Err(error(format!("{} is not a listed level", file)));
+                        }
+                    }
+                    if index.neighbour(from, side).is_some() {
+                        return Err(error(format!("{} side of {} is connected twice", side.name(), from)));
+                    }
+                    index.connections.push(Connection { from: (*from).into(), side, to: (*to).into() });
+                },
+                _ => return Err(error(format!(
+                    "expected `level <file>` or `connect <file> <side> <file>`, found {}", line,
+                ))),
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+impl Load for LevelIndex {
+    fn load(filepath: impl AsRef<Path>) -> io::Result<Self> {
+        let text = String::from_utf8(crate::file::read_all(filepath)?)
+            .map_err(|e| invalid_data(format!("level index is not valid UTF-8: {}", e)))?;
+        Self::from_text(&text)
+    }
+}
+
+impl Save for LevelIndex {
+    fn save(&self, filepath: impl AsRef<Path>) -> io::Result<()> {
+        crate::file::write_all(filepath, self.to_text().as_bytes())
+    }
+}
+
+/// Files of `folder` with `extension`, those in `index` first in its order, then the rest by name.
+/// Files named in `skip` are left out
+pub fn level_files(folder: impl AsRef<Path>, extension: &str, index: &LevelIndex, skip: &[&str]) -> io::Result<Vec<String>> {
+    let folder = folder.as_ref();
+    let mut rest = Vec::new();
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let is_level = path.is_file() && path.extension().map_or(false, |ext| ext == extension);
+        if is_level && !skip.contains(&name.as_str()) && !index.levels.contains(&name) {
+            rest.push(name);
+        }
+    }
+    rest.sort();
+
+    let mut result: Vec<String> = index.levels.iter()
+        .filter(|level| folder.join(level).is_file())
+        .cloned()
+        .collect();
+    result.extend(rest);
+    Ok(result)
+}
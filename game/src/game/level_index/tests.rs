@@ -0,0 +1,35 @@
+use super::*;
+
+#[test]
+fn round_trip_and_connections() {
+    let mut index = LevelIndex::default();
+    index.add("a.txt");
+    index.connect("a.txt", Side::Right, "b.txt");
+    index.connect("c.txt", Side::Left, "b.txt");
+    index.add("a.txt");
+
+    assert_eq!(index.levels, ["a.txt", "b.txt", "c.txt"]);
+    // b.txt's right side was connected to a.txt, now it leads to c.txt
+    assert_eq!(index.neighbour("b.txt", Side::Right), Some("c.txt"));
+    assert_eq!(index.neighbour("a.txt", Side::Right), Some("b.txt"));
+    assert_eq!(index.neighbour("b.txt", Side::Left), Some("a.txt"));
+    assert_eq!(index.neighbour("a.txt", Side::Up), None);
+
+    index.move_level("c.txt", false);
+    index.move_level("a.txt", false);
+    assert_eq!(index.levels, ["a.txt", "c.txt", "b.txt"]);
+
+    assert_eq!(LevelIndex::from_text(&index.to_text()).unwrap(), index);
+}
+
+#[test]
+fn bad_index_is_an_error() {
+    for bad in &[
+        "level a.txt\nconnect a.txt right b.txt",
+        "level a.txt\nlevel b.txt\nconnect a.txt forward b.txt",
+        "level a.txt\nlevel b.txt\nconnect a.txt up b.txt\nconnect a.txt up a.txt",
+        "levels a.txt",
+    ] {
+        assert!(LevelIndex::from_text(bad).is_err(), "accepted {:?}", bad);
+    }
+}
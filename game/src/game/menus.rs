@@ -0,0 +1,199 @@
+/* Menu screens of the state stack, see `states`: the title screen and the overlays over playing.
+   Enter picks the first button of a menu, Escape goes back where there is a way back.
+*/
+
+use platform::input::{Input, KBKey};
+use crate::{
+    render::{self, Bitmap, Color, space::{CanvasPos, ScreenPos}},
+    geom::vector::prelude::*,
+    file::Load,
+    ui::Ui,
+};
+use super::{
+    GameData,
+    GameState,
+    level::Level,
+    in_levels_folder,
+    restart,
+    set_level,
+    back_to_editor,
+};
+
+pub struct Options {
+    /// 4 canvas pixels per window pixel instead of 1, F12 switches it too
+    pub big_pixels: bool,
+    /// Multiplies camera shake, 0 turns it off
+    pub screen_shake: f32,
+    /// Collision boxes of the player and enemies and trigger areas
+    pub show_boxes: bool,
+    /// Camera follows the player on a spring that overshoots a bit, instead of easing in
+    pub springy_camera: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { big_pixels: true, screen_shake: 1.0, show_boxes: true, springy_camera: false }
+    }
+}
+
+/// Window in the middle of the canvas with `lines` of text and `buttons` under them,
+/// returns the clicked button
+fn menu<'b>(
+    canvas: &mut Bitmap,
+    input: &Input,
+    data: &mut GameData,
+    title: &str,
+    lines: &[String],
+    buttons: &[&'b str],
+) -> Option<&'b str> {
+    let CanvasPos(mouse) = data.canvas_info.screen_to_canvas(ScreenPos::from(input.mouse.pos()));
+    let center = canvas.dim() / 2;
+    let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse);
+    ui.begin_window(title, center, V2::new(0.5, 0.5));
+    ui.label(title);
+    for line in lines {
+        ui.label(line);
+    }
+    ui.space(ui.line_height() / 2);
+    let mut clicked = None;
+    for &button in buttons {
+        if ui.button(button) {
+            clicked = Some(button);
+        }
+    }
+    ui.end_window();
+    ui.finish();
+
+    let enter = input.keyboard[KBKey::Enter].pressed() && !input.keyboard[KBKey::Alt].is_down();
+    clicked.or_else(|| buttons.first().copied().filter(|_| enter))
+}
+
+/// Starts playing the loaded level from its player spawn
+fn play(data: &mut GameData) {
+    data.states.push(GameState::Playing);
+    data.play_start = None;
+    data.checkpoint = None;
+    data.editor_snapshot = None;
+    restart(data);
+}
+
+/// Menu of the title screen, only the background while a state over it is drawn
+pub fn title(canvas: &mut Bitmap, input: &Input, data: &mut GameData, top: bool) {
+    render::clear(canvas, Color::BLACK);
+    if !top {
+        return;
+    }
+    let lines = ["Enter to play.".to_string()];
+    match menu(canvas, input, data, "Main menu", &lines, &["Play", "Level editor", "Options", "Quit"]) {
+        Some("Play") => play(data),
+        Some("Level editor") => data.states.reset(GameState::LevelEditor),
+        Some("Options") => data.states.push(GameState::Options),
+        Some("Quit") => data.quit = true,
+        _ => (),
+    }
+}
+
+/// Last button of the overlays over playing: back to where playing was started from
+fn leave_button(data: &GameData) -> &'static str {
+    if data.states.contains(GameState::LevelEditor) { "Back to editor" } else { "Quit to title" }
+}
+
+fn leave(data: &mut GameData) {
+    if data.states.contains(GameState::LevelEditor) {
+        back_to_editor(data);
+    } else {
+        data.states.reset(GameState::Title);
+    }
+}
+
+pub fn pause(canvas: &mut Bitmap, input: &Input, data: &mut GameData) {
+    let lines = ["Escape to resume.".to_string()];
+    let buttons = ["Resume", "Restart", "Options", leave_button(data)];
+    match menu(canvas, input, data, "Paused", &lines, &buttons) {
+        Some("Resume") => data.states.pop(),
+        Some("Restart") => {
+            data.checkpoint = None;
+            restart(data);
+            data.states.pop();
+        },
+        Some("Options") => data.states.push(GameState::Options),
+        Some(_) => leave(data),
+        None if input.keyboard[KBKey::Escape].pressed() => data.states.pop(),
+        None => (),
+    }
+}
+
+pub fn options(canvas: &mut Bitmap, input: &Input, data: &mut GameData) {
+    let CanvasPos(mouse) = data.canvas_info.screen_to_canvas(ScreenPos::from(input.mouse.pos()));
+    let center = canvas.dim() / 2;
+    let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse);
+    ui.begin_window("Options", center, V2::new(0.5, 0.5));
+    ui.label("Options");
+    ui.checkbox("Big pixels", &mut data.options.big_pixels);
+    ui.checkbox("Show collision boxes", &mut data.options.show_boxes);
+    ui.checkbox("Springy camera", &mut data.options.springy_camera);
+    ui.slider("Screen shake", &mut data.options.screen_shake, 0.0, 2.0);
+    ui.space(ui.line_height() / 2);
+    let back = ui.button("Back");
+    ui.end_window();
+    ui.finish();
+
+    if back || input.keyboard[KBKey::Escape].pressed() {
+        data.states.pop();
+    }
+}
+
+pub fn game_over(canvas: &mut Bitmap, input: &Input, data: &mut GameData) {
+    let lines = [format!("Coins: {}", data.coins)];
+    let buttons = ["Retry", leave_button(data)];
+    match menu(canvas, input, data, "Game over", &lines, &buttons) {
+        Some("Retry") => {
+            restart(data);
+            data.states.pop();
+        },
+        Some(_) => leave(data),
+        None => (),
+    }
+}
+
+/// Level after the loaded one in the level index, when playing from the title screen
+fn next_level(data: &GameData) -> Option<String> {
+    if data.states.contains(GameState::LevelEditor) {
+        return None;
+    }
+    let levels = &data.level_index.levels;
+    let i = levels.iter().position(|level| Some(level) == data.level_file.as_ref())?;
+    levels.get(i + 1).cloned()
+}
+
+pub fn level_complete(canvas: &mut Bitmap, input: &Input, data: &mut GameData) {
+    let next = next_level(data);
+    let lines = [format!("Coins: {}", data.coins)];
+    let mut buttons = vec!["Retry", leave_button(data)];
+    if next.is_some() {
+        buttons.insert(0, "Next level");
+    }
+    match menu(canvas, input, data, "Level complete", &lines, &buttons) {
+        Some("Next level") => {
+            let file = next.unwrap_or_default();
+            match Level::load(in_levels_folder(&file)) {
+                Ok(level) => {
+                    set_level(data, level, Some(file));
+                    data.states.pop();
+                },
+                Err(_) => {
+                    data.text_timer = 1.0;
+                    //TODO: error info
+                    data.text = "{red}Error opening level".into();
+                },
+            }
+        },
+        Some("Retry") => {
+            data.checkpoint = None;
+            restart(data);
+            data.states.pop();
+        },
+        Some(_) => leave(data),
+        None => (),
+    }
+}
@@ -0,0 +1,82 @@
+/* Minimap of the level editor: the whole level in the bottom right corner of the canvas,
+   with the visible area outlined. Clicking it moves the camera there.
+*/
+
+use crate::{
+    render::{self, Bitmap, Color},
+    geom::{vector::prelude::*, aabb::AABB},
+};
+use super::{
+    tilemap::Tile,
+    level::{Level, TileLayerRole},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Biggest size of the minimap in canvas pixels
+const MAX_SIZE: V2i = V2 { x: 64, y: 48 };
+/// Most canvas pixels per tile, small levels aren't blown up more than that
+const MAX_SCALE: f32 = 4.0;
+const MARGIN: i32 = 4;
+
+pub struct Minimap {
+    /// Where it is on the canvas
+    pub rect: AABB<i32>,
+    /// Canvas pixels per tile
+    scale: f32,
+    level_height: i32,
+}
+
+impl Minimap {
+    pub fn new(canvas_dim: V2i, level_dim: V2i) -> Self {
+        let scale = (MAX_SIZE.x as f32 / level_dim.x as f32)
+            .min(MAX_SIZE.y as f32 / level_dim.y as f32)
+            .min(MAX_SCALE);
+        let size = V2::new(
+            ((level_dim.x as f32 * scale).round() as i32).max(1),
+            ((level_dim.y as f32 * scale).round() as i32).max(1),
+        );
+        let max = canvas_dim - V2::diag(MARGIN);
+        Self { rect: AABB { min: max - size, max }, scale, level_height: level_dim.y }
+    }
+
+    /// World position of canvas pixel `p`
+    pub fn to_world(&self, p: V2i) -> V2f {
+        let offset = p - self.rect.min;
+        V2::new(offset.x as f32 / self.scale, self.level_height as f32 - offset.y as f32 / self.scale)
+    }
+
+    /// Canvas pixel of world position `p`
+    pub fn to_canvas(&self, p: V2f) -> V2i {
+        let offset = V2::new(p.x * self.scale, (self.level_height as f32 - p.y) * self.scale);
+        self.rect.min + V2::new(offset.x.floor() as i32, offset.y.floor() as i32)
+    }
+
+    /// Draws tiles of `level` as pixels, collision tiles are brighter than the others,
+    /// and `view` as a rectangle
+    pub fn draw(&self, canvas: &mut Bitmap, level: &Level, view: AABB<f32>) {
+        render::fill_rect(canvas, self.rect.min, self.rect.max, Color::BLACK);
+        for y in self.rect.min.y..self.rect.max.y {
+            for x in self.rect.min.x..self.rect.max.x {
+                let p = self.to_world(V2::new(x, y)) + V2::new(0.5, -0.5) / self.scale;
+                let tile = V2i::from(p.floor());
+                let color = level.tile_layers()
+                    .filter(|layer| layer.tiles.get(tile.x, tile.y).map_or(false, |t| t != Tile::EMPTY))
+                    .map(|layer| layer.role == TileLayerRole::Collision)
+                    .max()
+                    .map(|collision| if collision { Color::WHITE } else { Color::GREY });
+                if let Some(color) = color {
+                    render::fill_rect(canvas, V2::new(x, y), V2::new(x + 1, y + 1), color);
+                }
+            }
+        }
+
+        let min = self.to_canvas(V2::new(view.min.x, view.max.y));
+        let max = self.to_canvas(V2::new(view.max.x, view.min.y));
+        canvas.push_clip(self.rect);
+        render::draw_rect(canvas, min, max, Color::YELLOW, 1);
+        canvas.pop_clip();
+        render::draw_rect(canvas, self.rect.min - V2::diag(1), self.rect.max + V2::diag(1), Color::WHITE, 1);
+    }
+}
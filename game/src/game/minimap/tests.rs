@@ -0,0 +1,20 @@
+use super::*;
+
+#[test]
+fn fits_and_maps_both_ways() {
+    let canvas = V2::new(320, 180);
+    for &level in &[V2::new(5, 3), V2::new(100, 20), V2::new(400, 400)] {
+        let minimap = Minimap::new(canvas, level);
+        let size = minimap.rect.max - minimap.rect.min;
+        assert!(size.x <= MAX_SIZE.x && size.y <= MAX_SIZE.y, "{:?} for a level of {:?}", size, level);
+        assert!(minimap.rect.max.x <= canvas.x && minimap.rect.max.y <= canvas.y);
+
+        // top left corner of the minimap is the top left corner of the level
+        assert_eq!(minimap.to_world(minimap.rect.min), V2::new(0.0, level.y as f32));
+        assert_eq!(minimap.to_canvas(V2::new(0.0, level.y as f32)), minimap.rect.min);
+    }
+
+    let minimap = Minimap::new(canvas, V2::new(4, 2));
+    assert_eq!(minimap.to_canvas(V2::new(1.5, 0.5)), minimap.rect.min + V2::new(6, 6));
+    assert_eq!(minimap.to_world(minimap.rect.min + V2::new(6, 6)), V2::new(1.5, 0.5));
+}
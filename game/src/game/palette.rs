@@ -0,0 +1,114 @@
+/* Palette panel of the level editor: every tile of the tile set and every entity kind,
+   as thumbnails in a grid along the right edge of the canvas.
+*/
+
+use crate::{
+    render::{self, Bitmap, Color},
+    geom::{vector::prelude::*, aabb::AABB},
+};
+use super::{
+    tilemap::Tile,
+    tileset::TileSet,
+    spawns,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Size of a palette cell in canvas pixels, with the border
+pub const CELL: i32 = 20;
+/// Size of a thumbnail in canvas pixels
+pub const THUMB: i32 = 16;
+const MARGIN: i32 = 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PaletteItem {
+    Tile(Tile),
+    Entity(&'static str),
+}
+
+/// Tiles of the tile set, then entity kinds
+pub fn items(tileset: &TileSet) -> Vec<PaletteItem> {
+    tileset.tiles().map(PaletteItem::Tile)
+        .chain(spawns::KINDS.iter().map(|kind| PaletteItem::Entity(kind.name)))
+        .collect()
+}
+
+/// Item before or after `current`, wraps around
+pub fn cycle(items: &[PaletteItem], current: PaletteItem, forward: bool) -> Option<PaletteItem> {
+    if items.is_empty() {
+        return None;
+    }
+    let count = items.len();
+    let next = match items.iter().position(|&item| item == current) {
+        Some(i) if forward => (i + 1) % count,
+        Some(i) => (i + count - 1) % count,
+        None => 0,
+    };
+    Some(items[next])
+}
+
+/// Where the cells of `count` items are on a canvas
+pub struct Layout {
+    /// Whole panel
+    pub rect: AABB<i32>,
+    columns: i32,
+    count: usize,
+}
+
+impl Layout {
+    /// Columns are added until all items fit into the canvas height
+    pub fn new(canvas_dim: V2i, count: usize) -> Self {
+        let max_rows = ((canvas_dim.y - MARGIN * 2) / CELL).max(1);
+        let columns = ((count as i32 + max_rows - 1) / max_rows).max(1);
+        let rows = ((count as i32 + columns - 1) / columns).max(1);
+
+        let size = V2::new(columns * CELL, rows * CELL) + V2::diag(MARGIN * 2);
+        let min = V2::new(canvas_dim.x - size.x, 0);
+        Self { rect: AABB { min, max: min + size }, columns, count }
+    }
+
+    pub fn cell(&self, index: usize) -> AABB<i32> {
+        let (column, row) = (index as i32 % self.columns, index as i32 / self.columns);
+        let min = self.rect.min + V2::diag(MARGIN) + V2::new(column * CELL, row * CELL);
+        AABB { min, max: min + V2::diag(CELL) }
+    }
+
+    /// Index of the item under `p`, in canvas pixels
+    pub fn item_at(&self, p: V2i) -> Option<usize> {
+        (0..self.count).find(|&i| self.cell(i).contains(p))
+    }
+}
+
+/// Draws `bmp` scaled to fit into a `THUMB` sized square at `top_left`
+pub fn draw_thumbnail(dst: &Bitmap, bmp: &Bitmap, top_left: V2i) {
+    let biggest = bmp.width().max(bmp.height());
+    let scale = THUMB as f32 / biggest as f32;
+    let size = (V2f::from(bmp.dim()) * scale).round();
+    let centered = top_left + (V2::diag(THUMB) - V2::new(size.x as i32, size.y as i32)) / 2;
+    render::draw_bmp_scaled(dst, bmp, centered, scale);
+}
+
+/// Draws the panel, `thumbnail` gives the bitmap of an item
+pub fn draw<'a>(
+    canvas: &mut Bitmap,
+    layout: &Layout,
+    items: &[PaletteItem],
+    selected: PaletteItem,
+    thumbnail: impl Fn(PaletteItem) -> Option<&'a Bitmap>,
+) {
+    render::fill_rect(canvas, layout.rect.min, layout.rect.max, Color::BLACK);
+    render::draw_rect(canvas, layout.rect.min, layout.rect.max, Color::WHITE, 1);
+
+    for (i, &item) in items.iter().enumerate() {
+        let cell = layout.cell(i);
+        let thumb_pos = cell.min + V2::diag((CELL - THUMB) / 2);
+        match thumbnail(item) {
+            Some(bmp) => draw_thumbnail(canvas, bmp, thumb_pos),
+            None => render::fill_rect(canvas, thumb_pos, thumb_pos + V2::diag(THUMB), Color::GREY),
+        }
+        if item == selected {
+            render::draw_rect(canvas, cell.min, cell.max, Color::YELLOW, 1);
+        }
+    }
+}
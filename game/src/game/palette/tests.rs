@@ -0,0 +1,26 @@
+use super::*;
+
+#[test]
+fn layout_fits_into_canvas() {
+    let canvas = V2::new(320, 100);
+    for &count in &[0, 1, 4, 5, 40] {
+        let layout = Layout::new(canvas, count);
+        assert!(layout.rect.max.x <= canvas.x && layout.rect.min.x >= 0);
+        for i in 0..count {
+            let cell = layout.cell(i);
+            assert!(cell.max.y <= canvas.y, "cell {} of {} is below the canvas", i, count);
+            assert_eq!(layout.item_at(cell.min + V2::diag(CELL / 2)), Some(i));
+        }
+        assert_eq!(layout.item_at(V2::new(0, 0)), None);
+    }
+}
+
+#[test]
+fn cycle_wraps_around() {
+    let items = [PaletteItem::Tile(Tile(1)), PaletteItem::Tile(Tile(3)), PaletteItem::Entity("player")];
+    assert_eq!(cycle(&items, items[2], true), Some(items[0]));
+    assert_eq!(cycle(&items, items[0], false), Some(items[2]));
+    assert_eq!(cycle(&items, items[0], true), Some(items[1]));
+    assert_eq!(cycle(&items, PaletteItem::Tile(Tile(9)), true), Some(items[0]));
+    assert_eq!(cycle(&[], items[0], true), None);
+}
@@ -0,0 +1,124 @@
+/* Kinds of spawns the editor can place and the properties they have.
+
+    player      where the player starts, only one per level
+    enemy       hp
+    pickup      item: coin or heart
+    trigger     width and height in tiles, to the right and up from the spawn tile,
+                action: checkpoint (player restarts there), hazard (player loses a life)
+                or exit (level is complete)
+
+   Property values are strings in `Spawn::props`, missing or invalid ones mean the default.
+*/
+
+use std::collections::BTreeMap;
+use crate::geom::vector::prelude::*;
+use super::level::Spawn;
+
+#[cfg(test)]
+mod tests;
+
+pub enum PropType {
+    Int { min: i32, max: i32 },
+    Choice(&'static [&'static str]),
+}
+
+pub struct PropDef {
+    pub name: &'static str,
+    pub default: &'static str,
+    pub ty: PropType,
+}
+
+impl PropDef {
+    /// Value after or before `value`, numbers stop at the ends, choices wrap around
+    pub fn step(&self, value: &str, forward: bool) -> String {
+        match self.ty {
+            PropType::Int { min, max } => {
+                let value = value.parse().unwrap_or_else(|_| self.default.parse().unwrap_or(min));
+                let value = if forward { value + 1 } else { value - 1 };
+                utils::clamp(value, min, max).to_string()
+            },
+            PropType::Choice(choices) => {
+                let count = choices.len();
+                let next = match choices.iter().position(|&choice| choice == value) {
+                    Some(i) if forward => (i + 1) % count,
+                    Some(i) => (i + count - 1) % count,
+                    None => return self.default.into(),
+                };
+                choices[next].into()
+            },
+        }
+    }
+}
+
+pub struct SpawnKind {
+    pub name: &'static str,
+    /// Placing another one replaces the old one
+    pub unique: bool,
+    pub props: &'static [PropDef],
+}
+
+impl SpawnKind {
+    /// Spawn with default properties
+    pub fn spawn(&self, pos: V2f) -> Spawn {
+        Spawn {
+            kind: self.name.into(),
+            pos,
+            props: self.props.iter()
+                .map(|prop| (prop.name.to_string(), prop.default.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+}
+
+pub const KINDS: [SpawnKind; 4] = [
+    SpawnKind { name: "player", unique: true, props: &[] },
+    SpawnKind {
+        name: "enemy",
+        unique: false,
+        props: &[PropDef { name: "hp", default: "5", ty: PropType::Int { min: 1, max: 99 } }],
+    },
+    SpawnKind {
+        name: "pickup",
+        unique: false,
+        props: &[PropDef { name: "item", default: "coin", ty: PropType::Choice(&["coin", "heart"]) }],
+    },
+    SpawnKind {
+        name: "trigger",
+        unique: false,
+        props: &[
+            PropDef { name: "width", default: "1", ty: PropType::Int { min: 1, max: 64 } },
+            PropDef { name: "height", default: "1", ty: PropType::Int { min: 1, max: 64 } },
+            PropDef { name: "action", default: "checkpoint", ty: PropType::Choice(&["checkpoint", "hazard", "exit"]) },
+        ],
+    },
+];
+
+pub fn kind(name: &str) -> Option<&'static SpawnKind> {
+    KINDS.iter().find(|kind| kind.name == name)
+}
+
+/// Property of a spawn as a number, the default of its kind if it's missing or invalid
+pub fn int_prop(spawn: &Spawn, name: &str) -> i32 {
+    let default = kind(&spawn.kind)
+        .and_then(|kind| kind.props.iter().find(|prop| prop.name == name))
+        .and_then(|prop| prop.default.parse().ok())
+        .unwrap_or(0);
+    spawn.props.get(name).and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+/// Property of a spawn, the default of its kind if it's missing
+pub fn prop<'a>(spawn: &'a Spawn, name: &str) -> &'a str {
+    spawn.props.get(name).map(String::as_str)
+        .or_else(|| kind(&spawn.kind)?.props.iter().find(|prop| prop.name == name).map(|prop| prop.default))
+        .unwrap_or("")
+}
+
+/// Index of the spawn closest to `p`, if it's less than half a tile away
+pub fn find_at(spawns: &[Spawn], p: V2f) -> Option<usize> {
+    spawns.iter()
+        .enumerate()
+        .map(|(i, spawn)| (i, (spawn.pos.x - p.x).abs().max((spawn.pos.y - p.y).abs())))
+        .filter(|&(_, distance)| distance < 0.5)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+}
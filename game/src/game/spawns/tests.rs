@@ -0,0 +1,41 @@
+use super::*;
+
+#[test]
+fn step_clamps_numbers_and_wraps_choices() {
+    let hp = &kind("enemy").unwrap().props[0];
+    assert_eq!(hp.step("5", true), "6");
+    assert_eq!(hp.step("1", false), "1");
+    assert_eq!(hp.step("99", true), "99");
+    assert_eq!(hp.step("lots", true), "6");
+
+    let item = &kind("pickup").unwrap().props[0];
+    assert_eq!(item.step("coin", true), "heart");
+    assert_eq!(item.step("heart", true), "coin");
+    assert_eq!(item.step("coin", false), "heart");
+    assert_eq!(item.step("sword", true), "coin");
+}
+
+#[test]
+fn spawn_has_default_props() {
+    let mut spawn = kind("trigger").unwrap().spawn(V2::new(1.5, 2.5));
+    assert_eq!(spawn.props.len(), 3);
+    assert_eq!(prop(&spawn, "action"), "checkpoint");
+    assert_eq!(int_prop(&spawn, "width"), 1);
+
+    spawn.props.clear();
+    assert_eq!(prop(&spawn, "action"), "checkpoint");
+    spawn.props.insert("height".into(), "3".into());
+    assert_eq!(int_prop(&spawn, "height"), 3);
+    assert_eq!(int_prop(&spawn, "unknown"), 0);
+}
+
+#[test]
+fn find_at_picks_the_closest() {
+    let spawns = [
+        kind("enemy").unwrap().spawn(V2::new(1.5, 1.5)),
+        kind("pickup").unwrap().spawn(V2::new(2.0, 1.5)),
+    ];
+    assert_eq!(find_at(&spawns, V2::new(1.4, 1.6)), Some(0));
+    assert_eq!(find_at(&spawns, V2::new(1.9, 1.2)), Some(1));
+    assert_eq!(find_at(&spawns, V2::new(3.0, 1.5)), None);
+}
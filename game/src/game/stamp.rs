@@ -0,0 +1,206 @@
+/* Stamps: rectangles of tiles copied in the editor, pasted with empty tiles left out.
+
+   Stamp library file, the same tile rows as in the text level format:
+
+    # comment
+    stamp platform 3 2
+    01 01 01
+    .. 02 ..
+
+    stamp <name> <width> <height>   followed by `height` rows, top row first,
+                                    `..` is an empty tile, otherwise two hex digits of the id
+
+   Names are one word, libraries with other names aren't saved.
+*/
+
+use std::fmt::Write;
+use crate::{
+    geom::{vector::prelude::*, aabb::AABB},
+    file::{prelude::*, invalid_data},
+};
+use super::tilemap::{Tilemap, Tile, check_size};
+
+#[cfg(test)]
+mod tests;
+
+/// Rectangle of tiles between two corners, both of them inside
+pub fn selection(a: V2i, b: V2i) -> AABB<i32> {
+    AABB {
+        min: V2::new(a.x.min(b.x), a.y.min(b.y)),
+        max: V2::new(a.x.max(b.x) + 1, a.y.max(b.y) + 1),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Stamp {
+    pub name: String,
+    width: i32,
+    height: i32,
+    /// Rows from the bottom, like in `Tilemap`
+    tiles: Vec<Tile>,
+}
+
+impl Stamp {
+    pub fn new(name: impl Into<String>, width: i32, height: i32) -> Self {
+        assert!(width > 0 && height > 0, "Stamp::new: (width, height): {:?}", (width, height));
+        Self { name: name.into(), width, height, tiles: vec![Tile::EMPTY; (width * height) as usize] }
+    }
+
+    /// Tiles of `tiles` inside of `area`, `None` if `area` is outside of the tilemap
+    pub fn copy(tiles: &Tilemap, area: AABB<i32>) -> Option<Self> {
+        let map_rect = AABB { min: V2::new(0, 0), max: tiles.dim() };
+        let area = area.intersection(map_rect)?;
+        let mut result = Self::new("", area.width(), area.height());
+        for y in 0..result.height {
+            for x in 0..result.width {
+                result.set(x, y, tiles[(area.min.x + x, area.min.y + y)]);
+            }
+        }
+        Some(result)
+    }
+
+    pub fn width(&self) -> i32 { self.width }
+    pub fn height(&self) -> i32 { self.height }
+
+    pub fn get(&self, x: i32, y: i32) -> Tile {
+        assert!((0..self.width).contains(&x) && (0..self.height).contains(&y));
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, tile: Tile) {
+        assert!((0..self.width).contains(&x) && (0..self.height).contains(&y));
+        self.tiles[(y * self.width + x) as usize] = tile;
+    }
+
+    /// Positions relative to the bottom left corner and tiles that aren't empty
+    pub fn cells(&self) -> impl Iterator<Item = (V2i, Tile)> + '_ {
+        (0..self.height)
+            .flat_map(move |y| (0..self.width).map(move |x| V2::new(x, y)))
+            .map(move |p| (p, self.get(p.x, p.y)))
+            .filter(|&(_, tile)| tile != Tile::EMPTY)
+    }
+
+    /// Mirrored left to right
+    pub fn flipped_x(&self) -> Self {
+        self.mapped(self.width, self.height, |x, y| (self.width - 1 - x, y))
+    }
+
+    /// Mirrored top to bottom
+    pub fn flipped_y(&self) -> Self {
+        self.mapped(self.width, self.height, |x, y| (x, self.height - 1 - y))
+    }
+
+    /// Rotated 90 degrees clockwise
+    pub fn rotated(&self) -> Self {
+        // the new top row is the old left column, from the bottom up
+        self.mapped(self.height, self.width, |x, y| (self.width - 1 - y, x))
+    }
+
+    /// Stamp of the given size, `source` gives where every tile comes from
+    fn mapped(&self, width: i32, height: i32, source: impl Fn(i32, i32) -> (i32, i32)) -> Self {
+        let mut result = Self::new(self.name.clone(), width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = source(x, y);
+                result.set(x, y, self.get(src_x, src_y));
+            }
+        }
+        result
+    }
+}
+
+/// Stamps saved for later, in a text file
+#[derive(Default)]
+pub struct StampLibrary {
+    pub stamps: Vec<Stamp>,
+}
+
+impl StampLibrary {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for stamp in &self.stamps {
+            // writing to a String can't fail
+            let _ = writeln!(out, "stamp {} {} {}", stamp.name, stamp.width, stamp.height);
+            for y in (0..stamp.height).rev() {
+                let row: Vec<String> = (0..stamp.width)
+                    .map(|x| match stamp.get(x, y) {
+                        Tile::EMPTY => "..".into(),
+                        Tile(id) => format!("{:02x}", id),
+                    })
+                    .collect();
+                let _ = writeln!(out, "{}", row.join(" "));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn from_text(text: &str) -> io::Result<Self> {
+        let mut stamps = Vec::new();
+        let mut lines = text.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        while let Some((line_number, line)) = lines.next() {
+            let error = |msg: String| invalid_data(format!("stamps line {}: {}", line_number, msg));
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (name, width, height) = match words.as_slice() {
+                ["stamp", name, width, height] => (*name, width.parse::<i64>(), height.parse::<i64>()),
+                _ => return Err(error(format!("expected `stamp <name> <width> <height>`, found {}", line))),
+            };
+            let (width, height) = match (width, height) {
+                (Ok(width), Ok(height)) => check_size(width, height).map_err(|e| error(e.to_string()))?,
+                _ => return Err(error("invalid stamp size".into())),
+            };
+
+            let mut stamp = Stamp::new(name, width, height);
+            for y in (0..height).rev() {
+                let (line_number, row) = lines.next()
+                    .ok_or_else(|| error(format!("stamp {} has less than {} rows", name, height)))?;
+                let row_error = |msg: String| invalid_data(format!("stamps line {}: {}", line_number, msg));
+
+                let cells: Vec<&str> = row.split_whitespace().collect();
+                if cells.len() != width as usize {
+                    return Err(row_error(format!("expected {} tiles, found {}", width, cells.len())));
+                }
+                for (x, cell) in cells.into_iter().enumerate() {
+                    let tile = match cell {
+                        ".." => Tile::EMPTY,
+                        _ if cell.len() == 2 => u8::from_str_radix(cell, 16)
+                            .map(Tile)
+                            .map_err(|_| row_error(format!("invalid tile: {}", cell)))?,
+                        _ => return Err(row_error(format!("invalid tile: {}", cell))),
+                    };
+                    stamp.set(x as i32, y, tile);
+                }
+            }
+            stamps.push(stamp);
+        }
+
+        Ok(Self { stamps })
+    }
+
+    /// Names that `from_text` can't read back
+    fn check_names(&self) -> io::Result<()> {
+        match self.stamps.iter().find(|stamp| stamp.name.is_empty() || stamp.name.contains(char::is_whitespace)) {
+            Some(stamp) => Err(invalid_data(format!("stamp name is not one word: {:?}", stamp.name))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Load for StampLibrary {
+    fn load(filepath: impl AsRef<Path>) -> io::Result<Self> {
+        let text = String::from_utf8(crate::file::read_all(filepath)?)
+            .map_err(|e| invalid_data(format!("stamps are not valid UTF-8: {}", e)))?;
+        Self::from_text(&text)
+    }
+}
+
+impl Save for StampLibrary {
+    fn save(&self, filepath: impl AsRef<Path>) -> io::Result<()> {
+        self.check_names()?;
+        crate::file::write_all(filepath, self.to_text().as_bytes())
+    }
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+/// 3x2 stamp, rows from the top:
+/// 1 2 3
+/// 4 . 6
+fn sample() -> Stamp {
+    let mut stamp = Stamp::new("sample", 3, 2);
+    for (i, &id) in [4, 0, 6, 1, 2, 3].iter().enumerate() {
+        stamp.set(i as i32 % 3, i as i32 / 3, Tile(id));
+    }
+    stamp
+}
+
+/// Rows from the top, like the stamp is drawn
+fn rows(stamp: &Stamp) -> Vec<Vec<u8>> {
+    (0..stamp.height()).rev()
+        .map(|y| (0..stamp.width()).map(|x| stamp.get(x, y).0).collect())
+        .collect()
+}
+
+#[test]
+fn flip_and_rotate() {
+    let stamp = sample();
+    assert_eq!(rows(&stamp.flipped_x()), [[3, 2, 1], [6, 0, 4]]);
+    assert_eq!(rows(&stamp.flipped_y()), [[4, 0, 6], [1, 2, 3]]);
+    assert_eq!(rows(&stamp.rotated()), [[4, 1], [0, 2], [6, 3]]);
+
+    let turned = stamp.rotated().rotated().rotated().rotated();
+    assert_eq!(turned, stamp);
+    assert_eq!(stamp.rotated().rotated(), stamp.flipped_x().flipped_y());
+}
+
+#[test]
+fn copy_is_clipped_to_the_map() {
+    let mut tiles = Tilemap::new(4, 4);
+    tiles.set(3, 3, Tile(7));
+    tiles.set(2, 3, Tile(8));
+
+    let stamp = Stamp::copy(&tiles, selection(V2::new(5, 5), V2::new(2, 3))).unwrap();
+    assert_eq!((stamp.width(), stamp.height()), (2, 1));
+    assert_eq!(rows(&stamp), [[8, 7]]);
+    assert_eq!(stamp.cells().count(), 2);
+
+    assert!(Stamp::copy(&tiles, selection(V2::new(4, 0), V2::new(6, 2))).is_none());
+}
+
+#[test]
+fn library_round_trip() {
+    let library = StampLibrary { stamps: vec![sample(), sample().rotated()] };
+    let loaded = StampLibrary::from_text(&library.to_text()).unwrap();
+    assert_eq!(loaded.stamps, library.stamps);
+
+    let bad = [
+        "stamp a 2 1\n01", "stamp a 2 1\n01 02 03", "stamp a 0 1\n", "stamp a 1 1\nzz", "platform 1 1\n01",
+        "stamp a 16385 1\n", "stamp a 65536 65536\n", "stamp a 4294967297 1\n01",
+    ];
+    for bad in &bad {
+        assert!(StampLibrary::from_text(bad).is_err(), "accepted {:?}", bad);
+    }
+}
+
+#[test]
+fn names_are_checked_on_save() {
+    let path = std::env::temp_dir().join("stamp_tests_names_are_checked_on_save.txt");
+    let _ = std::fs::remove_file(&path);
+    for name in &["two words", "", "tab\there"] {
+        let mut stamp = sample();
+        stamp.name = name.to_string();
+        let library = StampLibrary { stamps: vec![sample(), stamp] };
+        assert!(library.save(&path).is_err(), "saved {:?}", name);
+        assert!(!path.exists());
+    }
+}
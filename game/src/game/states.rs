@@ -0,0 +1,78 @@
+/* Stack of game states, the top one gets input and time.
+
+   Entering a state pushes it and exiting pops it, so the state under it continues where it was:
+   playing from the editor is [LevelEditor, Playing], pausing that is [LevelEditor, Playing, Paused].
+   Overlays are menus over the state under them, which is still drawn but frozen.
+*/
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameState {
+    Title,
+    Playing,
+    LevelEditor,
+    Paused,
+    Options,
+    GameOver,
+    LevelComplete,
+}
+
+impl GameState {
+    /// Drawn over the state under it
+    pub fn is_overlay(self) -> bool {
+        match self {
+            GameState::Paused | GameState::Options | GameState::GameOver | GameState::LevelComplete => true,
+            GameState::Title | GameState::Playing | GameState::LevelEditor => false,
+        }
+    }
+}
+
+/// Never empty
+pub struct StateStack {
+    states: Vec<GameState>,
+}
+
+impl StateStack {
+    pub fn new(first: GameState) -> Self {
+        Self { states: vec![first] }
+    }
+
+    pub fn top(&self) -> GameState {
+        self.states[self.states.len() - 1]
+    }
+
+    pub fn contains(&self, state: GameState) -> bool {
+        self.states.contains(&state)
+    }
+
+    /// Enters `state`
+    pub fn push(&mut self, state: GameState) {
+        self.states.push(state);
+    }
+
+    /// Exits the top state, the last one stays
+    pub fn pop(&mut self) {
+        if self.states.len() > 1 {
+            self.states.pop();
+        }
+    }
+
+    /// Exits every state and enters `state`
+    pub fn reset(&mut self, state: GameState) {
+        self.states.clear();
+        self.states.push(state);
+    }
+
+    /// States drawn this frame, bottom to top: the top one and, if it's an overlay,
+    /// the closest state under it that isn't. Overlays under the top one are hidden
+    pub fn drawn(&self) -> Vec<GameState> {
+        let top = self.top();
+        let base = self.states.iter().rev().copied().find(|state| !state.is_overlay());
+        match base {
+            Some(base) if top.is_overlay() => vec![base, top],
+            _ => vec![top],
+        }
+    }
+}
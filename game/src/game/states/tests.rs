@@ -0,0 +1,26 @@
+use super::*;
+
+#[test]
+fn pop_keeps_last_state() {
+    let mut states = StateStack::new(GameState::LevelEditor);
+    states.push(GameState::Playing);
+    states.pop();
+    assert_eq!(states.top(), GameState::LevelEditor);
+    states.pop();
+    assert_eq!(states.top(), GameState::LevelEditor);
+}
+
+#[test]
+fn overlays_are_drawn_over_base() {
+    let mut states = StateStack::new(GameState::Title);
+    states.push(GameState::Playing);
+    assert_eq!(states.drawn(), vec![GameState::Playing]);
+    states.push(GameState::Paused);
+    assert_eq!(states.drawn(), vec![GameState::Playing, GameState::Paused]);
+    states.push(GameState::Options);
+    assert_eq!(states.drawn(), vec![GameState::Playing, GameState::Options]);
+
+    states.reset(GameState::Title);
+    assert!(!states.contains(GameState::Playing));
+    assert_eq!(states.drawn(), vec![GameState::Title]);
+}
@@ -0,0 +1,42 @@
+use super::*;
+
+#[test]
+fn releasing_a_handle_keeps_the_opposite_side() {
+    let mut level = Level::new(4, 3);
+    level.tile_layer_mut(1).unwrap().tiles.set(0, 0, Tile(1));
+    let mut history = History::new(10);
+
+    // left edge dragged two tiles out, the tile moves right
+    let offset = release_handle(&mut history, &mut level, Anchor { x: 0, y: 1 }, V2::new(-2.2, 9.0));
+    assert_eq!(offset, V2::new(2, 0));
+    assert_eq!(level.dim(), V2::new(6, 3));
+    assert_eq!(level.collision()[(2, 0)], Tile(1));
+
+    // top right corner dragged inside, nothing moves
+    let offset = release_handle(&mut history, &mut level, Anchor { x: 2, y: 2 }, V2::new(3.4, 1.6));
+    assert_eq!(offset, V2::new(0, 0));
+    assert_eq!(level.dim(), V2::new(3, 2));
+    assert_eq!(level.collision()[(2, 0)], Tile(1));
+
+    // dragged past the opposite side, one tile is left
+    release_handle(&mut history, &mut level, Anchor { x: 0, y: 0 }, V2::new(10.0, 10.0));
+    assert_eq!(level.dim(), V2::new(1, 1));
+
+    assert!(history.undo(&mut level) && history.undo(&mut level) && history.undo(&mut level));
+    assert_eq!(level.dim(), V2::new(4, 3));
+    assert_eq!(level.collision()[(0, 0)], Tile(1));
+}
+
+#[test]
+fn resized_rect_is_limited() {
+    let dim = V2::new(4, 3);
+    let far = V2::diag(1.0e9);
+    assert_eq!(resized_rect(dim, Anchor { x: 2, y: 2 }, far).max, V2::new(MAX_SIDE, MAX_SIDE));
+    assert_eq!(resized_rect(dim, Anchor::BOTTOM_LEFT, -far).min, V2::new(4 - MAX_SIDE, 3 - MAX_SIDE));
+    assert_eq!(resized_rect(dim, Anchor::BOTTOM_LEFT, far).min, V2::new(3, 2));
+
+    let mut level = Level::new(4, 3);
+    let mut history = History::new(10);
+    release_handle(&mut history, &mut level, Anchor { x: 2, y: 1 }, far);
+    assert_eq!(level.dim(), V2::new(MAX_SIDE, 3));
+}
@@ -0,0 +1,436 @@
+/* Import of maps made in Tiled (TMX maps and TSX tilesets).
+
+   Supported:
+    - orthogonal finite maps
+    - tile layers with XML, CSV, base64 or zlib compressed base64 data
+    - embedded and external tilesets, with one image or an image per tile,
+      animations if all frames have the same duration
+    - object layers, every object becomes a spawn
+    - image layers
+    - custom properties
+   Anything else is an error that says what isn't supported.
+
+   Tiled global tile ids are used as tile ids, so they must fit into 1..=255.
+
+   Properties:
+    tile            solid, one_way, hazard (bool), friction (float)
+    tile layer      role (background, collision or foreground), without it a layer
+                    named "collision" is the collision layer, layers before it are
+                    background and after it foreground
+    object          all of them go into spawn properties
+    map             all of them go into level metadata
+
+   Image layers after the collision layer are drawn in front of entities.
+
+   Spawn kind is the object's class (type in older versions of Tiled), or its name if there
+   is no class. Spawn position is the center of the object in tiles.
+*/
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+};
+use crate::{
+    render::Bitmap,
+    geom::vector::prelude::*,
+    file::{prelude::*, invalid_data, decode_base64, xml::{self, Element}},
+};
+use super::{
+    tilemap::{Tilemap, Tile, check_size},
+    tileset::{TileSet, TileDef, TileProperties, DEFAULT_FRAME_TIME},
+    level::{Level, Layer, TileLayer, TileLayerRole, ImageLayer, Repeat, Spawn},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Gid bits that are flags for flipping and rotation
+const GID_FLAGS: u32 = 0xF000_0000;
+
+pub struct TiledMap {
+    pub level: Level,
+    pub tileset: TileSet,
+}
+
+pub fn import(filepath: impl AsRef<Path>) -> io::Result<TiledMap> {
+    let filepath = filepath.as_ref();
+    let folder = folder_of(filepath);
+    let map = read_xml(filepath)?;
+    import_map(&map, &folder)
+        .map_err(|e| invalid_data(format!("{}: {}", filepath.display(), e)))
+}
+
+fn folder_of(filepath: &Path) -> PathBuf {
+    filepath.parent().map(PathBuf::from).unwrap_or_default()
+}
+
+fn read_xml(filepath: &Path) -> io::Result<Element> {
+    let text = String::from_utf8(crate::file::read_all(filepath)?)
+        .map_err(|e| invalid_data(format!("{}: not valid UTF-8: {}", filepath.display(), e)))?;
+    xml::parse(&text).map_err(|e| invalid_data(format!("{}: {}", filepath.display(), e)))
+}
+
+fn unsupported(what: &str) -> io::Error {
+    invalid_data(format!("{} is not supported", what))
+}
+
+/// Attribute that must be a positive number
+fn positive_attr(element: &Element, name: &str) -> io::Result<i32> {
+    match element.parse_attr::<i32>(name)? {
+        Some(value) if value > 0 => Ok(value),
+        Some(value) => Err(invalid_data(format!("<{}> `{}` must be positive, not {}", element.name, name, value))),
+        None => Err(invalid_data(format!("<{}> has no `{}` attribute", element.name, name))),
+    }
+}
+
+fn properties(element: &Element) -> BTreeMap<String, String> {
+    element.child("properties")
+        .map(|properties| {
+            properties.children_named("property")
+                .filter_map(|property| {
+                    let name = property.attr("name")?;
+                    // multi-line values are in the text
+                    let value = property.attr("value").unwrap_or(&property.text);
+                    Some((name.to_string(), value.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn import_map(map: &Element, folder: &Path) -> io::Result<TiledMap> {
+    if map.name != "map" {
+        return Err(invalid_data(format!("expected <map>, found <{}>", map.name)));
+    }
+    match map.attr("orientation") {
+        Some("orthogonal") | None => (),
+        Some(orientation) => return Err(unsupported(&format!("{} orientation", orientation))),
+    }
+    if map.attr("infinite") == Some("1") {
+        return Err(unsupported("infinite map"));
+    }
+
+    let (width, height) = check_size(positive_attr(map, "width")?.into(), positive_attr(map, "height")?.into())?;
+    let tile_size = V2::new(positive_attr(map, "tilewidth")? as f32, positive_attr(map, "tileheight")? as f32);
+    let map_height_px = height as f32 * tile_size.y;
+
+    let mut tileset = TileSet::new();
+    for element in map.children_named("tileset") {
+        import_tileset(element, folder, &mut tileset)?;
+    }
+
+    let mut layers: Vec<(Option<TileLayerRole>, Layer)> = Vec::new();
+    let mut spawns = Vec::new();
+    for element in &map.children {
+        let name = element.attr("name").unwrap_or("").to_string();
+        match element.name.as_str() {
+            "layer" => {
+                let context = |e: io::Error| invalid_data(format!("layer {}: {}", name, e));
+                let props = properties(element);
+                let role = match props.get("role").map(String::as_str) {
+                    Some("background") => Some(TileLayerRole::Background),
+                    Some("collision") => Some(TileLayerRole::Collision),
+                    Some("foreground") => Some(TileLayerRole::Foreground),
+                    Some(role) => return Err(context(invalid_data(format!("invalid role {}", role)))),
+                    None if name.eq_ignore_ascii_case("collision") => Some(TileLayerRole::Collision),
+                    None => None,
+                };
+                let tiles = import_tile_layer(element, width, height).map_err(context)?;
+                let layer = Layer::Tiles(TileLayer { name, role: TileLayerRole::Background, tiles });
+                layers.push((role, layer));
+            },
+            "objectgroup" => {
+                for object in element.children_named("object") {
+                    let spawn = import_object(object, tile_size, map_height_px)
+                        .map_err(|e| invalid_data(format!("object layer {}: {}", name, e)))?;
+                    spawns.push(spawn);
+                }
+            },
+            "imagelayer" => {
+                let layer = import_image_layer(element, folder, tile_size, map_height_px)
+                    .map_err(|e| invalid_data(format!("image layer {}: {}", name, e)))?;
+                layers.push((None, Layer::Image(layer)));
+            },
+            "group" => return Err(unsupported("layer group")),
+            "tileset" | "properties" | "editorsettings" => (),
+            other => return Err(unsupported(&format!("<{}>", other))),
+        }
+    }
+
+    // layers without a role are background before the collision layer and foreground after it,
+    // image layers are drawn in front of entities if they are after it
+    let collision_index = layers.iter()
+        .position(|(role, _)| *role == Some(TileLayerRole::Collision))
+        .ok_or_else(|| invalid_data(
+            "no collision layer, name a tile layer \"collision\" or give it a `role` property".into()
+        ))?;
+    let layers = layers.into_iter()
+        .enumerate()
+        .map(|(i, (role, mut layer))| {
+            match &mut layer {
+                Layer::Tiles(tile_layer) => tile_layer.role = role.unwrap_or(if i < collision_index {
+                    TileLayerRole::Background
+                } else {
+                    TileLayerRole::Foreground
+                }),
+                Layer::Image(image_layer) => image_layer.in_front = i > collision_index,
+            }
+            layer
+        })
+        .collect();
+
+    let level = Level { layers, spawns, meta: properties(map) }.validate()?;
+    Ok(TiledMap { level, tileset })
+}
+
+fn import_tileset(element: &Element, folder: &Path, tileset: &mut TileSet) -> io::Result<()> {
+    let first_gid = element.parse_attr::<u32>("firstgid")?
+        .ok_or_else(|| invalid_data("<tileset> has no `firstgid` attribute".into()))?;
+
+    // external tileset, paths in it are relative to its own file
+    if let Some(source) = element.attr("source") {
+        let path = folder.join(source);
+        let external = read_xml(&path)?;
+        return import_tileset_contents(&external, &folder_of(&path), first_gid, tileset)
+            .map_err(|e| invalid_data(format!("{}: {}", path.display(), e)));
+    }
+    import_tileset_contents(element, folder, first_gid, tileset)
+}
+
+fn import_tileset_contents(element: &Element, folder: &Path, first_gid: u32, tileset: &mut TileSet) -> io::Result<()> {
+    let name = element.attr("name").unwrap_or("");
+    let context = |e: io::Error| invalid_data(format!("tileset {}: {}", name, e));
+
+    let tile_count = element.parse_attr::<u32>("tilecount").map_err(context)?.unwrap_or(0);
+
+    // tiles cut from a single image
+    let mut sheet_tiles: Vec<Bitmap> = Vec::new();
+    if let Some(image) = element.child("image") {
+        let sheet = load_image(image, folder).map_err(context)?;
+        let tile_w = positive_attr(element, "tilewidth").map_err(context)?;
+        let tile_h = positive_attr(element, "tileheight").map_err(context)?;
+        let spacing = element.parse_attr::<i32>("spacing").map_err(context)?.unwrap_or(0);
+        let margin = element.parse_attr::<i32>("margin").map_err(context)?.unwrap_or(0);
+        // bigger values leave no room for a single tile, which keeps the math below in range
+        let image_side = sheet.width().max(sheet.height());
+        if !(0..=image_side).contains(&spacing) || !(0..=image_side).contains(&margin) {
+            return Err(context(invalid_data(format!("invalid spacing {} or margin {}", spacing, margin))));
+        }
+        if tile_w > sheet.width() || tile_h > sheet.height() {
+            return Err(context(invalid_data("tiles are bigger than the image".into())));
+        }
+        let columns = match element.parse_attr::<i32>("columns").map_err(context)? {
+            Some(columns) if columns > 0 => columns,
+            _ => (sheet.width() - 2 * margin + spacing) / (tile_w + spacing),
+        };
+        if columns <= 0 {
+            return Err(context(invalid_data("no tile fits into the image".into())));
+        }
+
+        for i in 0..tile_count as i32 {
+            let top_left = V2::new(
+                margin + i % columns * (tile_w + spacing),
+                margin + i / columns * (tile_h + spacing),
+            );
+            if top_left.x < 0 || top_left.y < 0
+                || top_left.x + tile_w > sheet.width() || top_left.y + tile_h > sheet.height()
+            {
+                return Err(context(invalid_data(format!("tile {} is outside of the image", i))));
+            }
+            sheet_tiles.push(sheet.sub_bitmap(top_left, V2::new(tile_w, tile_h)));
+        }
+    }
+
+    let tile_for = |id: u32| -> io::Result<Tile> {
+        match first_gid.checked_add(id) {
+            Some(gid) if gid != 0 && gid <= u32::from(u8::max_value()) => Ok(Tile(gid as u8)),
+            _ => Err(invalid_data(format!("tile id {} + {} doesn't fit into 1..=255", first_gid, id))),
+        }
+    };
+    let simple_def = |id: u32, bmp: Option<Bitmap>| TileDef {
+        name: format!("{}_{}", name, id),
+        frames: bmp.into_iter().collect(),
+        frame_time: DEFAULT_FRAME_TIME,
+        autotile: None,
+        props: TileProperties::default(),
+    };
+
+    // first every tile of the image, then tiles with extra info on top of them
+    let mut defs: BTreeMap<u32, TileDef> = sheet_tiles.iter()
+        .enumerate()
+        .map(|(id, bmp)| (id as u32, simple_def(id as u32, Some(bmp.clone()))))
+        .collect();
+
+    for tile in element.children_named("tile") {
+        let id = tile.parse_attr::<u32>("id").map_err(context)?
+            .ok_or_else(|| context(invalid_data("<tile> has no `id` attribute".into())))?;
+        let tile_context = |e: io::Error| context(invalid_data(format!("tile {}: {}", id, e)));
+
+        let mut def = defs.remove(&id).unwrap_or_else(|| simple_def(id, None));
+        if let Some(image) = tile.child("image") {
+            def.frames = vec![load_image(image, folder).map_err(tile_context)?];
+        }
+
+        if let Some(animation) = tile.child("animation") {
+            let mut frames = Vec::new();
+            let mut duration = None;
+            for frame in animation.children_named("frame") {
+                let frame_id = frame.parse_attr::<u32>("tileid").map_err(tile_context)?
+                    .ok_or_else(|| tile_context(invalid_data("<frame> has no `tileid`".into())))?;
+                let frame_duration = frame.parse_attr::<u32>("duration").map_err(tile_context)?.unwrap_or(100);
+                if *duration.get_or_insert(frame_duration) != frame_duration {
+                    return Err(tile_context(unsupported("animation with different frame durations")));
+                }
+                let bmp = sheet_tiles.get(frame_id as usize)
+                    .ok_or_else(|| tile_context(invalid_data(format!("animation frame {} is not in the image", frame_id))))?;
+                frames.push(bmp.clone());
+            }
+            if let Some(duration) = duration {
+                if duration == 0 {
+                    return Err(tile_context(invalid_data("animation frame duration is 0".into())));
+                }
+                def.frames = frames;
+                def.frame_time = duration as f32 / 1000.0;
+            }
+        }
+
+        let props = properties(tile);
+        let flag = |name: &str| props.get(name).map_or(false, |value| value == "true");
+        def.props = TileProperties {
+            solid: flag("solid"),
+            one_way: flag("one_way"),
+            hazard: flag("hazard"),
+            friction: match props.get("friction") {
+                Some(value) => value.parse()
+                    .map_err(|e| tile_context(invalid_data(format!("friction {}: {}", value, e))))?,
+                None => TileProperties::default().friction,
+            },
+        };
+
+        defs.insert(id, def);
+    }
+
+    for (id, def) in defs {
+        tileset.define(tile_for(id).map_err(context)?, def);
+    }
+    Ok(())
+}
+
+fn load_image(image: &Element, folder: &Path) -> io::Result<Bitmap> {
+    let source = image.required_attr("source")?;
+    let path = folder.join(source);
+    Bitmap::load(&path).map_err(|e| invalid_data(format!("can't load {}: {:?}", path.display(), e)))
+}
+
+fn import_tile_layer(layer: &Element, width: i32, height: i32) -> io::Result<Tilemap> {
+    if positive_attr(layer, "width")? != width || positive_attr(layer, "height")? != height {
+        return Err(unsupported("tile layer with a size different from the map"));
+    }
+    let offset = (layer.parse_attr::<f32>("offsetx")?, layer.parse_attr::<f32>("offsety")?);
+    if offset.0.unwrap_or(0.0) != 0.0 || offset.1.unwrap_or(0.0) != 0.0 {
+        return Err(unsupported("tile layer offset"));
+    }
+
+    let data = layer.child("data").ok_or_else(|| invalid_data("tile layer has no <data>".into()))?;
+    if data.child("chunk").is_some() {
+        return Err(unsupported("chunked tile layer data"));
+    }
+
+    let gids: Vec<u32> = match (data.attr("encoding"), data.attr("compression")) {
+        (None, None) => data.children_named("tile")
+            .map(|tile| tile.parse_attr::<u32>("gid").map(|gid| gid.unwrap_or(0)))
+            .collect::<io::Result<_>>()?,
+        (Some("csv"), None) => data.text.split(',')
+            .map(|gid| gid.trim().parse::<u32>().map_err(|e| invalid_data(format!("tile {:?}: {}", gid.trim(), e))))
+            .collect::<io::Result<_>>()?,
+        (Some("base64"), compression) => {
+            let bytes = decode_base64(&data.text)?;
+            let bytes = match compression {
+                None => bytes,
+                Some("zlib") => crate::file::zlib::decompress(&bytes, (width * height * 4) as usize)?,
+                Some(other) => return Err(unsupported(&format!("{} compression", other))),
+            };
+            if bytes.len() % 4 != 0 {
+                return Err(invalid_data("tile data length is not a multiple of 4".into()));
+            }
+            bytes.chunks(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect()
+        },
+        (encoding, compression) => return Err(unsupported(&format!(
+            "tile data encoding {} with compression {}",
+            encoding.unwrap_or("xml"), compression.unwrap_or("none"),
+        ))),
+    };
+
+    if gids.len() != (width * height) as usize {
+        return Err(invalid_data(format!("expected {} tiles, found {}", width * height, gids.len())));
+    }
+
+    let mut tiles = Tilemap::new(width, height);
+    for (i, &gid) in gids.iter().enumerate() {
+        if gid & GID_FLAGS != 0 {
+            return Err(unsupported("flipped or rotated tile"));
+        }
+        if gid > u32::from(u8::max_value()) {
+            return Err(invalid_data(format!("tile id {} doesn't fit into 1..=255", gid)));
+        }
+        // tiled rows go from the top
+        let (x, row) = (i as i32 % width, i as i32 / width);
+        tiles.set(x, height - 1 - row, Tile(gid as u8));
+    }
+    Ok(tiles)
+}
+
+fn import_object(object: &Element, tile_size: V2f, map_height_px: f32) -> io::Result<Spawn> {
+    let id = object.attr("id").unwrap_or("?");
+    let kind = object.attr("class")
+        .or_else(|| object.attr("type"))
+        .or_else(|| object.attr("name"))
+        .filter(|kind| !kind.is_empty())
+        .ok_or_else(|| invalid_data(format!("object {} has no class or name", id)))?;
+    if kind.contains(char::is_whitespace) {
+        return Err(invalid_data(format!("object {}: kind {:?} contains whitespace", id, kind)));
+    }
+
+    let attr = |name: &str| object.parse_attr::<f32>(name).map(|value| value.unwrap_or(0.0));
+    let (x, y) = (attr("x")?, attr("y")?);
+    let (w, h) = (attr("width")?, attr("height")?);
+    // tile objects are positioned by their bottom left corner, everything else by top left
+    let center_y = if object.attr("gid").is_some() { y - h * 0.5 } else { y + h * 0.5 };
+
+    Ok(Spawn {
+        kind: kind.into(),
+        pos: V2::new((x + w * 0.5) / tile_size.x, (map_height_px - center_y) / tile_size.y),
+        props: properties(object),
+    })
+}
+
+fn import_image_layer(layer: &Element, folder: &Path, tile_size: V2f, map_height_px: f32) -> io::Result<ImageLayer> {
+    let image = layer.child("image").ok_or_else(|| invalid_data("image layer has no <image>".into()))?;
+    let path = folder.join(image.required_attr("source")?);
+    let bmp = Bitmap::load(&path)
+        .map_err(|e| invalid_data(format!("can't load {}: {:?}", path.display(), e)))?;
+
+    let attr = |name: &str, default: f32| layer.parse_attr::<f32>(name).map(|value| value.unwrap_or(default));
+    let flag = |name: &str| layer.attr(name) == Some("1");
+    let (offset_x, offset_y) = (attr("offsetx", 0.0)?, attr("offsety", 0.0)?);
+
+    Ok(ImageLayer {
+        name: layer.attr("name").unwrap_or("").into(),
+        path: path.to_string_lossy().into_owned(),
+        parallax: V2::new(attr("parallaxx", 1.0)?, attr("parallaxy", 1.0)?),
+        offset: V2::new(
+            offset_x / tile_size.x,
+            (map_height_px - offset_y - bmp.height() as f32) / tile_size.y,
+        ),
+        repeat: match (flag("repeatx"), flag("repeaty")) {
+            (false, false) => Repeat::No,
+            (true, false) => Repeat::X,
+            (false, true) => Repeat::Y,
+            (true, true) => Repeat::Both,
+        },
+        in_front: false,
+        bmp,
+    })
+}
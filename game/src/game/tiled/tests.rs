@@ -0,0 +1,138 @@
+use super::*;
+use crate::test_utils::write_bmp;
+
+const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="name" value="Imported"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="16" tileheight="16" tilecount="2" columns="0">
+  <tile id="0">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+    <property name="friction" type="float" value="3.5"/>
+   </properties>
+  </tile>
+  <tile id="1">
+   <properties>
+    <property name="hazard" type="bool" value="true"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="back" width="3" height="2">
+  <data encoding="csv">
+0,0,0,
+1,1,1
+</data>
+ </layer>
+ <layer id="2" name="Collision" width="3" height="2">
+  <data encoding="base64" compression="zlib">eJxjZEAAJiQMAABgAAg=</data>
+ </layer>
+ <layer id="3" name="front" width="3" height="2">
+  <data encoding="base64">AQAAAAAAAAAAAAAAAgAAAAIAAAACAAAA</data>
+ </layer>
+ <objectgroup id="4" name="entities">
+  <object id="1" name="start" type="player" x="8" y="8"/>
+  <object id="2" class="enemy" x="16" y="0" width="16" height="32">
+   <properties>
+    <property name="hp" type="int" value="3"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+fn import_str(text: &str) -> io::Result<TiledMap> {
+    import_map(&xml::parse(text)?, Path::new(""))
+}
+
+#[test]
+fn import_layers_tiles_and_objects() {
+    let map = import_str(MAP).unwrap();
+    let level = &map.level;
+
+    assert_eq!(level.dim(), V2::new(3, 2));
+    assert_eq!(level.meta.get("name").map(String::as_str), Some("Imported"));
+
+    let roles: Vec<_> = level.tile_layers().map(|layer| layer.role).collect();
+    assert_eq!(roles, [TileLayerRole::Background, TileLayerRole::Collision, TileLayerRole::Foreground]);
+
+    // tiled rows go from the top, ours from the bottom
+    let layers: Vec<_> = level.tile_layers().collect();
+    assert_eq!(layers[0].tiles[(0, 0)], Tile(1));
+    assert_eq!(layers[0].tiles[(0, 1)], Tile::EMPTY);
+    assert_eq!(layers[1].tiles[(0, 1)], Tile(1));
+    assert_eq!(layers[1].tiles[(1, 1)], Tile::EMPTY);
+    assert_eq!(layers[1].tiles[(2, 0)], Tile(2));
+    for y in 0..2 {
+        for x in 0..3 {
+            assert_eq!(layers[1].tiles[(x, y)], layers[2].tiles[(x, y)]);
+        }
+    }
+
+    let ground = map.tileset.props(Tile(1));
+    assert!(ground.solid && !ground.hazard && ground.friction == 3.5);
+    assert!(map.tileset.props(Tile(2)).hazard);
+
+    assert_eq!(level.spawns.len(), 2);
+    assert_eq!(level.spawns[0].kind, "player");
+    assert_eq!(level.spawns[0].pos, V2::new(0.5, 1.5));
+    assert_eq!(level.spawns[1].kind, "enemy");
+    assert_eq!(level.spawns[1].pos, V2::new(1.5, 1.0));
+    assert_eq!(level.spawns[1].props.get("hp").map(String::as_str), Some("3"));
+}
+
+#[test]
+fn unsupported_features_are_errors() {
+    let broken = [
+        MAP.replacen(r#"orientation="orthogonal""#, r#"orientation="isometric""#, 1),
+        MAP.replacen(r#"infinite="0""#, r#"infinite="1""#, 1),
+        MAP.replacen(r#"compression="zlib""#, r#"compression="zstd""#, 1),
+        MAP.replacen("1,1,1", "1,1,2147483649", 1),
+        MAP.replacen("1,1,1", "1,1,300", 1),
+        MAP.replacen("1,1,1", "1,1", 1),
+        MAP.replacen(r#"name="Collision""#, r#"name="middle""#, 1),
+        MAP.replacen(r#"<layer id="1" name="back" width="3""#, r#"<layer id="1" name="back" width="4""#, 1),
+        MAP.replacen(r#"<objectgroup id="4""#, r#"<group id="5"/><objectgroup id="4""#, 1),
+        MAP.replacen(r#"class="enemy""#, "", 1),
+        MAP.replacen("</map>", "", 1),
+        MAP.replace(r#"width="3" height="2""#, r#"width="65536" height="65536""#),
+        MAP.replace(r#"width="3" height="2""#, r#"width="3" height="16385""#),
+    ];
+    for text in &broken {
+        assert!(import_str(text).is_err(), "accepted:\n{}", text);
+    }
+}
+
+#[test]
+fn tiles_cut_from_an_image() {
+    // 2x1 tiles of 16x16
+    let path = std::env::temp_dir().join("tiled_tests_tiles_cut_from_an_image.bmp");
+    write_bmp(&path, 32, 16);
+    let tileset = r#"tilecount="2" columns="0">"#;
+    let map = MAP.replacen(tileset, &format!(r#"{}<image source="{}"/>"#, tileset, path.display()), 1);
+
+    let imported = import_str(&map).unwrap();
+    let ground = imported.tileset.get(Tile(1)).unwrap();
+    assert_eq!(ground.frames[0].dim(), V2::new(16, 16));
+    assert!(ground.props.solid);
+    assert_eq!(imported.tileset.get(Tile(2)).unwrap().frames.len(), 1);
+
+    let broken = [
+        (r#"columns="0""#, r#"columns="0" spacing="-16""#),
+        (r#"columns="0""#, r#"columns="0" spacing="-1""#),
+        (r#"columns="0""#, r#"columns="0" margin="-8""#),
+        (r#"columns="0""#, r#"columns="0" margin="9""#),
+        (r#"columns="0""#, r#"columns="0" margin="2147483647""#),
+        (r#"columns="0""#, r#"columns="0" spacing="2147483647""#),
+        (r#"tilewidth="16" tileheight="16" tilecount"#, r#"tilewidth="2147483647" tileheight="16" tilecount"#),
+        (r#"tilecount="2""#, r#"tilecount="3""#),
+        (r#"firstgid="1""#, r#"firstgid="4294967295""#),
+        (r#"firstgid="1""#, r#"firstgid="255""#),
+    ];
+    for (from, to) in broken.iter() {
+        let text = map.replacen(from, to, 1);
+        assert_ne!(text, map, "{:?} is not in the map", from);
+        assert!(import_str(&text).is_err(), "{:?} is not an error", to);
+    }
+}
@@ -1,62 +1,75 @@
-use std::ops::{Index, IndexMut};
+use std::ops::Index;
 use crate::{
     render::{self, Bitmap, canvas_info::CanvasInfo},
     geom::{
         vector::prelude::*,
         aabb::AABB,
     },
-    file::prelude::*,
+    file::{prelude::*, BinaryReader, BinaryWriter, invalid_data},
 };
+use super::{
+    tileset::TileSet,
+    autotile,
+};
+
+#[cfg(test)]
+mod tests;
 
-// TileInfo
+// Tile
 
-pub struct TileInfo {
-    pub size: i32,
-    pub screen_width: f32,
-    pub screen_height: f32,
-    pub bmps: [Bitmap; 1],
+/// Id of a tile in the `TileSet`
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Tile(pub u8);
+
+impl Tile {
+    pub const EMPTY: Self = Tile(0);
 }
 
-impl TileInfo {
-    pub fn get_bmp(&self, tile: Tile) -> &Bitmap {
-        use Tile::*;
-        assert!(if let Ground = tile { true } else { false });
-        &self.bmps[0]
+/// Longest side of a tilemap read from a file, anything bigger is a broken file, not a level
+pub const MAX_SIDE: i32 = 1 << 14;
+
+/// `width` and `height` read from a file, if they are from 1 to `MAX_SIDE`
+pub fn check_size(width: i64, height: i64) -> io::Result<(i32, i32)> {
+    let valid = |side: i64| (1..=i64::from(MAX_SIDE)).contains(&side);
+    if !valid(width) || !valid(height) {
+        return Err(invalid_data(format!("invalid tilemap size: {}x{}", width, height)));
     }
+    Ok((width as i32, height as i32))
 }
 
-// Tile
+// Anchor
 
-#[repr(u8)]
-#[derive(Copy, Clone, Debug)]
-pub enum Tile {
-    Empty = 0,
-    Ground = 1,
+/// Point of a tilemap that stays in place when it's resized.
+///
+/// Each coordinate is 0 for left or bottom, 1 for center, 2 for right or top
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Anchor {
+    pub x: i32,
+    pub y: i32,
 }
 
-impl Default for Tile {
-    fn default() -> Self {
-        Tile::Empty
-    }
-}
+impl Anchor {
+    pub const BOTTOM_LEFT: Self = Anchor { x: 0, y: 0 };
+    pub const CENTER: Self = Anchor { x: 1, y: 1 };
 
-impl Tile {
-    pub fn is_visible(self) -> bool {
-        use self::Tile::*;
-        match self {
-            Ground => true,
-            Empty => false,
-        }
+    /// Where the bottom left tile of a map of size `old` goes in a map of size `new`
+    pub fn offset(self, old: V2i, new: V2i) -> V2i {
+        let grow = new - old;
+        V2::new(grow.x * self.x / 2, grow.y * self.y / 2)
     }
 }
 
 // Tilemap
 
+/// Tiles are changed through `set`, which keeps autotile masks up to date
 #[derive(Clone)]
 pub struct Tilemap {
     width: i32,
     height: i32,
     map: Vec<Tile>,
+    /// Autotile neighbor mask of every tile, see `autotile`
+    masks: Vec<u8>,
 }
 
 impl Index<(i32, i32)> for Tilemap {
@@ -66,20 +79,17 @@ impl Index<(i32, i32)> for Tilemap {
     }
 }
 
-impl IndexMut<(i32, i32)> for Tilemap {
-    fn index_mut(&mut self, (x, y): (i32, i32)) -> &mut Tile {
-        unsafe { &mut *self.mut_ptr_at(x, y) }
-    }
-}
-
 impl Tilemap {
     pub fn new(width: i32, height: i32) -> Self {
-        assert!(width > 0, height > 0);
-        Self {
-            width,
-            height,
-            map: vec![Tile::Empty; (width * height) as usize],
-        }
+        assert!(width > 0 && height > 0, "Tilemap::new: (width, height): {:?}", (width, height));
+        Self::from_tiles(width, height, vec![Tile::EMPTY; (width * height) as usize])
+    }
+
+    fn from_tiles(width: i32, height: i32, map: Vec<Tile>) -> Self {
+        debug_assert_eq!(map.len(), (width * height) as usize);
+        let mut result = Self { width, height, map, masks: Vec::new() };
+        result.update_all_masks();
+        result
     }
 
     pub fn width(&self) -> i32 { self.width }
@@ -94,11 +104,6 @@ impl Tilemap {
         );
     }
 
-    unsafe fn mut_ptr_at(&mut self, x: i32, y: i32) -> *mut Tile {
-        self.check(x, y);
-        self.map.as_mut_ptr().add((y * self.width + x) as usize)
-    }
-
     unsafe fn ptr_at(&self, x: i32, y: i32) -> *const Tile {
         self.check(x, y);
         self.map.as_ptr().add((y * self.width + x) as usize)
@@ -112,194 +117,206 @@ impl Tilemap {
         }
     }
 
-    pub fn resize(&mut self, new_width: i32, new_height: i32) {
+    /// Changes one tile and masks of its neighbors
+    pub fn set(&mut self, x: i32, y: i32, tile: Tile) {
+        self.check(x, y);
+        let index = (y * self.width + x) as usize;
+        if self.map[index] == tile {
+            return;
+        }
+        self.map[index] = tile;
+
+        self.update_mask(x, y);
+        for &(dx, dy, _) in &autotile::NEIGHBORS {
+            if self.get(x + dx, y + dy).is_some() {
+                self.update_mask(x + dx, y + dy);
+            }
+        }
+    }
+
+    /// Autotile neighbor mask of a tile
+    pub fn mask(&self, x: i32, y: i32) -> u8 {
+        self.check(x, y);
+        self.masks[(y * self.width + x) as usize]
+    }
+
+    /// Neighbors outside of the map count as the same tile,
+    /// so ground continues past the edges
+    fn compute_mask(&self, x: i32, y: i32) -> u8 {
+        let tile = self[(x, y)];
+        autotile::NEIGHBORS.iter()
+            .filter(|&&(dx, dy, _)| self.get(x + dx, y + dy).map_or(true, |other| other == tile))
+            .fold(0, |mask, &(_, _, bit)| mask | bit)
+    }
+
+    fn update_mask(&mut self, x: i32, y: i32) {
+        self.masks[(y * self.width + x) as usize] = self.compute_mask(x, y);
+    }
+
+    fn update_all_masks(&mut self) {
+        self.masks.resize(self.map.len(), 0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.update_mask(x, y);
+            }
+        }
+    }
+
+    /// Tile at `(x, y)` moves to `(x, y) + offset` in a map of the new size,
+    /// tiles that end up outside are dropped
+    pub fn resize_with_offset(&mut self, new_width: i32, new_height: i32, offset: V2i) {
         assert!(
             new_width > 0 && new_height > 0,
             "Tilemap::resize: (new_width, new_height): {:?}",
             (new_width, new_height),
         );
 
-        let old_width = self.width as usize;
-        self.width = new_width;
-        let new_width = new_width as usize;
-        if new_width > old_width {
-            let dwidth = new_width - old_width;
-            self.map.resize(new_width * self.height as usize, Tile::default());
-
-            let mut cursor = old_width;
-            while cursor < self.map.len() {
-                self.map[cursor..].rotate_right(dwidth);
-                cursor += new_width;
-            }
-        } else if new_width < old_width {
-            let dwidth = old_width - new_width;
-
-            let mut cursor = new_width;
-            while cursor < self.map.len() {
-                self.map[cursor..].rotate_left(dwidth);
-                cursor += new_width;
+        let mut map = vec![Tile::EMPTY; (new_width * new_height) as usize];
+        for y in 0..new_height {
+            for x in 0..new_width {
+                if let Some(tile) = self.get(x - offset.x, y - offset.y) {
+                    map[(y * new_width + x) as usize] = tile;
+                }
             }
-            self.map.resize(new_width * self.height as usize, Tile::default());
-        }
-
-        if new_height != self.height {
-            self.map.resize((self.width * new_height) as usize, Tile::default());
-            self.height = new_height;
         }
+        *self = Self::from_tiles(new_width, new_height, map);
     }
 
-    pub fn draw(&self, canvas: &Bitmap, canvas_info: &CanvasInfo, tile_info: &TileInfo) {
-        use std::cmp::{min, max};
-
-        let camera_i: V2i = canvas_info.camera.floor().into();
-        let v_draw_tiles = tile_info.screen_height.ceil() as i32;
-        let h_draw_tiles = tile_info.screen_width.ceil() as i32;
+    /// Range of tiles on one axis that overlaps `min..max` in world units
+    fn visible_range(min: f32, max: f32, len: i32) -> std::ops::Range<i32> {
+        let first = utils::clamp(min.floor() as i32, 0, len);
+        let last = utils::clamp(max.ceil() as i32, 0, len);
+        first..last
+    }
 
-        let lower_bound = max(camera_i.y, 0);
-        let upper_bound = min(camera_i.y + v_draw_tiles, self.height - 1);
+    pub fn draw(&self, canvas: &Bitmap, canvas_info: &CanvasInfo, tileset: &TileSet) {
+        let visible = canvas_info.visible_rect();
 
-        let left_bound = max(camera_i.x, 0);
-        let right_bound = min(camera_i.x + h_draw_tiles, self.width - 1);
+        for tile_y in Self::visible_range(visible.bottom(), visible.top(), self.height) {
+            for tile_x in Self::visible_range(visible.left(), visible.right(), self.width) {
+                let bmp = match tileset.frame(self[(tile_x, tile_y)], self.mask(tile_x, tile_y)) {
+                    Some(bmp) => bmp,
+                    None => continue,
+                };
 
-        for tile_y in lower_bound..=upper_bound {
-            for tile_x in left_bound..=right_bound {
-                let tile = self[(tile_x, tile_y)];
-                if !tile.is_visible() {
-                    continue
-                }
-
-                let V2 { x, y } = render::v2_to_screen((tile_x as f32, tile_y as f32).into(), canvas_info);
-                render::draw_bmp(canvas, tile_info.get_bmp(tile), (x, y - tile_info.size).into());
+                let top_left = (tile_x as f32, (tile_y + 1) as f32);
+                render::world::draw_bmp(canvas, canvas_info, bmp, top_left);
             }
         }
     }
 
-    pub fn draw_outline(&self, canvas: &mut Bitmap, canvas_info: &CanvasInfo) {
-        let AABB { min, max } = render::aabb_to_screen(
-            AABB { min: (0.0, 0.0).into(), max: (self.width as f32, self.height as f32).into() },
-            canvas_info,
-        );
-        render::draw_rect(canvas, min, max, render::Color::YELLOW, 1);
+    fn world_rect(&self) -> AABB<f32> {
+        AABB { min: (0.0, 0.0).into(), max: (self.width as f32, self.height as f32).into() }
     }
 
-    pub fn draw_grid(&self, canvas: &mut Bitmap, canvas_info: &CanvasInfo, tile_info: &TileInfo) {
-        use std::cmp::{min, max};
-        use utils::clamp;
+    pub fn draw_outline(&self, canvas: &mut Bitmap, canvas_info: &CanvasInfo) {
+        render::world::draw_rect(canvas, canvas_info, self.world_rect(), render::Color::YELLOW, 1);
+    }
 
+    pub fn draw_grid(&self, canvas: &mut Bitmap, canvas_info: &CanvasInfo) {
         let color = render::Color::GREY;
         let thickness = 1;
 
-        let camera_i: V2i = canvas_info.camera.floor().into();
-        let v_draw_tiles = tile_info.screen_height.ceil() as i32;
-        let h_draw_tiles = tile_info.screen_width.ceil() as i32;
+        canvas.push_clip(canvas_info.world_to_canvas_rect(self.world_rect()));
 
-        let lower_bound = max(camera_i.y, 1);
-        let upper_bound = min(camera_i.y + v_draw_tiles + 1, self.height);
+        let visible = canvas_info.visible_rect();
+        let (width, height) = (self.width as f32, self.height as f32);
 
-        for tile_y in lower_bound..upper_bound {
-            let mut min = render::v2_to_screen((0., tile_y as f32).into(), canvas_info);
-            if !(0..canvas.height()).contains(&min.y) {
-                continue;
-            }
-            min.x = clamp(min.x, 0, canvas.width());
+        // outer lines are drawn by draw_outline
+        for tile_y in Self::visible_range(visible.bottom(), visible.top(), self.height).filter(|&y| y > 0) {
+            let y = tile_y as f32;
+            render::world::draw_line(canvas, canvas_info, (0.0, y), (width, y), color, thickness);
+        }
 
-            let mut max = render::v2_to_screen((self.width as f32, tile_y as f32).into(), canvas_info);
-            max.x = clamp(max.x, 0, canvas.width());
+        for tile_x in Self::visible_range(visible.left(), visible.right(), self.width).filter(|&x| x > 0) {
+            let x = tile_x as f32;
+            render::world::draw_line(canvas, canvas_info, (x, height), (x, 0.0), color, thickness);
+        }
+
+        canvas.pop_clip();
+    }
+}
 
-            render::draw_line(canvas, min, max, color, thickness);
+impl Tilemap {
+    /// u32 width, u32 height, then one byte per tile, row by row from the bottom
+    pub fn write_to(&self, writer: &mut BinaryWriter) {
+        writer.write_u32(self.width as u32);
+        writer.write_u32(self.height as u32);
+        for &tile in &self.map {
+            writer.write_u8(tile.0);
         }
+    }
 
-        let left_bound = max(camera_i.x, 1);
-        let right_bound = min(camera_i.x + h_draw_tiles + 1, self.width);
+    pub fn read_from(reader: &mut BinaryReader) -> io::Result<Self> {
+        let (width, height) = check_size(reader.read_u32()?.into(), reader.read_u32()?.into())?;
+        let map = reader.read_bytes((width * height) as usize)?
+            .iter()
+            .map(|&id| Tile(id))
+            .collect();
 
-        for tile_x in left_bound..right_bound {
-            let mut min = render::v2_to_screen((tile_x as f32, self.height as f32).into(), canvas_info);
-            if !(0..canvas.width()).contains(&min.x) {
-                continue;
-            }
-            min.y = clamp(min.y, 0, canvas.height());
+        Ok(Self::from_tiles(width, height, map))
+    }
+}
 
-            let mut max = render::v2_to_screen((tile_x as f32, 0.).into(), canvas_info);
-            max.y = clamp(max.y, 0, canvas.height());
+/* File format, see `file::write_versioned` for the header:
+    magic       b"TMAP"
+    version     1
+    payload     see Tilemap::write_to
 
-            render::draw_line(canvas, min, max, color, thickness);
+   Files without the magic are the old format that was a memory dump:
+    width, height   u32 each, in native byte order (always little-endian in practice)
+    tiles           one byte per tile, 0 - empty, 1 - ground
+*/
+const MAGIC: &[u8; 4] = b"TMAP";
+const VERSION: u32 = 1;
+
+impl Tilemap {
+    pub fn from_bytes(file: &[u8]) -> io::Result<Self> {
+        match crate::file::read_versioned(file, MAGIC)? {
+            Some((VERSION, payload)) => {
+                let mut reader = BinaryReader::new(payload);
+                let result = Self::read_from(&mut reader)?;
+                if !reader.is_empty() {
+                    return Err(invalid_data("unexpected data after the tilemap".into()));
+                }
+                Ok(result)
+            },
+            Some((version, _)) => Err(invalid_data(format!(
+                "unsupported tilemap version {}, newest supported is {}", version, VERSION,
+            ))),
+            None => Self::from_old_format(file),
         }
     }
-}
 
-/// For saving/loading to/from file
-#[repr(C)]
-#[derive(Copy, Clone, Default)]
-struct TilemapSize {
-    width: u32,
-    height: u32,
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = BinaryWriter::new();
+        self.write_to(&mut writer);
+        crate::file::write_versioned(MAGIC, VERSION, &writer.into_bytes())
+    }
+
+    fn from_old_format(file: &[u8]) -> io::Result<Self> {
+        // same as the current payload, but only the tiles old Tile enum had are valid
+        let mut reader = BinaryReader::new(file);
+        let result = Self::read_from(&mut reader)
+            .map_err(|e| invalid_data(format!("not a tilemap file: {}", e)))?;
+        if !reader.is_empty() {
+            return Err(invalid_data("not a tilemap file: unexpected data after the tiles".into()));
+        }
+        if let Some(tile) = result.map.iter().find(|tile| tile.0 > 1) {
+            return Err(invalid_data(format!("not a tilemap file: invalid tile {} in old format", tile.0)));
+        }
+        Ok(result)
+    }
 }
 
 impl Load for Tilemap {
     fn load(filepath: impl AsRef<Path>) -> io::Result<Self> {
-        use std::mem::size_of;
-
-        let file = crate::file::read_all(filepath)?;
-
-        let TilemapSize { width, height } = {
-            let mut tilemap_size = TilemapSize::default();
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    file.as_ptr(),
-                    &mut tilemap_size as *mut _ as *mut u8,
-                    size_of::<TilemapSize>(),
-                );
-            }
-            tilemap_size
-        };
-        let tilemap_size = (width * height) as usize;
-
-        let mut map = Vec::<Tile>::with_capacity(tilemap_size);
-        map.resize_with(tilemap_size, Default::default);
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                file.as_ptr().add(size_of::<TilemapSize>()),
-                map.as_mut_ptr() as *mut u8,
-                tilemap_size * size_of::<Tile>(),
-            );
-        }
-
-        Ok(Self {
-            width: width as i32,
-            height: height as i32,
-            map,
-        })
+        Self::from_bytes(&crate::file::read_all(filepath)?)
     }
 }
 
 impl Save for Tilemap {
     fn save(&self, filepath: impl AsRef<Path>) -> io::Result<()> {
-        use std::mem::size_of;
-
-        let to_file = {
-            let tilemap_size = TilemapSize {
-                width: self.width as u32,
-                height: self.height as u32,
-            };
-
-            let filesize = size_of::<TilemapSize>() + self.map.len() / size_of::<Tile>();
-
-            let mut bytes = Vec::<u8>::with_capacity(filesize);
-            bytes.resize_with(filesize, Default::default);
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    &tilemap_size as *const _ as *const u8,
-                    bytes.as_mut_ptr(),
-                    size_of::<TilemapSize>(),
-                );
-                std::ptr::copy_nonoverlapping(
-                    self.map.as_ptr() as *const _ as *const u8,
-                    bytes.as_mut_ptr().add(size_of::<TilemapSize>()),
-                    self.map.len() / size_of::<Tile>(),
-                );
-            }
-            bytes
-        };
-        crate::file::write_all(filepath, to_file.as_slice())
+        crate::file::write_all(filepath, &self.to_bytes())
     }
 }
@@ -0,0 +1,146 @@
+use super::*;
+use crate::{
+    game::autotile::{AutotileMode, BLOB_VARIANTS},
+    test_utils::Rng,
+};
+
+fn masks_from_scratch(tilemap: &Tilemap) -> Vec<u8> {
+    let mut copy = tilemap.clone();
+    copy.update_all_masks();
+    copy.masks
+}
+
+#[test]
+fn incremental_masks_match_full_update() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut tilemap = Tilemap::new(7, 5);
+
+    for step in 0..2000 {
+        if step % 200 == 199 {
+            let offset = V2::new(rng.below(5) - 2, rng.below(5) - 2);
+            tilemap.resize_with_offset(1 + rng.below(10), 1 + rng.below(10), offset);
+        } else {
+            let (x, y) = (rng.below(tilemap.width()), rng.below(tilemap.height()));
+            tilemap.set(x, y, Tile(rng.below(3) as u8));
+        }
+        assert!(tilemap.masks == masks_from_scratch(&tilemap), "masks differ after step {}", step);
+    }
+}
+
+#[test]
+fn resize_keeps_anchor_in_place() {
+    let mut tilemap = Tilemap::new(4, 3);
+    tilemap.set(0, 0, Tile(1));
+    tilemap.set(3, 2, Tile(2));
+
+    let resized = |width, height, anchor: Anchor| {
+        let mut resized = tilemap.clone();
+        resized.resize_with_offset(width, height, anchor.offset(tilemap.dim(), V2::new(width, height)));
+        resized
+    };
+
+    let grown = resized(6, 5, Anchor { x: 2, y: 2 });
+    assert_eq!(grown[(2, 2)], Tile(1));
+    assert_eq!(grown[(5, 4)], Tile(2));
+
+    let shrunk = resized(2, 1, Anchor::CENTER);
+    assert!((0..2).all(|x| shrunk[(x, 0)] == Tile::EMPTY));
+
+    let cut = resized(1, 1, Anchor::BOTTOM_LEFT);
+    assert_eq!(cut[(0, 0)], Tile(1));
+
+    let mut shifted = tilemap.clone();
+    shifted.resize_with_offset(4, 3, V2::new(-1, 1));
+    assert_eq!(shifted[(2, 2)], Tile::EMPTY);
+    assert!((0..4).all(|x| shifted[(x, 0)] == Tile::EMPTY));
+    assert!(shifted.masks == masks_from_scratch(&shifted));
+}
+
+#[test]
+fn mask_edges_count_as_connected() {
+    let mut tilemap = Tilemap::new(3, 3);
+    tilemap.set(1, 1, Tile(1));
+
+    assert_eq!(tilemap.mask(1, 1), 0);
+    // corner tile doesn't see the center diagonally, everything else is empty or outside
+    assert_eq!(tilemap.mask(0, 0), 0xFF & !autotile::NE);
+    assert_eq!(tilemap.mask(1, 0), 0xFF & !autotile::N);
+}
+
+#[test]
+fn every_mask_has_a_variant() {
+    for &mode in &[AutotileMode::Bits4, AutotileMode::Blob] {
+        let mut used = vec![false; mode.variant_count()];
+        for mask in 0..=255 {
+            used[mode.variant(mask)] = true;
+        }
+        assert!(used.iter().all(|&used| used), "{:?} has unused variants", mode);
+    }
+    assert_eq!(AutotileMode::Blob.variant_count(), BLOB_VARIANTS);
+}
+
+fn random_tilemap(rng: &mut Rng) -> Tilemap {
+    let mut tilemap = Tilemap::new(1 + rng.below(20), 1 + rng.below(20));
+    for y in 0..tilemap.height() {
+        for x in 0..tilemap.width() {
+            tilemap.set(x, y, Tile(rng.below(256) as u8));
+        }
+    }
+    tilemap
+}
+
+#[test]
+fn file_round_trip() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..50 {
+        let tilemap = random_tilemap(&mut rng);
+        let loaded = Tilemap::from_bytes(&tilemap.to_bytes()).unwrap();
+        assert_eq!(loaded.dim(), tilemap.dim());
+        assert!(loaded.map == tilemap.map && loaded.masks == tilemap.masks);
+    }
+}
+
+#[test]
+fn damaged_file_is_an_error() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    let bytes = random_tilemap(&mut rng).to_bytes();
+
+    for len in 0..bytes.len() {
+        assert!(Tilemap::from_bytes(&bytes[..len]).is_err(), "truncated to {} bytes", len);
+    }
+    for _ in 0..200 {
+        let mut damaged = bytes.clone();
+        let i = rng.below(damaged.len() as i32) as usize;
+        damaged[i] ^= 1 << rng.below(8);
+        assert!(Tilemap::from_bytes(&damaged).is_err(), "bit flipped in byte {}", i);
+    }
+}
+
+#[test]
+fn unsupported_version_is_an_error() {
+    let mut writer = BinaryWriter::new();
+    Tilemap::new(2, 2).write_to(&mut writer);
+    let bytes = crate::file::write_versioned(MAGIC, VERSION + 1, &writer.into_bytes());
+    assert!(Tilemap::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn old_format_is_migrated() {
+    let mut old = Vec::new();
+    old.extend_from_slice(&3_u32.to_le_bytes());
+    old.extend_from_slice(&2_u32.to_le_bytes());
+    old.extend_from_slice(&[0, 1, 0, 1, 1, 1]);
+
+    let tilemap = Tilemap::from_bytes(&old).unwrap();
+    assert_eq!(tilemap.dim(), V2::new(3, 2));
+    assert_eq!(tilemap[(1, 0)], Tile(1));
+    assert_eq!(tilemap[(0, 0)], Tile::EMPTY);
+
+    // memory dump of an invalid enum value
+    *old.last_mut().unwrap() = 7;
+    assert!(Tilemap::from_bytes(&old).is_err());
+    // truncated
+    assert!(Tilemap::from_bytes(&old[..old.len() - 1]).is_err());
+    // random bytes
+    assert!(Tilemap::from_bytes(b"not a tilemap at all").is_err());
+}
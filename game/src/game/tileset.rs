@@ -0,0 +1,251 @@
+/* Tile set definition file.
+
+   Plain text, one block per tile, `#` starts a comment:
+
+    tile 1 ground
+        sprite ground.png
+        solid
+        friction 12
+
+    tile 2 water
+        sprite water_0.png water_1.png water_2.png
+        frame_time 0.25
+        hazard
+
+    tile 3 grass
+        autotile 8 grass_blob.png
+        solid
+
+   Block starts with `tile <id> <name>`, where id is 1..=255
+   (0 is always empty and can't be defined). Properties:
+    sprite <paths...>   one path per animation frame, relative to the definition file
+    frame_time <f32>    seconds per animation frame
+    autotile <4|8> <path>
+                        sheet with variants picked from neighbors, see `autotile`,
+                        can't be combined with sprite
+    solid               entities can't pass through
+    one_way             entities can only land on it from above
+    hazard              touching it kills the player
+    friction <f32>      how fast entities stop when walking on it
+*/
+
+use std::path::PathBuf;
+use crate::{
+    render::Bitmap,
+    file::{prelude::*, invalid_data},
+};
+use super::{
+    tilemap::Tile,
+    autotile::{Autotile, AutotileMode},
+};
+
+#[cfg(test)]
+mod tests;
+
+pub const DEFAULT_FRICTION: f32 = 12.0;
+pub const DEFAULT_FRAME_TIME: f32 = 0.2;
+
+#[derive(Copy, Clone, Debug)]
+pub struct TileProperties {
+    pub solid: bool,
+    pub one_way: bool,
+    pub hazard: bool,
+    pub friction: f32,
+}
+
+impl Default for TileProperties {
+    fn default() -> Self {
+        Self {
+            solid: false,
+            one_way: false,
+            hazard: false,
+            friction: DEFAULT_FRICTION,
+        }
+    }
+}
+
+pub struct TileDef {
+    pub name: String,
+    pub frames: Vec<Bitmap>,
+    pub frame_time: f32,
+    /// Replaces `frames`
+    pub autotile: Option<Autotile>,
+    pub props: TileProperties,
+}
+
+pub struct TileSet {
+    /// Indexed by tile id
+    tiles: Vec<Option<TileDef>>,
+    /// For animations
+    time: f32,
+}
+
+impl TileSet {
+    pub fn new() -> Self {
+        Self {
+            tiles: (0..=u8::max_value()).map(|_| None).collect(),
+            time: 0.0,
+        }
+    }
+
+    /// Tile set that is used when there is no definition file:
+    /// one solid tile with id 1
+    pub fn with_ground(bmp: Bitmap) -> Self {
+        let mut result = Self::new();
+        result.tiles[1] = Some(TileDef {
+            name: "ground".into(),
+            frames: vec![bmp],
+            frame_time: DEFAULT_FRAME_TIME,
+            autotile: None,
+            props: TileProperties { solid: true, ..TileProperties::default() },
+        });
+        result
+    }
+
+    /// Replaces the previous definition, if there was one
+    pub fn define(&mut self, tile: Tile, def: TileDef) {
+        assert!(tile != Tile::EMPTY, "TileSet::define: empty tile can't be defined");
+        self.tiles[tile.0 as usize] = Some(def);
+    }
+
+    pub fn get(&self, tile: Tile) -> Option<&TileDef> {
+        self.tiles[tile.0 as usize].as_ref()
+    }
+
+    /// Undefined tiles (including empty) have default properties
+    pub fn props(&self, tile: Tile) -> TileProperties {
+        self.get(tile).map(|def| def.props).unwrap_or_default()
+    }
+
+    /// All defined tiles, in order of their ids
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        self.tiles.iter().enumerate()
+            .filter(|(_, def)| def.is_some())
+            .map(|(id, _)| Tile(id as u8))
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// Current animation frame or autotile variant for neighbor `mask`,
+    /// `None` for tiles that aren't drawn
+    pub fn frame(&self, tile: Tile, mask: u8) -> Option<&Bitmap> {
+        let def = self.get(tile)?;
+        if let Some(autotile) = &def.autotile {
+            return Some(autotile.get(mask));
+        }
+        if def.frames.is_empty() {
+            return None;
+        }
+        let frame = (self.time / def.frame_time) as usize % def.frames.len();
+        Some(&def.frames[frame])
+    }
+}
+
+impl Load for TileSet {
+    fn load(filepath: impl AsRef<Path>) -> io::Result<Self> {
+        let text = String::from_utf8(crate::file::read_all(&filepath)?)
+            .map_err(|e| invalid_data(format!("tile set is not valid UTF-8: {}", e)))?;
+        let folder = filepath.as_ref().parent().map(PathBuf::from).unwrap_or_default();
+        Self::from_text(&text, |path| Bitmap::load(folder.join(path)).map_err(|e| format!("{:?}", e)))
+    }
+}
+
+impl TileSet {
+    /// Parses a definition file, `load_image` loads the images it names
+    fn from_text(text: &str, load_image: impl Fn(&str) -> Result<Bitmap, String>) -> io::Result<Self> {
+        let mut result = Self::new();
+        let mut current: Option<(usize, TileDef)> = None;
+
+        for (line_index, line) in text.lines().enumerate() {
+            let error = |msg: String| invalid_data(format!("tile set line {}: {}", line_index + 1, msg));
+
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let key = match words.next() {
+                Some(key) => key,
+                None => continue,
+            };
+            let args: Vec<&str> = words.collect();
+            let parse_f32 = |args: &[&str]| match args {
+                [v] => v.parse::<f32>().map_err(|e| error(format!("{}: {}", key, e))),
+                _ => Err(error(format!("{} expects one number", key))),
+            };
+
+            if key == "tile" {
+                let (id, name) = match args.as_slice() {
+                    [id, name] => (id.parse::<u8>().map_err(|e| error(format!("tile id: {}", e)))?, name),
+                    _ => return Err(error("expected `tile <id> <name>`".into())),
+                };
+                if id == 0 {
+                    return Err(error("tile 0 is always empty".into()));
+                }
+                if result.tiles[id as usize].is_some() || current.as_ref().map_or(false, |(i, _)| *i == id as usize) {
+                    return Err(error(format!("tile {} is defined twice", id)));
+                }
+
+                if let Some((index, def)) = current.take() {
+                    result.tiles[index] = Some(def);
+                }
+                current = Some((id as usize, TileDef {
+                    name: (*name).into(),
+                    frames: Vec::new(),
+                    frame_time: DEFAULT_FRAME_TIME,
+                    autotile: None,
+                    props: TileProperties::default(),
+                }));
+                continue;
+            }
+
+            let def = match current.as_mut() {
+                Some((_, def)) => def,
+                None => return Err(error(format!("`{}` outside of a tile block", key))),
+            };
+            match key {
+                "sprite" => {
+                    if def.autotile.is_some() {
+                        return Err(error("sprite can't be combined with autotile".into()));
+                    }
+                    if args.is_empty() {
+                        return Err(error("sprite expects at least one path".into()));
+                    }
+                    for path in args {
+                        let bmp = load_image(path).map_err(|e| error(format!("can't load {}: {}", path, e)))?;
+                        def.frames.push(bmp);
+                    }
+                },
+                "autotile" => {
+                    if !def.frames.is_empty() {
+                        return Err(error("autotile can't be combined with sprite".into()));
+                    }
+                    let (mode, path) = match args.as_slice() {
+                        ["4", path] => (AutotileMode::Bits4, path),
+                        ["8", path] => (AutotileMode::Blob, path),
+                        _ => return Err(error("expected `autotile <4|8> <path>`".into())),
+                    };
+                    let sheet = load_image(path).map_err(|e| error(format!("can't load {}: {}", path, e)))?;
+                    let autotile = Autotile::from_sheet(mode, &sheet)
+                        .map_err(|e| error(format!("{}: {}", path, e)))?;
+                    def.autotile = Some(autotile);
+                },
+                "frame_time" => {
+                    def.frame_time = parse_f32(&args)?;
+                    if def.frame_time <= 0.0 {
+                        return Err(error("frame_time must be positive".into()));
+                    }
+                },
+                "friction" => def.props.friction = parse_f32(&args)?,
+                "solid" => def.props.solid = true,
+                "one_way" => def.props.one_way = true,
+                "hazard" => def.props.hazard = true,
+                _ => return Err(error(format!("unknown property `{}`", key))),
+            }
+        }
+        if let Some((index, def)) = current.take() {
+            result.tiles[index] = Some(def);
+        }
+
+        Ok(result)
+    }
+}
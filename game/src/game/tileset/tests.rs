@@ -0,0 +1,85 @@
+use super::*;
+
+/// Every image is a blank 32x24 sheet, which both autotile modes can split, except "missing.png"
+fn parse(text: &str) -> io::Result<TileSet> {
+    TileSet::from_text(text, |path| match path {
+        "missing.png" => Err("not found".into()),
+        _ => Ok(Bitmap::with_dimensions(32, 24)),
+    })
+}
+
+fn error(text: &str) -> String {
+    match parse(text) {
+        Ok(_) => panic!("accepted:\n{}", text),
+        Err(e) => e.to_string(),
+    }
+}
+
+const TILES: &str = "\
+# ground and water
+tile 1 ground
+    sprite ground.png
+    solid
+    friction 3.5
+
+tile 7 water    # animated
+    sprite water_0.png water_1.png
+    frame_time 0.5
+    hazard
+    one_way
+
+tile 255 grass
+    autotile 8 grass.png
+";
+
+#[test]
+fn valid_file() {
+    let tileset = parse(TILES).unwrap();
+    assert_eq!(tileset.tiles().collect::<Vec<_>>(), vec![Tile(1), Tile(7), Tile(255)]);
+
+    let ground = tileset.get(Tile(1)).unwrap();
+    assert_eq!((ground.name.as_str(), ground.frames.len()), ("ground", 1));
+    assert!(ground.props.solid && !ground.props.hazard);
+    assert_eq!(ground.props.friction, 3.5);
+
+    let water = tileset.get(Tile(7)).unwrap();
+    assert_eq!((water.frames.len(), water.frame_time), (2, 0.5));
+    assert!(water.props.hazard && water.props.one_way && !water.props.solid);
+    assert_eq!(water.props.friction, DEFAULT_FRICTION);
+
+    let grass = tileset.get(Tile(255)).unwrap();
+    assert!(grass.autotile.is_some() && grass.frames.is_empty());
+    assert!(tileset.get(Tile(2)).is_none());
+}
+
+#[test]
+fn bad_tile_ids() {
+    assert_eq!(error("tile 1 a\ntile 1 b"), "tile set line 2: tile 1 is defined twice");
+    assert_eq!(error("tile 1 a\ntile 2 b\ntile 1 c"), "tile set line 3: tile 1 is defined twice");
+    assert_eq!(error("\ntile 0 empty"), "tile set line 2: tile 0 is always empty");
+    assert!(error("tile 256 big").starts_with("tile set line 1: tile id: "));
+    assert!(error("tile -1 negative").starts_with("tile set line 1: tile id: "));
+    assert_eq!(error("tile 1"), "tile set line 1: expected `tile <id> <name>`");
+}
+
+#[test]
+fn sprite_and_autotile_are_exclusive() {
+    assert_eq!(
+        error("tile 1 a\n    sprite a.png\n    autotile 4 a.png"),
+        "tile set line 3: autotile can't be combined with sprite",
+    );
+    assert_eq!(
+        error("tile 1 a\n    autotile 4 a.png\n    sprite a.png"),
+        "tile set line 3: sprite can't be combined with autotile",
+    );
+}
+
+#[test]
+fn bad_properties() {
+    assert_eq!(error("tile 1 a\n  shiny"), "tile set line 2: unknown property `shiny`");
+    assert_eq!(error("solid\ntile 1 a"), "tile set line 1: `solid` outside of a tile block");
+    assert_eq!(error("tile 1 a\n\n  frame_time 0"), "tile set line 3: frame_time must be positive");
+    assert_eq!(error("tile 1 a\n  friction 1 2"), "tile set line 2: friction expects one number");
+    assert_eq!(error("tile 1 a\n  autotile 6 a.png"), "tile set line 2: expected `autotile <4|8> <path>`");
+    assert_eq!(error("tile 1 a\n  sprite missing.png"), "tile set line 2: can't load missing.png: not found");
+}
@@ -0,0 +1,205 @@
+/* Checks that a level is playable.
+
+   Errors:      no player spawn, spawns inside of solid tiles
+   Warnings:    open areas that can't be reached from the player spawn,
+                gaps in the border on sides that don't lead to another level,
+                levels bigger than `MAX_SIZE` tiles on a side
+
+   F5 in the editor shows issues of the open level, the `validate_levels` binary checks
+   every level in a folder.
+*/
+
+use std::{collections::VecDeque, fmt};
+use crate::{
+    geom::{vector::prelude::*, aabb::AABB},
+    file::prelude::*,
+};
+use super::{
+    tilemap::{Tilemap, Tile},
+    tileset::TileSet,
+    level::{Level, TEXT_EXTENSION},
+    level_index::{self, LevelIndex, Side},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// Bigger levels are slow to draw and hard to find your way around in
+pub const MAX_SIZE: i32 = 256;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+    /// Tiles the issue is about
+    pub area: Option<AABB<i32>>,
+}
+
+impl Issue {
+    fn new(severity: Severity, message: String, area: Option<AABB<i32>>) -> Self {
+        Self { severity, message, area }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        if let Some(area) = self.area {
+            write!(f, " at {}, {}", area.min.x, area.min.y)?;
+        }
+        Ok(())
+    }
+}
+
+fn tile_rect(min: V2i, max: V2i) -> AABB<i32> {
+    AABB { min, max: max + V2::diag(1) }
+}
+
+/// Issues of `level`, errors first. `open_sides` lead to other levels, gaps there are fine
+pub fn validate(level: &Level, is_solid: impl Fn(Tile) -> bool, open_sides: &[Side]) -> Vec<Issue> {
+    let tiles = level.collision();
+    // outside of the level is solid, like for the player
+    let solid = |p: V2i| tiles.get(p.x, p.y).map_or(true, |tile| is_solid(tile));
+    let mut issues = Vec::new();
+
+    let player = level.spawn("player");
+    if player.is_none() {
+        issues.push(Issue::new(Severity::Error, "no player spawn".into(), None));
+    }
+    for spawn in level.spawns.iter().filter(|spawn| spawn.kind != "trigger") {
+        let p = V2i::from(spawn.pos.floor());
+        if solid(p) {
+            let message = format!("{} spawn is inside of a solid tile", spawn.kind);
+            issues.push(Issue::new(Severity::Error, message, Some(tile_rect(p, p))));
+        }
+    }
+
+    if let Some(player) = player {
+        let start = V2i::from(player.pos.floor());
+        if !solid(start) {
+            let mut reached = open_area(tiles, &solid, start);
+            for y in 0..tiles.height() {
+                for x in 0..tiles.width() {
+                    if solid(V2::new(x, y)) || reached[(y * tiles.width() + x) as usize] {
+                        continue;
+                    }
+                    let area = open_area(tiles, &solid, V2::new(x, y));
+                    let (count, bounds) = area_bounds(tiles, &area);
+                    for (done, new) in reached.iter_mut().zip(area) {
+                        *done |= new;
+                    }
+                    let message = format!("{} open tiles can't be reached from the player spawn", count);
+                    issues.push(Issue::new(Severity::Warning, message, Some(bounds)));
+                }
+            }
+        }
+    }
+
+    let (w, h) = (tiles.width(), tiles.height());
+    for &side in Side::ALL.iter().filter(|side| !open_sides.contains(side)) {
+        let edge: Vec<V2i> = match side {
+            Side::Left => (0..h).map(|y| V2::new(0, y)).collect(),
+            Side::Right => (0..h).map(|y| V2::new(w - 1, y)).collect(),
+            Side::Down => (0..w).map(|x| V2::new(x, 0)).collect(),
+            Side::Up => (0..w).map(|x| V2::new(x, h - 1)).collect(),
+        };
+        // every run of open tiles is one gap
+        let mut gap: Option<(V2i, V2i)> = None;
+        for p in edge.into_iter().map(Some).chain(Some(None)) {
+            match (p, gap) {
+                (Some(p), Some((start, _))) if !solid(p) => gap = Some((start, p)),
+                (Some(p), None) if !solid(p) => gap = Some((p, p)),
+                (_, Some((start, end))) => {
+                    let count = (end - start).x + (end - start).y + 1;
+                    let message = format!("gap of {} tiles in the {} border", count, side.name());
+                    issues.push(Issue::new(Severity::Warning, message, Some(tile_rect(start, end))));
+                    gap = None;
+                },
+                (_, None) => (),
+            }
+        }
+    }
+
+    if w > MAX_SIZE || h > MAX_SIZE {
+        let message = format!("level is {}x{}, more than {} tiles on a side", w, h, MAX_SIZE);
+        issues.push(Issue::new(Severity::Warning, message, None));
+    }
+
+    issues.sort_by(|a, b| b.severity.cmp(&a.severity));
+    issues
+}
+
+/// Open tiles 4-connected to `start`, indexed by `y * width + x`
+fn open_area(tiles: &Tilemap, solid: impl Fn(V2i) -> bool, start: V2i) -> Vec<bool> {
+    let mut result = vec![false; (tiles.width() * tiles.height()) as usize];
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(p) = queue.pop_front() {
+        if solid(p) {
+            continue;
+        }
+        let i = (p.y * tiles.width() + p.x) as usize;
+        if result[i] {
+            continue;
+        }
+        result[i] = true;
+        for &offset in &[V2::new(1, 0), V2::new(-1, 0), V2::new(0, 1), V2::new(0, -1)] {
+            queue.push_back(p + offset);
+        }
+    }
+    result
+}
+
+/// Number of tiles in `area` and the rectangle around them
+fn area_bounds(tiles: &Tilemap, area: &[bool]) -> (usize, AABB<i32>) {
+    let mut count = 0;
+    let mut bounds: Option<AABB<i32>> = None;
+    for (i, _) in area.iter().enumerate().filter(|(_, &inside)| inside) {
+        let p = V2::new(i as i32 % tiles.width(), i as i32 / tiles.width());
+        count += 1;
+        bounds = Some(match bounds {
+            Some(b) => AABB {
+                min: V2::new(b.min.x.min(p.x), b.min.y.min(p.y)),
+                max: V2::new(b.max.x.max(p.x + 1), b.max.y.max(p.y + 1)),
+            },
+            None => tile_rect(p, p),
+        });
+    }
+    (count, bounds.unwrap_or_else(|| tile_rect(V2::diag(0), V2::diag(0))))
+}
+
+/// Issues of every level in `folder`, see `level_index::level_files`.
+/// Levels that can't be loaded have the error instead
+pub fn validate_folder(folder: &str) -> io::Result<Vec<(String, io::Result<Vec<Issue>>)>> {
+    let tileset = TileSet::load(super::TILESET_PATH).ok();
+    let is_solid = |tile: Tile| match &tileset {
+        Some(tileset) => tileset.props(tile).solid,
+        // like the default tile set, any tile is ground
+        None => tile != Tile::EMPTY,
+    };
+    let index = LevelIndex::load(format!("{}/{}", folder, super::LEVEL_INDEX_FILE)).unwrap_or_default();
+    let skip = [super::LEVEL_INDEX_FILE, super::STAMPS_FILE];
+
+    let files = level_index::level_files(folder, TEXT_EXTENSION, &index, &skip)?;
+    Ok(files.into_iter()
+        .map(|file| {
+            let open_sides: Vec<Side> = Side::ALL.iter()
+                .copied()
+                .filter(|&side| index.neighbour(&file, side).is_some())
+                .collect();
+            let issues = Level::load(format!("{}/{}", folder, file))
+                .map(|level| validate(&level, &is_solid, &open_sides));
+            (file, issues)
+        })
+        .collect())
+}
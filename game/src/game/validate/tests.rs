@@ -0,0 +1,59 @@
+use super::*;
+use super::super::level::Spawn;
+
+/// Level with a solid border, `rows` are from the top, `#` is solid
+fn level(rows: &[&str]) -> Level {
+    let (w, h) = (rows[0].len() as i32, rows.len() as i32);
+    let mut level = Level::new(w, h);
+    let tiles = level.tile_layer_mut(level.collision_layer_index()).unwrap();
+    for (y, row) in rows.iter().rev().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == '#' {
+                tiles.tiles.set(x as i32, y as i32, Tile(1));
+            }
+        }
+    }
+    level
+}
+
+fn spawn(kind: &str, x: f32, y: f32) -> Spawn {
+    Spawn { kind: kind.into(), pos: V2::new(x, y), props: Default::default() }
+}
+
+fn messages(level: &Level, open_sides: &[Side]) -> Vec<String> {
+    validate(level, |tile| tile != Tile::EMPTY, open_sides).iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn playable_level_has_no_issues() {
+    let mut level = level(&[
+        "#####",
+        "#...#",
+        "#####",
+    ]);
+    level.spawns.push(spawn("player", 1.5, 1.5));
+    level.spawns.push(spawn("enemy", 3.5, 1.5));
+    assert!(messages(&level, &[]).is_empty());
+}
+
+#[test]
+fn finds_every_kind_of_issue() {
+    let mut level = level(&[
+        "##..#",
+        "#.#.#",
+        "#####",
+    ]);
+    assert_eq!(messages(&level, &[Side::Up]), ["error: no player spawn"]);
+
+    level.spawns.push(spawn("player", 1.5, 1.5));
+    level.spawns.push(spawn("enemy", 2.5, 1.5));
+    assert_eq!(messages(&level, &[]), [
+        "error: enemy spawn is inside of a solid tile at 2, 1",
+        "warning: 3 open tiles can't be reached from the player spawn at 2, 1",
+        "warning: gap of 2 tiles in the up border at 2, 2",
+    ]);
+    assert_eq!(messages(&level, &[Side::Up]).len(), 2);
+
+    let level = self::level(&[&"#".repeat(MAX_SIZE as usize + 1)]);
+    assert!(messages(&level, &[]).iter().any(|message| message.contains("more than")));
+}
@@ -22,10 +22,35 @@ impl<T: Num32> AABB<T> {
     pub fn bottom_left(self)  -> V2<T> { self.min }
     pub fn bottom_right(self) -> V2<T> { (self.max.x, self.min.y).into() }
 
+    pub fn width(self)  -> T { self.max.x - self.min.x }
+    pub fn height(self) -> T { self.max.y - self.min.y }
+
     pub fn translate(self, by: V2<T>) -> Self {
         Self {
             min: self.min + by,
             max: self.max + by,
         }
     }
+
+    /// `min` is inclusive, `max` is exclusive
+    pub fn contains(self, p: V2<T>) -> bool {
+        self.min.x <= p.x && p.x < self.max.x
+            && self.min.y <= p.y && p.y < self.max.y
+    }
+
+    /// Returns `None` if rectangles don't overlap
+    pub fn intersection(self, other: Self) -> Option<Self> {
+        fn max<T: PartialOrd>(a: T, b: T) -> T { if a > b { a } else { b } }
+        fn min<T: PartialOrd>(a: T, b: T) -> T { if a < b { a } else { b } }
+
+        let result = Self {
+            min: (max(self.min.x, other.min.x), max(self.min.y, other.min.y)).into(),
+            max: (min(self.max.x, other.max.x), min(self.max.y, other.max.y)).into(),
+        };
+        if result.min.x < result.max.x && result.min.y < result.max.y {
+            Some(result)
+        } else {
+            None
+        }
+    }
 }
\ No newline at end of file
@@ -11,8 +11,13 @@ mod game;
 mod render;
 mod file;
 mod geom;
+mod camera;
+mod ui;
+#[cfg(test)]
+mod test_utils;
 
 pub use game::{
     startup,
     update_and_render,
+    validate::{validate_folder, Issue, Severity},
 };
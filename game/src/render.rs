@@ -2,13 +2,17 @@ pub mod text;
 pub mod color;
 pub mod bitmap;
 pub mod canvas_info;
+pub mod space;
+pub mod world;
+
+#[cfg(test)]
+mod tests;
 
 use std::mem::swap;
 use crate::geom::{
     vector::prelude::*,
     aabb::AABB,
 };
-use canvas_info::CanvasInfo;
 
 pub use color::Color;
 pub use bitmap::Bitmap;
@@ -47,13 +51,9 @@ pub fn fill_rect(dst_bmp: &Bitmap, mut min: V2i, mut max: V2i, color: Color) {
     }
 }
 
-/// FIXME:
-///
-/// if left or top edge is in bounds,
-///
-/// but same edge + thickness is out of bounds,
+/// Draws rectangle outline inside of `min`..`max`.
 ///
-/// function panics
+/// Rectangle can be partially or fully out of clip rectangle
 pub fn draw_rect(
     dst: &mut Bitmap,
     mut min: V2i,
@@ -61,178 +61,198 @@ pub fn draw_rect(
     color: Color,
     thickness: i32,
 ) {
-    if min.x >= dst.width() || min.y >= dst.height() || max.x < 1 || max.y < 1 {
+    if min.x > max.x {
+        swap(&mut min.x, &mut max.x)
+    }
+    if min.y > max.y {
+        swap(&mut min.y, &mut max.y)
+    }
+    if thickness <= 0 {
         return
     }
 
-    let draw_left = min.x >= 0;
-    let draw_top = min.y >= 0;
-    let draw_right = max.x <= dst.width();
-    let draw_bottom = max.y <= dst.height();
+    let width = max.x.saturating_sub(min.x);
+    let height = max.y.saturating_sub(min.y);
+    if thickness.saturating_mul(2) >= width || thickness.saturating_mul(2) >= height {
+        fill_rect(dst, min, max, color);
+        return
+    }
 
-    if draw_left && draw_top && draw_right && draw_bottom {
-        for t in 0..thickness {
-            for x in min.x..max.x {
-                dst[(x, min.y + t)] = color.into();
-                dst[(x, max.y - t - 1)] = color.into();
-            }
-            for y in (min.y + 1)..max.y {
-                dst[(min.x + t    , y)] = color.into();
-                dst[(max.x - t - 1, y)] = color.into();
-            }
-        }
-    } else {
-        if !draw_left {
-            min.x = 0;
-        }
-        if !draw_top {
-            min.y = 0;
-        }
-        if !draw_right {
-            max.x = dst.width();
-        }
-        if !draw_bottom {
-            max.y = dst.height();
-        }
+    let inner_min = V2::new(min.x + thickness, min.y + thickness);
+    let inner_max = V2::new(max.x - thickness, max.y - thickness);
 
-        if draw_left {
-            for t in 0..thickness {
-                for y in (min.y + 1)..max.y {
-                    dst[(min.x + t, y)] = color.into();
-                }
-            }
-        }
-        if draw_top {
-            for t in 0..thickness {
-                for x in min.x..max.x {
-                    dst[(x, min.y + t)] = color.into();
-                }
-            }
-        }
-        if draw_right {
-            for t in 0..thickness {
-                for y in (min.y + 1)..max.y {
-                    dst[(max.x - t - 1, y)] = color.into();
-                }
-            }
-        }
-        if draw_bottom {
-            for t in 0..thickness {
-                for x in min.x..max.x {
-                    dst[(x, max.y - t - 1)] = color.into();
-                }
-            }
-        }
-    }
+    // top, bottom, left, right
+    fill_rect(dst, min, V2::new(max.x, inner_min.y), color);
+    fill_rect(dst, V2::new(min.x, inner_max.y), max, color);
+    fill_rect(dst, V2::new(min.x, inner_min.y), V2::new(inner_min.x, inner_max.y), color);
+    fill_rect(dst, V2::new(inner_max.x, inner_min.y), V2::new(max.x, inner_max.y), color);
 }
 
-// (y - y0) / (y1 - y0) = (x - x0) / (x1 - x0)
-// y = (y1 - y0) / (x1 - x0) * (x - x0) + y0
+/// Draws line from `start` to `end` (not including `end`).
+///
+/// Only part of the line that is inside of clip rectangle is drawn,
+/// so cost doesn't depend on how far away from it the endpoints are
 pub fn draw_line(
     dst: &mut Bitmap,
-    mut min: V2i,
-    mut max: V2i,
+    start: V2i,
+    end: V2i,
     color: Color,
     _thickness: i32,
 ) {
-    //TODO:
-    // line clipping
-    // horizontal and vertical special cases
-    // thickness?
+    //TODO: thickness?
 
-    let width = (max.x - min.x) as f32;
-    let height = (max.y - min.y) as f32;
+    let clip = dst.clip_rect();
+    let width = i64::from(end.x) - i64::from(start.x);
+    let height = i64::from(end.y) - i64::from(start.y);
     if width.abs() > height.abs() {
-        if width.is_sign_negative() {
-            swap(&mut min, &mut max);
-        }
-        let slope = height / width;
-        let derr = slope.abs();
-        let dy = slope.signum() as i32;
-
-        let mut err = 0.0;
-        let mut y = min.y;
-        for x in min.x..max.x {
-            dst[(x, y)] = color.into();
-            err += derr;
-            if err >= 0.5 {
-                y += dy;
-                err -= 1.0;
-            }
-        }
+        let (start, end) = if width < 0 { (end, start) } else { (start, end) };
+        for_each_line_pixel(
+            (start.x, start.y),
+            (end.x, end.y),
+            ((clip.min.x, clip.max.x), (clip.min.y, clip.max.y)),
+            |x, y| dst[(x, y)] = color.into(),
+        );
     } else {
-        if height.is_sign_negative() {
-            swap(&mut min, &mut max);
+        let (start, end) = if height < 0 { (end, start) } else { (start, end) };
+        for_each_line_pixel(
+            (start.y, start.x),
+            (end.y, end.x),
+            ((clip.min.y, clip.max.y), (clip.min.x, clip.max.x)),
+            |y, x| dst[(x, y)] = color.into(),
+        );
+    }
+}
+
+/// Walks the line along its major axis,
+/// visiting only the pixels inside of `clip` (half-open ranges).
+///
+/// `start.0 <= end.0` and `|end.0 - start.0| >= |end.1 - start.1|` are expected.
+/// Coordinates are (major, minor).
+#[allow(clippy::type_complexity)]
+fn for_each_line_pixel(
+    start: (i32, i32),
+    end: (i32, i32),
+    ((major_min, major_max), (minor_min, minor_max)): ((i32, i32), (i32, i32)),
+    mut f: impl FnMut(i32, i32),
+) {
+    let d_major = i64::from(end.0) - i64::from(start.0);
+    let d_minor = i64::from(end.1) - i64::from(start.1);
+    if d_major == 0 {
+        return
+    }
+
+    let mut first = i64::from(std::cmp::max(start.0, major_min));
+    let mut last = i64::from(std::cmp::min(end.0, major_max));
+
+    if d_minor == 0 {
+        if !(minor_min..minor_max).contains(&start.1) {
+            return
         }
-        let slope = width / height;
-        let derr = slope.abs();
-        let dx = slope.signum() as i32;
-
-        let mut err = 0.0;
-        let mut x = min.x;
-        for y in min.y..max.y {
-            dst[(x, y)] = color.into();
-            err += derr;
-            if err >= 0.5 {
-                x += dx;
-                err -= 1.0;
-            }
+    } else {
+        // range of major coordinates where minor coordinate is roughly inside of the clip,
+        // exact check is done per pixel
+        let slope = d_major as f64 / d_minor as f64;
+        let a = i64::from(start.0) as f64 + (f64::from(minor_min) - 0.5 - f64::from(start.1)) * slope;
+        let b = i64::from(start.0) as f64 + (f64::from(minor_max) - 0.5 - f64::from(start.1)) * slope;
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        first = std::cmp::max(first, a.floor() as i64 - 1);
+        last = std::cmp::min(last, b.ceil() as i64 + 2);
+    }
+
+    for major in first..last {
+        // minor = start.minor + round((major - start.major) * d_minor / d_major)
+        // i128, because product of two coordinate differences doesn't fit in i64
+        let numerator = 2 * i128::from(major - i64::from(start.0)) * i128::from(d_minor) + i128::from(d_major);
+        let minor = i128::from(start.1) + numerator.div_euclid(2 * i128::from(d_major));
+        if (i128::from(minor_min)..i128::from(minor_max)).contains(&minor) {
+            f(major as i32, minor as i32);
         }
     }
 }
 
+/// Draws `src` with its top left corner at `p`, blending by `src` alpha
 pub fn draw_bmp(dst: &Bitmap, src: &Bitmap, p: V2i) {
-    let src0 = V2 {
-        x: if p.x < 0 { -p.x } else { 0 },
-        y: if p.y < 0 { -p.y } else { 0 },
-    };
-    let src1 = src.dim();
+    draw_bmp_with(dst, src, p, |src| src);
+}
 
-    let dst0 = p;
-    let dst1 = dst0 + src1;
+/// Draws `src` like `draw_bmp`, but only its alpha is used, times `color` alpha, in `color`
+pub fn draw_bmp_tinted(dst: &Bitmap, src: &Bitmap, p: V2i, color: Color) {
+    let rgb = u32::from(color) & !Color::A_MASK;
+    draw_bmp_with(dst, src, p, |src| (((src >> 24) * u32::from(color.a) / 0xFF) << 24) | rgb);
+}
+
+/// Blends `src` pixels passed through `f` over `dst`, `src` top left corner at `p`
+fn draw_bmp_with(dst: &Bitmap, src: &Bitmap, p: V2i, f: impl Fn(u32) -> u32) {
+    let dst_rect = AABB {
+        min: p,
+        max: V2::new(p.x.saturating_add(src.width()), p.y.saturating_add(src.height())),
+    };
+    let dst_rect = match dst_rect.intersection(dst.clip_rect()) {
+        Some(rect) => rect,
+        None => return,
+    };
 
-    let dst_view = dst.clamped_view(dst0, dst1);
-    let src_view = src.clamped_view(src0, src1);
+    let dst_view = dst.clamped_view(dst_rect.min, dst_rect.max);
+    let src_view = src.clamped_view(dst_rect.min - p, dst_rect.max - p);
     for (dst_row, src_row) in dst_view.zip(src_view) {
         for (dst, src) in dst_row.iter_mut().zip(src_row.iter_mut()) {
-            let src_color = *src;
-            let dst_color = *dst;
-
-            let acoeff: f32 = (src_color >> 24) as f32 / 255.0;
-
-            let sr: i32 = ((src_color & Color::R_MASK) >> 16) as i32;
-            let sg: i32 = ((src_color & Color::G_MASK) >> 8) as i32;
-            let sb: i32 = (src_color & Color::B_MASK) as i32;
-
-            let dr: i32 = ((dst_color & Color::R_MASK) >> 16) as i32;
-            let dg: i32 = ((dst_color & Color::G_MASK) >> 8) as i32;
-            let db: i32 = (dst_color & Color::B_MASK) as i32;
+            *dst = blend(*dst, f(*src));
+        }
+    }
+}
 
-            // r = dr + (sr - dr) * acoeff
-            let r: u32 = (dr + ((sr - dr) as f32 * acoeff) as i32) as u32;
-            let g: u32 = (dg + ((sg - dg) as f32 * acoeff) as i32) as u32;
-            let b: u32 = (db + ((sb - db) as f32 * acoeff) as i32) as u32;
+/// Draws `src` with its top left corner at `p`, scaled by `scale` (nearest neighbour)
+pub fn draw_bmp_scaled(dst: &Bitmap, src: &Bitmap, p: V2i, scale: f32) {
+    if (scale - 1.0).abs() < f32::EPSILON {
+        draw_bmp(dst, src, p);
+        return
+    }
+    if scale <= 0.0 {
+        return
+    }
 
-            let color: u32 = (r << 16) | (g << 8) | b;
+    let size = (V2f::from(src.dim()) * scale).round();
+    let dst_rect = AABB {
+        min: p,
+        max: V2::new(p.x.saturating_add(size.x as i32), p.y.saturating_add(size.y as i32)),
+    };
+    let dst_rect = match dst_rect.intersection(dst.clip_rect()) {
+        Some(rect) => rect,
+        None => return,
+    };
 
-            *dst = color;
+    let dst_view = dst.clamped_view(dst_rect.min, dst_rect.max);
+    for (row, dst_row) in dst_view.enumerate() {
+        let y = dst_rect.min.y - p.y + row as i32;
+        let src_y = std::cmp::min((y as f32 / scale) as i32, src.height() - 1);
+        for (col, dst) in dst_row.iter_mut().enumerate() {
+            let x = dst_rect.min.x - p.x + col as i32;
+            let src_x = std::cmp::min((x as f32 / scale) as i32, src.width() - 1);
+            *dst = blend(*dst, src[(src_x, src_y)]);
         }
     }
 }
 
-pub fn clear(dst: &Bitmap, color: Color) {
-    fill_rect(dst, (0, 0).into(), dst.dim(), color);
-}
+/// Blends `src` over `dst` by `src` alpha
+fn blend(dst_color: u32, src_color: u32) -> u32 {
+    let acoeff: f32 = (src_color >> 24) as f32 / 255.0;
 
-pub fn aabb_to_screen(rect: AABB<f32>, canvas_info: &CanvasInfo) -> AABB<i32> {
-    let mut min = v2_to_screen(rect.min, canvas_info);
-    let mut max = v2_to_screen(rect.max, canvas_info);
-    core::mem::swap(&mut min.y, &mut max.y);
+    let sr: i32 = ((src_color & Color::R_MASK) >> 16) as i32;
+    let sg: i32 = ((src_color & Color::G_MASK) >> 8) as i32;
+    let sb: i32 = (src_color & Color::B_MASK) as i32;
 
-    AABB { min, max }
+    let dr: i32 = ((dst_color & Color::R_MASK) >> 16) as i32;
+    let dg: i32 = ((dst_color & Color::G_MASK) >> 8) as i32;
+    let db: i32 = (dst_color & Color::B_MASK) as i32;
+
+    // r = dr + (sr - dr) * acoeff
+    let r: u32 = (dr + ((sr - dr) as f32 * acoeff) as i32) as u32;
+    let g: u32 = (dg + ((sg - dg) as f32 * acoeff) as i32) as u32;
+    let b: u32 = (db + ((sb - db) as f32 * acoeff) as i32) as u32;
+
+    (r << 16) | (g << 8) | b
 }
 
-pub fn v2_to_screen(v: V2f, canvas_info: &CanvasInfo) -> V2i {
-    (&canvas_info.game_to_screen_matrix * (v - canvas_info.camera) + V2::new(0.0, canvas_info.height as f32))
-        .round().into()
+pub fn clear(dst: &Bitmap, color: Color) {
+    fill_rect(dst, (0, 0).into(), dst.dim(), color);
 }
@@ -7,7 +7,10 @@ use std::{
 };
 use crate::{
     file::{prelude::*, read_all},
-    geom::vector::prelude::*,
+    geom::{
+        vector::prelude::*,
+        aabb::AABB,
+    },
     render::Color,
 };
 
@@ -16,6 +19,8 @@ pub struct Bitmap {
     data: *mut u32,
     width: i32,
     height: i32,
+    /// Every rectangle is already intersected with the one below it
+    clip_stack: Vec<AABB<i32>>,
 }
 
 impl Drop for Bitmap {
@@ -33,6 +38,12 @@ impl Drop for Bitmap {
     }
 }
 
+impl Clone for Bitmap {
+    fn clone(&self) -> Self {
+        self.sub_bitmap((0, 0).into(), self.dim())
+    }
+}
+
 impl Index<(i32, i32)> for Bitmap {
     type Output = u32;
     fn index(&self, (x, y): (i32, i32)) -> &Self::Output {
@@ -68,7 +79,7 @@ impl Bitmap {
             )) as *mut u32
         };
 
-        Self { data, width, height }
+        Self { data, width, height, clip_stack: Vec::new() }
     }
 
     pub fn filled(mut self, color: Color) -> Self {
@@ -78,6 +89,17 @@ impl Bitmap {
         self
     }
 
+    /// Copy of a `size` area starting at `top_left`, which must be inside of the bitmap
+    pub fn sub_bitmap(&self, top_left: V2i, size: V2i) -> Self {
+        let mut result = Self::with_dimensions(size.x, size.y);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                result[(x, y)] = self[(top_left.x + x, top_left.y + y)];
+            }
+        }
+        result
+    }
+
     pub fn as_slice(&self) -> &[u32] {
         unsafe {
             slice::from_raw_parts(self.data, self.width as usize * self.height as usize)
@@ -90,22 +112,56 @@ impl Bitmap {
         }
     }
 
+    pub fn bounds(&self) -> AABB<i32> {
+        AABB { min: (0, 0).into(), max: self.dim() }
+    }
+
+    /// Area that drawing functions are allowed to touch.
+    ///
+    /// Whole bitmap if clip stack is empty
+    pub fn clip_rect(&self) -> AABB<i32> {
+        self.clip_stack.last().copied().unwrap_or_else(|| self.bounds())
+    }
+
+    /// Pushes `rect` intersected with current clip rectangle.
+    ///
+    /// Rectangle can be anywhere, if it doesn't overlap the current one
+    /// nothing will be drawn until it's popped.
+    pub fn push_clip(&mut self, rect: AABB<i32>) {
+        let current = self.clip_rect();
+        let clip = current.intersection(rect)
+            .unwrap_or(AABB { min: current.min, max: current.min });
+        self.clip_stack.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        let popped = self.clip_stack.pop();
+        debug_assert!(popped.is_some(), "Bitmap::pop_clip: clip stack is empty");
+    }
+
+    /// View of the area between `top_left` and `bottom_right` clamped to clip rectangle
     pub fn clamped_view(&self, mut top_left: V2i, mut bottom_right: V2i) -> BitmapView {
-        //TODO: rectangle type and contains method
-        top_left.x = utils::clamp(top_left.x, 0, self.width);
-        top_left.y = utils::clamp(top_left.y, 0, self.height);
-        bottom_right.x = utils::clamp(bottom_right.x, 0, self.width);
-        bottom_right.y = utils::clamp(bottom_right.y, 0, self.height);
+        let AABB { min, max } = self.clip_rect();
+        top_left.x = utils::clamp(top_left.x, min.x, max.x);
+        top_left.y = utils::clamp(top_left.y, min.y, max.y);
+        bottom_right.x = utils::clamp(bottom_right.x, top_left.x, max.x);
+        bottom_right.y = utils::clamp(bottom_right.y, top_left.y, max.y);
+
+        let width = (bottom_right.x - top_left.x) as usize;
+        let height = (bottom_right.y - top_left.y) as isize;
+        if width == 0 || height == 0 {
+            // empty view, top_left may point past the end of the data
+            return BitmapView { ptr: self.data, end: self.data, width: 0, bmp: self };
+        }
 
         let ptr = unsafe {
             self.data.add((top_left.y * self.width + top_left.x) as usize)
         };
-        let height = (bottom_right.y - top_left.y) as isize;
 
         BitmapView {
             ptr,
             end: unsafe { ptr.offset(height * self.width as isize) },
-            width: (bottom_right.x - top_left.x) as usize,
+            width,
             bmp: self,
         }
     }
@@ -150,7 +206,7 @@ impl Bitmap {
             let height = png.height as i32;
             mem::forget(png);
 
-            Ok(Bitmap { data, width, height })
+            Ok(Bitmap { data, width, height, clip_stack: Vec::new() })
         }
 
         fn load_bmp(filepath: impl AsRef<Path>) -> Result {
@@ -186,7 +242,7 @@ impl Bitmap {
                     let mut dst = dst_row;
                     let mut src = src_row;
                     for _x in 0..bmp_width {
-                        ptr::write_unaligned(dst, *src);
+                        ptr::write_unaligned(dst, ptr::read_unaligned(src));
                         dst = dst.add(1);
                         src = src.add(1);
                     }
@@ -199,6 +255,7 @@ impl Bitmap {
                 data: bmp_data,
                 width: bmp_width,
                 height: bmp_height,
+                clip_stack: Vec::new(),
             })
         }
     }
@@ -255,6 +312,7 @@ impl From<platform::graphics::WindowBuffer> for Bitmap {
             data: window_buffer.data as *mut u32,
             width: window_buffer.width,
             height: window_buffer.height,
+            clip_stack: Vec::new(),
         }
     }
 }
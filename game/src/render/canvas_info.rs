@@ -1,7 +1,9 @@
 use crate::geom::{
     matrix::Mat2,
     vector::prelude::*,
+    aabb::AABB,
 };
+use super::space::{WorldPos, CanvasPos, ScreenPos};
 
 pub struct CanvasInfo {
     pub width: i32,
@@ -11,5 +13,64 @@ pub struct CanvasInfo {
     pub game_to_screen_matrix: Mat2::<f32>,
     pub screen_to_game_matrix: Mat2::<f32>,
 
+    /// Bottom left corner of the screen in world units
     pub camera: V2f,
-}
\ No newline at end of file
+    pub zoom: f32,
+}
+
+impl CanvasInfo {
+    /// Size of the canvas in world units at zoom 1.0
+    pub fn unzoomed_view_size(&self) -> V2f {
+        let V2 { x, y } = &self.screen_to_game_matrix * V2::new(self.width as f32, self.height as f32);
+        V2::new(x.abs(), y.abs())
+    }
+
+    /// Size of the canvas in world units
+    pub fn view_size(&self) -> V2f {
+        self.unzoomed_view_size() / self.zoom
+    }
+
+    /// Part of the world that is on the canvas
+    pub fn visible_rect(&self) -> AABB<f32> {
+        AABB {
+            min: self.camera,
+            max: self.camera + self.view_size(),
+        }
+    }
+
+    /// Canvas pixels per world unit, including zoom
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.game_to_screen_matrix.0.x.abs() * self.zoom
+    }
+
+    pub fn world_to_canvas(&self, WorldPos(v): WorldPos) -> CanvasPos {
+        let p = (&self.game_to_screen_matrix * (v - self.camera)) * self.zoom
+            + V2::new(0.0, self.height as f32);
+        CanvasPos(p.round().into())
+    }
+
+    pub fn screen_to_canvas(&self, ScreenPos(p): ScreenPos) -> CanvasPos {
+        CanvasPos(V2::new(p.x.div_euclid(self.scale), p.y.div_euclid(self.scale)))
+    }
+
+    /// Doesn't lose precision of screen pixels like going through canvas does
+    pub fn screen_to_world(&self, ScreenPos(p): ScreenPos) -> WorldPos {
+        self.canvas_f32_to_world(V2f::from(p) / self.scale as f32)
+    }
+
+    /// World rectangle (y up) to canvas rectangle (y down)
+    pub fn world_to_canvas_rect(&self, rect: AABB<f32>) -> AABB<i32> {
+        let CanvasPos(bottom_left) = self.world_to_canvas(WorldPos(rect.min));
+        let CanvasPos(top_right) = self.world_to_canvas(WorldPos(rect.max));
+        AABB {
+            min: V2::new(bottom_left.x, top_right.y),
+            max: V2::new(top_right.x, bottom_left.y),
+        }
+    }
+
+    fn canvas_f32_to_world(&self, p: V2f) -> WorldPos {
+        // moving origin from top left to bottom left
+        let p = p - V2::new(0.0, self.height as f32);
+        WorldPos(&self.screen_to_game_matrix * p / self.zoom + self.camera)
+    }
+}
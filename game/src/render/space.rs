@@ -0,0 +1,43 @@
+//! Coordinate spaces.
+//!
+//! - world: tiles, y goes up, origin is bottom left corner of a tilemap
+//! - canvas: pixels of the (low resolution) bitmap the game draws to, y goes down
+//! - screen: pixels of the window, y goes down, canvas scaled up by `CanvasInfo::scale`
+//!
+//! Conversions between them are on `CanvasInfo`
+
+use crate::geom::vector::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WorldPos(pub V2f);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CanvasPos(pub V2i);
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScreenPos(pub V2i);
+
+impl WorldPos {
+    /// Coordinates of the tile this position is in
+    pub fn tile(self) -> V2i {
+        self.0.floor().into()
+    }
+}
+
+impl From<V2f> for WorldPos {
+    fn from(v: V2f) -> Self {
+        Self(v)
+    }
+}
+
+impl From<(f32, f32)> for WorldPos {
+    fn from(v: (f32, f32)) -> Self {
+        Self(v.into())
+    }
+}
+
+impl From<(i32, i32)> for ScreenPos {
+    fn from(v: (i32, i32)) -> Self {
+        Self(v.into())
+    }
+}
@@ -0,0 +1,142 @@
+use super::*;
+use crate::test_utils::Rng;
+
+impl Rng {
+    /// Mostly coordinates around the bitmap, sometimes far away and extreme values
+    fn coord(&mut self) -> i32 {
+        match self.next() % 8 {
+            0 => i32::MIN,
+            1 => i32::MAX,
+            2 | 3 => self.range(-1_000_000, 1_000_000),
+            _ => self.range(-40, 80),
+        }
+    }
+
+    fn point(&mut self) -> V2i {
+        V2::new(self.coord(), self.coord())
+    }
+}
+
+const BACKGROUND: Color = Color::PURPLE;
+
+fn untouched_outside(bmp: &Bitmap, clip: AABB<i32>) -> bool {
+    (0..bmp.height()).all(|y| (0..bmp.width()).all(|x|
+        clip.contains((x, y).into()) || bmp[(x, y)] == u32::from(BACKGROUND)
+    ))
+}
+
+#[test]
+fn random_geometry_stays_inside_clip() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let sprite = Bitmap::with_dimensions(7, 5).filled(Color::WHITE);
+
+    for _ in 0..2000 {
+        let mut bmp = Bitmap::with_dimensions(32, 24).filled(BACKGROUND);
+        let clip_min = V2::new(rng.range(-8, 40), rng.range(-8, 32));
+        let clip_max = clip_min + V2::new(rng.range(0, 30), rng.range(0, 30));
+        bmp.push_clip(AABB { min: clip_min, max: clip_max });
+        let clip = bmp.clip_rect();
+
+        let (a, b) = (rng.point(), rng.point());
+        match rng.next() % 4 {
+            0 => fill_rect(&bmp, a, b, Color::RED),
+            1 => draw_rect(&mut bmp, a, b, Color::RED, rng.range(-1, 6)),
+            2 => draw_line(&mut bmp, a, b, Color::RED, 1),
+            _ => draw_bmp(&bmp, &sprite, a),
+        }
+
+        assert!(untouched_outside(&bmp, clip), "clip: {:?}, a: {:?}, b: {:?}", clip, a, b);
+        bmp.pop_clip();
+    }
+}
+
+#[test]
+fn nested_clip_is_intersection() {
+    let mut bmp = Bitmap::with_dimensions(16, 16).filled(BACKGROUND);
+    bmp.push_clip(AABB { min: (2, 2).into(), max: (10, 10).into() });
+    bmp.push_clip(AABB { min: (6, -5).into(), max: (30, 8).into() });
+
+    fill_rect(&bmp, (-100, -100).into(), (100, 100).into(), Color::RED);
+    assert!(untouched_outside(&bmp, AABB { min: (6, 2).into(), max: (10, 8).into() }));
+    assert_eq!(bmp[(6, 2)], u32::from(Color::RED));
+    assert_eq!(bmp[(9, 7)], u32::from(Color::RED));
+
+    bmp.pop_clip();
+    assert_eq!(bmp.clip_rect().min, (2, 2).into());
+    bmp.pop_clip();
+    assert_eq!(bmp.clip_rect().max, bmp.dim());
+}
+
+#[test]
+fn clipped_line_matches_unclipped() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+    for _ in 0..500 {
+        let (a, b) = (V2::new(rng.range(-20, 50), rng.range(-20, 50)), V2::new(rng.range(-20, 50), rng.range(-20, 50)));
+
+        // reference: big enough bitmap, so that nothing is clipped
+        let mut big = Bitmap::with_dimensions(100, 100).filled(BACKGROUND);
+        draw_line(&mut big, a + V2::diag(30), b + V2::diag(30), Color::RED, 1);
+
+        let mut small = Bitmap::with_dimensions(30, 30).filled(BACKGROUND);
+        draw_line(&mut small, a, b, Color::RED, 1);
+
+        for y in 0..30 {
+            for x in 0..30 {
+                assert_eq!(small[(x, y)], big[(x + 30, y + 30)], "a: {:?}, b: {:?}, (x, y): {:?}", a, b, (x, y));
+            }
+        }
+    }
+}
+
+#[test]
+fn rect_outline() {
+    let mut bmp = Bitmap::with_dimensions(8, 8).filled(BACKGROUND);
+    draw_rect(&mut bmp, (1, 1).into(), (7, 7).into(), Color::RED, 1);
+
+    for i in 1..7 {
+        assert_eq!(bmp[(i, 1)], u32::from(Color::RED));
+        assert_eq!(bmp[(i, 6)], u32::from(Color::RED));
+        assert_eq!(bmp[(1, i)], u32::from(Color::RED));
+        assert_eq!(bmp[(6, i)], u32::from(Color::RED));
+    }
+    assert_eq!(bmp[(3, 3)], u32::from(BACKGROUND));
+    assert!(untouched_outside(&bmp, AABB { min: (1, 1).into(), max: (7, 7).into() }));
+}
+
+#[test]
+fn coordinate_space_round_trip() {
+    use super::{
+        canvas_info::CanvasInfo,
+        space::{WorldPos, CanvasPos, ScreenPos},
+    };
+    use crate::geom::matrix::Mat2;
+
+    let info = CanvasInfo {
+        width: 240,
+        height: 135,
+        scale: 4,
+        game_to_screen_matrix: Mat2::from([[16.0, 0.0], [0.0, -16.0]]),
+        screen_to_game_matrix: Mat2::from([[1.0 / 16.0, 0.0], [0.0, -1.0 / 16.0]]),
+        camera: (3.25, -1.5).into(),
+        zoom: 2.0,
+    };
+
+    // world origin of the view is bottom left corner of the canvas
+    assert_eq!(info.world_to_canvas(WorldPos(info.camera)), CanvasPos((0, 135).into()));
+
+    let mut rng = Rng(0xDEAD_BEEF_CAFE_F00D);
+    for _ in 0..1000 {
+        // picking a tile by its center on screen gives back the same tile
+        let tile = V2::new(rng.range(-5, 30), rng.range(-5, 20));
+        let center = WorldPos(V2f::from(tile) + V2::diag(0.5));
+        let CanvasPos(on_canvas) = info.world_to_canvas(center);
+        assert_eq!(info.screen_to_world(ScreenPos(on_canvas * info.scale)).tile(), tile);
+
+        // one screen pixel is a quarter of a canvas pixel, so it may round to a neighbour
+        let screen = ScreenPos((rng.range(-100, 1100), rng.range(-100, 600)).into());
+        let CanvasPos(canvas) = info.screen_to_canvas(screen);
+        let CanvasPos(back) = info.world_to_canvas(info.screen_to_world(screen));
+        assert!((back.x - canvas.x).abs() <= 1 && (back.y - canvas.y).abs() <= 1, "{:?} {:?}", back, canvas);
+    }
+}
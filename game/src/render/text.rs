@@ -1,100 +1,395 @@
 use std::{
+    cell::RefCell,
     collections::HashMap,
+    iter,
     path::Path,
+    rc::Rc,
+};
+use rusttype::{point, Font, FontCollection, GlyphId, Scale};
+use crate::{
+    geom::{vector::prelude::*, aabb::AABB},
+    file::invalid_data,
 };
-use crate::geom::vector::prelude::*;
 use super::{
     Bitmap,
     Color,
-    draw_bmp,
+    draw_bmp_tinted,
+    draw_rect,
 };
 
+#[cfg(test)]
+mod tests;
+
+/// Drawn for characters that no font has, if a font has it
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// Glyph bitmaps of a font, rasterized when a character is first drawn or measured.
+/// Characters the font doesn't have come from fallback fonts, those that none of them has
+/// are drawn as `REPLACEMENT` or a box
 pub struct FontBitmaps {
-    chars: HashMap<char, Bitmap>,
+    /// The first one is the main font, the rest are fallbacks in the order they are tried
+    fonts: Vec<Font<'static>>,
+    scale: Scale,
+    /// Distance from the top of a glyph bitmap to the baseline
+    ascent: f32,
     height: i32,
+    /// Between the bottom of a line and the top of the next one
+    line_gap: i32,
+    glyphs: RefCell<HashMap<char, Rc<Glyph>>>,
+}
+
+struct Glyph {
+    /// `None` for whitespace
+    bmp: Option<Bitmap>,
+    /// From the pen position to the left edge of `bmp`
+    left: i32,
+    /// How far the pen moves after this glyph
+    advance: f32,
+    /// Index into `FontBitmaps::fonts` and the glyph there, for kerning.
+    /// `None` for the replacement box
+    id: Option<(usize, GlyphId)>,
+}
+
+/// Color and effects of drawn text
+#[derive(Copy, Clone)]
+pub struct TextStyle {
+    /// Of the text outside of color markup, see `FontBitmaps::layout`
+    pub color: Color,
+    /// One pixel around the glyphs, keeps text readable over anything
+    pub outline: Option<Color>,
+    /// One pixel down and right of the glyphs
+    pub shadow: Option<Color>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self { color: Color::WHITE, outline: None, shadow: None }
+    }
+}
+
+impl TextStyle {
+    pub fn outlined(self, outline: Color) -> Self {
+        Self { outline: Some(outline), ..self }
+    }
+
+    pub fn shadowed(self, shadow: Color) -> Self {
+        Self { shadow: Some(shadow), ..self }
+    }
+
+    /// How far the outline and shadow reach out of the glyphs to the left and top,
+    /// and to the right and bottom
+    fn reach(&self) -> (i32, i32) {
+        let outline = i32::from(self.outline.is_some());
+        let shadow = i32::from(self.shadow.is_some());
+        (outline, outline.max(shadow))
+    }
+}
+
+/// Glyph and its pen position, or a color change, see `FontBitmaps::pieces`
+enum Piece {
+    Glyph(Rc<Glyph>, f32),
+    /// `None` goes back to the color of the style
+    Color(Option<Color>),
+}
+
+/// Lines of a text placed by `FontBitmaps::layout`
+pub struct TextLayout {
+    /// Lines, their top left corners, relative to the top left corner of the layout,
+    /// and the markup color they start with
+    lines: Vec<(String, V2i, Option<Color>)>,
+    /// Box around the lines, relative to the top left corner of the layout
+    pub bounds: AABB<i32>,
 }
 
 impl FontBitmaps {
     pub fn height(&self) -> i32 { self.height }
 
+    /// From the top of a line to the top of the next one
+    pub fn line_height(&self) -> i32 { self.height + self.line_gap }
+
+    /// Width of one line of text, without markup
     pub fn width(&self, s: &str) -> i32 {
-        self.to_bitmaps(s).map(Bitmap::width).sum()
+        line_width(&self.pieces(s, false))
     }
 
-    /// Draws string of text to the dst `Bitmap`
-    /// 
+    /// Draws one line of text, without markup, to the dst `Bitmap`, `p` is its top left corner
+    ///
     /// Returns width of drawn string in pixels
-    pub fn draw_string(&self, canvas: &Bitmap, V2i { x: start_x, y }: V2i, s: &str) -> i32 {
-        let mut x = start_x;
-        for letter in self.to_bitmaps(s) {
-            draw_bmp(canvas, letter, (x, y).into());
-            x += letter.width();
-        }
-        x - start_x
+    pub fn draw_string(&self, canvas: &Bitmap, p: V2i, s: &str) -> i32 {
+        let pieces = self.pieces(s, false);
+        draw_pieces(canvas, p, &pieces, TextStyle::default(), None);
+        line_width(&pieces)
     }
 
-    fn to_bitmaps<'slf, 'str, 'res>(&'slf self, s: &'str str) -> impl Iterator<Item = &'res Bitmap>
-        where 'slf: 'res,
-              'str: 'res
-    {
-        s.chars().map(move |ref c| self.chars.get(c)
-            .unwrap_or_else(|| panic!("No bitmap for character: {}", c)))
+    /// Splits `text` into lines at '\n' and, with `max_width`, between words so that lines
+    /// aren't wider than that. A word that is wider gets a line of its own.
+    ///
+    /// `{red}`, `{yellow}`, `{white}`, `{grey}`, `{purple}` and `{black}` in `text` switch
+    /// the color of the text after them, `{}` switches back to the color of the style.
+    /// Other braces are drawn as they are
+    pub fn layout(&self, text: &str, max_width: Option<i32>) -> TextLayout {
+        let mut color = None;
+        let lines: Vec<(&str, i32, Option<Color>)> = text.split('\n')
+            .flat_map(|paragraph| self.wrap(paragraph, max_width))
+            .map(|line| {
+                let pieces = self.pieces(line, true);
+                let start = color;
+                for piece in &pieces {
+                    if let Piece::Color(new) = piece {
+                        color = *new;
+                    }
+                }
+                (line, line_width(&pieces), start)
+            })
+            .collect();
+
+        let mut bounds: Option<AABB<i32>> = None;
+        let lines = lines.into_iter()
+            .enumerate()
+            .map(|(i, (line, width, color))| {
+                let min = V2::new(0, i as i32 * self.line_height());
+                let rect = AABB { min, max: min + V2::new(width, self.height) };
+                bounds = Some(match bounds {
+                    Some(b) => AABB {
+                        min: V2::new(b.min.x.min(rect.min.x), b.min.y),
+                        max: V2::new(b.max.x.max(rect.max.x), rect.max.y),
+                    },
+                    None => rect,
+                });
+                (line.to_string(), min, color)
+            })
+            .collect();
+
+        TextLayout { lines, bounds: bounds.unwrap_or(AABB { min: V2::diag(0), max: V2::diag(0) }) }
     }
 
-    pub fn new(filepath: impl AsRef<Path>, height: i32) -> std::io::Result<Self> {
-        use rusttype::{point, FontCollection, PositionedGlyph, Scale};
+    /// Draws `layout` with its top left corner at `p`
+    pub fn draw_layout(&self, canvas: &Bitmap, p: V2i, layout: &TextLayout, style: TextStyle) {
+        for (line, pos, color) in &layout.lines {
+            draw_pieces(canvas, p + *pos, &self.pieces(line, true), style, *color);
+        }
+    }
 
-        const ALL_SYMBOLS: &str =
-            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-_.,:;!?";
+    /// Lays out and draws `text` with its top left corner at `p`, see `layout`.
+    /// Returns the box around the drawn text, with the outline and shadow
+    pub fn draw_text(
+        &self,
+        canvas: &Bitmap,
+        p: V2i,
+        text: &str,
+        max_width: Option<i32>,
+        style: TextStyle,
+    ) -> AABB<i32> {
+        let layout = self.layout(text, max_width);
+        self.draw_layout(canvas, p, &layout, style);
+        let (before, after) = style.reach();
+        let bounds = layout.bounds.translate(p);
+        AABB { min: bounds.min - V2::diag(before), max: bounds.max + V2::diag(after) }
+    }
 
-        let font = {
-            let file = crate::file::read_all(filepath)?;
-            let collection = FontCollection::from_bytes(file)
-                .unwrap_or_else(|e| panic!("error constructing a FontCollection from bytes: {}", e));
-            collection.into_font()
-                .unwrap_or_else(|e| panic!("error turning FontCollection into a Font: {}", e))
+    /// Lines of one paragraph, see `layout`
+    fn wrap<'a>(&self, paragraph: &'a str, max_width: Option<i32>) -> Vec<&'a str> {
+        let max_width = match max_width {
+            Some(max_width) => max_width,
+            None => return vec![paragraph],
         };
+        let mut lines = Vec::new();
+        // the current line and the end of its last word
+        let (mut start, mut end) = (0, 0);
+        let word_ends = paragraph.match_indices(' ').map(|(i, _)| i).chain(iter::once(paragraph.len()));
+        for word_end in word_ends {
+            if end > start && line_width(&self.pieces(&paragraph[start..word_end], true)) > max_width {
+                lines.push(&paragraph[start..end]);
+                // the space between the lines is dropped
+                start = end + 1;
+            }
+            end = word_end;
+        }
+        lines.push(&paragraph[start..end]);
+        lines
+    }
+
+    /// Glyphs of a line and pen positions they are drawn at, with kerning,
+    /// and with `markup` the color changes between them
+    fn pieces(&self, s: &str, markup: bool) -> Vec<Piece> {
+        let mut result = Vec::new();
+        let mut previous: Option<(Rc<Glyph>, f32)> = None;
+        let mut rest = s;
+        while let Some(c) = rest.chars().next() {
+            let tag = if markup { parse_markup(rest) } else { None };
+            if let Some((color, len)) = tag {
+                result.push(Piece::Color(color));
+                rest = &rest[len..];
+                continue;
+            }
+            rest = &rest[c.len_utf8()..];
+
+            let glyph = self.glyph(c);
+            let mut pen = 0.0;
+            if let Some((previous, previous_pen)) = &previous {
+                pen = previous_pen + previous.advance;
+                if let (Some((font, a)), Some((b_font, b))) = (previous.id, glyph.id) {
+                    if font == b_font {
+                        pen += self.fonts[font].pair_kerning(self.scale, a, b);
+                    }
+                }
+            }
+            previous = Some((Rc::clone(&glyph), pen));
+            result.push(Piece::Glyph(glyph, pen));
+        }
+        result
+    }
+
+    /// Glyph of `c`, from the cache or rasterized into it
+    fn glyph(&self, c: char) -> Rc<Glyph> {
+        if let Some(glyph) = self.glyphs.borrow().get(&c) {
+            return Rc::clone(glyph);
+        }
+        let glyph = self.rasterize(c)
+            .or_else(|| self.rasterize(REPLACEMENT))
+            .unwrap_or_else(|| replacement_box(self.height));
+        let glyph = Rc::new(glyph);
+        self.glyphs.borrow_mut().insert(c, Rc::clone(&glyph));
+        glyph
+    }
+
+    /// `c` from the first font that has it
+    fn rasterize(&self, c: char) -> Option<Glyph> {
+        let (font, glyph) = self.fonts.iter()
+            .map(|font| font.glyph(c))
+            .enumerate()
+            .find(|(_, glyph)| glyph.id().0 != 0)?;
+        let id = glyph.id();
+        let glyph = glyph.scaled(self.scale);
+        let advance = glyph.h_metrics().advance_width;
+
+        // The origin of a line of text is at the baseline (roughly where
+        // non-descending letters sit). We don't want to clip the text, so we shift
+        // it down with an offset when laying it out. Ascent is the distance between
+        // the baseline and the highest edge of any glyph in the font.
+        let glyph = glyph.positioned(point(0.0, self.ascent));
+        let bbox = glyph.pixel_bounding_box();
+        let bmp = bbox.map(|bbox| {
+            let width = bbox.max.x - bbox.min.x;
+            let mut bmp = Bitmap::with_dimensions(width.max(1), self.height).filled(Color::TRANSPARENT);
+            glyph.draw(|x, y, v| {
+                let v = utils::clamp(v, 0.0, 1.0);
+                let x = x as i32;
+                let y = y as i32 + bbox.min.y as i32;
+                // glyphs of fallback fonts can reach out of the line
+                if (0..bmp.height()).contains(&y) {
+                    bmp[(x, y)] = Color::argb(v, 1.0, 1.0, 1.0).into();
+                }
+            });
+            bmp
+        });
+        Some(Glyph { bmp, left: bbox.map_or(0, |bbox| bbox.min.x), advance, id: Some((font, id)) })
+    }
+
+    fn with_fonts(fonts: Vec<Font<'static>>, scale: Scale, ascent: f32, height: i32, line_gap: i32) -> Self {
+        Self { fonts, scale, ascent, height, line_gap, glyphs: RefCell::default() }
+    }
+
+    pub fn new(filepath: impl AsRef<Path>, height: i32) -> std::io::Result<Self> {
+        let font = load_font(filepath)?;
 
         let v_metrics_unscaled = font.v_metrics_unscaled();
         let height = height as f32
             * ((v_metrics_unscaled.ascent - v_metrics_unscaled.descent) / v_metrics_unscaled.ascent);
-        let height_px = height.ceil() as i32;
         let scale = Scale::uniform(height as f32);
-
-        // The origin of a line of text is at the baseline (roughly where
-        // non-descending letters sit). We don't want to clip the text, so we shift
-        // it down with an offset when laying it out. v_metrics.ascent is the
-        // distance between the baseline and the highest edge of any glyph in
-        // the font. That's enough to guarantee that there's no clipping.
         let v_metrics = font.v_metrics(scale);
-        let offset = point(0.0, v_metrics.ascent);
-
-        let glyphs: Vec<PositionedGlyph> = font.layout(ALL_SYMBOLS, scale, offset).collect();
-
-        let mut char_bitmaps: HashMap<char, Bitmap> = glyphs.iter().zip(ALL_SYMBOLS.chars())
-            .filter_map(|(g, ch)|
-                if let Some(bbox) = g.pixel_bounding_box() {
-                    let width = bbox.max.x - bbox.min.x;
-                    let mut bmp = Bitmap::with_dimensions(width, height_px).filled(Color::TRANSPARENT);
-                    g.draw(|x, y, v| {
-                        let v = utils::clamp(v, 0.0, 1.0);
-                        let x = x as i32;
-                        let y = y as i32 + bbox.min.y as i32;
-                        bmp[(x, y)] = Color::argb(v, 1.0, 1.0, 1.0).into();
-                    });
-
-                    Some((ch, bmp))
-                } else {
-                    None
-                }
-            )
-            .collect();
-        let space = Bitmap::with_dimensions(height_px / 2, height_px).filled(Color::TRANSPARENT);
-        char_bitmaps.insert(' ', space);
 
-        Ok(Self {
-            chars: char_bitmaps,
-            height: height_px,
+        Ok(Self::with_fonts(vec![font], scale, v_metrics.ascent, height.ceil() as i32, v_metrics.line_gap.round() as i32))
+    }
+
+    /// Adds a font for characters the fonts so far don't have, at the same size
+    pub fn add_fallback(&mut self, filepath: impl AsRef<Path>) -> std::io::Result<()> {
+        self.fonts.push(load_font(filepath)?);
+        // replacements in the cache may be in the new font
+        self.glyphs.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+/// Width of a line of `pieces`
+fn line_width(pieces: &[Piece]) -> i32 {
+    pieces.iter()
+        .rev()
+        .find_map(|piece| match piece {
+            Piece::Glyph(glyph, pen) => Some((pen + glyph.advance).round() as i32),
+            Piece::Color(_) => None,
         })
+        .unwrap_or(0)
+}
+
+/// Draws a line of `pieces` with its top left corner at `p`, `color` is the markup color it starts with
+fn draw_pieces(canvas: &Bitmap, p: V2i, pieces: &[Piece], style: TextStyle, mut color: Option<Color>) {
+    let draw_glyphs = |offset: V2i, color: Color| {
+        for piece in pieces {
+            if let Piece::Glyph(glyph, pen) = piece {
+                if let Some(bmp) = &glyph.bmp {
+                    let pos = V2::new(p.x + pen.round() as i32 + glyph.left, p.y) + offset;
+                    draw_bmp_tinted(canvas, bmp, pos, color);
+                }
+            }
+        }
+    };
+    if let Some(shadow) = style.shadow {
+        draw_glyphs(V2::new(1, 1), shadow);
+    }
+    if let Some(outline) = style.outline {
+        for y in -1..=1 {
+            for x in -1..=1 {
+                if (x, y) != (0, 0) {
+                    draw_glyphs(V2::new(x, y), outline);
+                }
+            }
+        }
+    }
+
+    for piece in pieces {
+        match piece {
+            Piece::Glyph(glyph, pen) => if let Some(bmp) = &glyph.bmp {
+                let pos = V2::new(p.x + pen.round() as i32 + glyph.left, p.y);
+                draw_bmp_tinted(canvas, bmp, pos, color.unwrap_or(style.color));
+            },
+            Piece::Color(new) => color = *new,
+        }
+    }
+}
+
+/// Color markup at the start of `s` and its length, see `FontBitmaps::layout`.
+/// The color is `None` for `{}`
+fn parse_markup(s: &str) -> Option<(Option<Color>, usize)> {
+    if !s.starts_with('{') {
+        return None;
     }
-}
\ No newline at end of file
+    let end = s.find('}')?;
+    let color = match &s[1..end] {
+        "" => None,
+        "red" => Some(Color::RED),
+        "yellow" => Some(Color::YELLOW),
+        "white" => Some(Color::WHITE),
+        "grey" => Some(Color::GREY),
+        "purple" => Some(Color::PURPLE),
+        "black" => Some(Color::BLACK),
+        _ => return None,
+    };
+    Some((color, end + 1))
+}
+
+fn load_font(filepath: impl AsRef<Path>) -> std::io::Result<Font<'static>> {
+    let file = crate::file::read_all(filepath)?;
+    FontCollection::from_bytes(file)
+        .and_then(FontCollection::into_font)
+        .map_err(|e| invalid_data(format!("invalid font: {}", e)))
+}
+
+/// Outline of a box, for characters that no font has
+fn replacement_box(height: i32) -> Glyph {
+    let width = (height / 2).max(3);
+    let mut bmp = Bitmap::with_dimensions(width, height).filled(Color::TRANSPARENT);
+    // a column is left empty on the right, to keep boxes apart
+    draw_rect(&mut bmp, V2::new(0, height / 5), V2::new(width - 1, height - height / 5), Color::WHITE, 1);
+    Glyph { bmp: Some(bmp), left: 0, advance: width as f32, id: None }
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+/// Every character is a replacement box, 6 pixels wide
+fn no_fonts() -> FontBitmaps {
+    FontBitmaps::with_fonts(Vec::new(), Scale::uniform(12.0), 10.0, 12, 0)
+}
+
+fn lines(layout: &TextLayout) -> Vec<(&str, i32)> {
+    layout.lines.iter().map(|(line, pos, _)| (line.as_str(), pos.x)).collect()
+}
+
+#[test]
+fn missing_characters_are_boxes() {
+    let font = no_fonts();
+    assert_eq!(font.width("(ж)"), 18);
+    assert_eq!(font.width(""), 0);
+
+    let canvas = Bitmap::with_dimensions(64, 16).filled(Color::BLACK);
+    assert_eq!(font.draw_string(&canvas, V2::new(60, 8), "[|]"), 18);
+}
+
+#[test]
+fn glyphs_are_cached() {
+    let font = no_fonts();
+    let first = font.glyph('?');
+    assert!(Rc::ptr_eq(&first, &font.glyph('?')));
+    font.glyphs.borrow_mut().clear();
+    assert!(!Rc::ptr_eq(&first, &font.glyph('?')));
+}
+
+#[test]
+fn wraps_between_words() {
+    let font = no_fonts();
+    let layout = font.layout("aa bb cc", Some(30));
+    assert_eq!(lines(&layout), vec![("aa bb", 0), ("cc", 0)]);
+    assert_eq!(layout.bounds.max, V2::new(30, 24));
+
+    let layout = font.layout("aaaaaa b\nc", Some(12));
+    assert_eq!(lines(&layout), vec![("aaaaaa", 0), ("b", 0), ("c", 0)]);
+}
+
+#[test]
+fn markup_changes_color_across_lines() {
+    let font = no_fonts();
+    let layout = font.layout("a {red}b c{} d {x}", Some(18));
+    assert_eq!(lines(&layout), vec![("a {red}b", 0), ("c{} d", 0), ("{x}", 0)]);
+    let starts: Vec<Option<u32>> = layout.lines.iter().map(|(_, _, color)| color.map(u32::from)).collect();
+    assert_eq!(starts, vec![None, Some(Color::RED.into()), None]);
+    // unknown markup is drawn
+    assert_eq!(layout.bounds.max.x, 18);
+    assert_eq!(font.width("{red}"), 30);
+}
+
+#[test]
+fn draws_in_style_color() {
+    let font = no_fonts();
+    let canvas = Bitmap::with_dimensions(16, 16).filled(Color::BLACK);
+    let style = TextStyle { color: Color::YELLOW, ..TextStyle::default() }.shadowed(Color::GREY);
+    let bounds = font.draw_text(&canvas, V2::new(2, 2), "{}|", None, style);
+    assert_eq!((bounds.min, bounds.max), (V2::new(2, 2), V2::new(9, 15)));
+
+    let rgb = |color: Color| u32::from(color) & !Color::A_MASK;
+    // left edge of the box, and its shadow to the right of it
+    assert_eq!(canvas[(2, 8)], rgb(Color::YELLOW));
+    assert_eq!(canvas[(3, 8)], rgb(Color::GREY));
+}
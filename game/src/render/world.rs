@@ -0,0 +1,52 @@
+//! Drawing in world coordinates.
+//!
+//! Same as drawing functions in `render`, but positions are converted
+//! with `CanvasInfo`, so camera and zoom are taken into account
+
+use crate::geom::aabb::AABB;
+use super::{
+    Bitmap,
+    Color,
+    canvas_info::CanvasInfo,
+    space::{WorldPos, CanvasPos},
+};
+
+/// Draws `src` with its top left corner at `top_left`.
+///
+/// Bitmaps are expected to have the same pixels per world unit as the canvas at zoom 1.0
+pub fn draw_bmp(dst: &Bitmap, info: &CanvasInfo, src: &Bitmap, top_left: impl Into<WorldPos>) {
+    let CanvasPos(p) = info.world_to_canvas(top_left.into());
+    super::draw_bmp_scaled(dst, src, p, info.zoom);
+}
+
+/// Draws `src` with its center at `center`
+pub fn draw_bmp_centered(dst: &Bitmap, info: &CanvasInfo, src: &Bitmap, center: impl Into<WorldPos>) {
+    let WorldPos(center) = center.into();
+    let unzoomed_pixels_per_unit = info.pixels_per_unit() / info.zoom;
+    let half_width = src.width() as f32 * 0.5 / unzoomed_pixels_per_unit;
+    let half_height = src.height() as f32 * 0.5 / unzoomed_pixels_per_unit;
+    draw_bmp(dst, info, src, (center.x - half_width, center.y + half_height));
+}
+
+pub fn fill_rect(dst: &Bitmap, info: &CanvasInfo, rect: AABB<f32>, color: Color) {
+    let AABB { min, max } = info.world_to_canvas_rect(rect);
+    super::fill_rect(dst, min, max, color);
+}
+
+pub fn draw_rect(dst: &mut Bitmap, info: &CanvasInfo, rect: AABB<f32>, color: Color, thickness: i32) {
+    let AABB { min, max } = info.world_to_canvas_rect(rect);
+    super::draw_rect(dst, min, max, color, thickness);
+}
+
+pub fn draw_line(
+    dst: &mut Bitmap,
+    info: &CanvasInfo,
+    start: impl Into<WorldPos>,
+    end: impl Into<WorldPos>,
+    color: Color,
+    thickness: i32,
+) {
+    let CanvasPos(start) = info.world_to_canvas(start.into());
+    let CanvasPos(end) = info.world_to_canvas(end.into());
+    super::draw_line(dst, start, end, color, thickness);
+}
@@ -0,0 +1,52 @@
+/* Helpers shared by tests of different modules */
+
+use std::path::Path;
+use crate::{
+    render::Color,
+    file::BinaryWriter,
+};
+
+/// xorshift, so that failures are reproducible without extra dependencies
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// In `min..max`
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        let len = (i64::from(max) - i64::from(min)) as u64;
+        (i64::from(min) + (self.next() % len) as i64) as i32
+    }
+
+    /// In `0..max`
+    pub fn below(&mut self, max: i32) -> i32 {
+        self.range(0, max)
+    }
+}
+
+/// Writes a 32-bit BMP of `width` x `height` white pixels that `Bitmap::load` can read
+pub fn write_bmp(path: &Path, width: i32, height: i32) {
+    const HEADER_SIZE: u32 = 14 + 124;
+    let image_size = (width * height * 4) as u32;
+    let mut writer = BinaryWriter::new();
+    writer.write_bytes(b"BM");
+    writer.write_u32(HEADER_SIZE + image_size);
+    writer.write_u32(0);
+    writer.write_u32(HEADER_SIZE);
+    writer.write_u32(124);
+    writer.write_u32(width as u32);
+    writer.write_u32(height as u32);
+    writer.write_bytes(&[1, 0, 32, 0]);
+    writer.write_u32(3);
+    writer.write_u32(image_size);
+    writer.write_bytes(&[0; 124 - 24]);
+    for _ in 0..width * height {
+        writer.write_u32(u32::from(Color::WHITE));
+    }
+    crate::file::write_all(path, &writer.into_bytes()).unwrap();
+}
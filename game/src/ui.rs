@@ -0,0 +1,437 @@
+/* Immediate mode UI for menus and editor panels. Widgets are function calls that draw themselves
+   and return what the user did with them this frame, there are no widget objects to keep in sync
+   with the game state:
+
+       let mut ui = Ui::new(&mut data.ui, canvas, &data.font_bmp, input, mouse_canvas);
+       ui.begin_window("Options", V2::new(10, 10), V2::new(0.0, 0.0));
+       ui.checkbox("Show grid", &mut data.show_grid);
+       if ui.button("Close") {
+           ...
+       }
+       ui.end_window();
+       ui.finish();
+
+   Widgets go top to bottom in the current window, `same_line` puts the next one to the right of
+   the previous one. Widgets are told apart by their window and label, the part of a label after
+   `##` isn't shown, so "less##hp" and "less##speed" are two buttons that both say "less".
+
+   What has to outlive a frame (the pressed widget, the focused text field, list scrolling,
+   window sizes) is kept in `UiState`.
+*/
+
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    mem,
+};
+use platform::input::{Input, KBKey, MouseKey};
+use crate::{
+    render::{self, Bitmap, Color, text::{FontBitmaps, TextStyle}},
+    geom::{vector::prelude::*, aabb::AABB},
+};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+struct Id(u64);
+
+#[derive(Default)]
+pub struct UiState {
+    /// Widget the left button was pressed on, only it reacts until the button is released
+    active: Option<Id>,
+    /// Text field that gets typed characters
+    focused: Option<Id>,
+    /// Window sizes of the last frame, a window is drawn before its size is known
+    window_sizes: HashMap<Id, V2i>,
+    /// Where windows were in the last frame
+    window_rects: Vec<AABB<i32>>,
+    /// First visible row and selected item of lists
+    lists: HashMap<Id, (usize, usize)>,
+}
+
+impl UiState {
+    /// The mouse at `p` is over a window or a widget is held, so the game should ignore the mouse
+    pub fn wants_mouse(&self, p: V2i) -> bool {
+        self.active.is_some() || self.window_rects.iter().any(|rect| rect.contains(p))
+    }
+}
+
+struct Window {
+    id: Id,
+    min: V2i,
+    /// Bottom right corner of the content so far
+    max: V2i,
+    /// Where the next widget goes
+    cursor: V2i,
+    /// Previous widget, for `same_line`
+    last: AABB<i32>,
+}
+
+pub struct Ui<'a> {
+    state: &'a mut UiState,
+    canvas: &'a mut Bitmap,
+    font: &'a FontBitmaps,
+    input: &'a Input,
+    /// In canvas pixels
+    mouse: V2i,
+    padding: i32,
+    window: Option<Window>,
+    window_rects: Vec<AABB<i32>>,
+    same_line: bool,
+    focus_kept: bool,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(state: &'a mut UiState, canvas: &'a mut Bitmap, font: &'a FontBitmaps, input: &'a Input, mouse: V2i) -> Self {
+        Self {
+            state,
+            canvas,
+            font,
+            input,
+            mouse,
+            padding: (font.height() / 4).max(1),
+            window: None,
+            window_rects: Vec::new(),
+            same_line: false,
+            focus_kept: false,
+        }
+    }
+
+    /// Ends the frame, windows of it are what `UiState::wants_mouse` looks at
+    pub fn finish(self) {
+        debug_assert!(self.window.is_none(), "Ui::finish: a window isn't ended");
+        if !self.input.mouse[MouseKey::LB].is_down() {
+            self.state.active = None;
+        }
+        // a text field that isn't drawn anymore loses focus
+        if !self.focus_kept {
+            self.state.focused = None;
+        }
+        self.state.window_rects = self.window_rects;
+    }
+
+    /// Height of buttons and a line of text
+    pub fn line_height(&self) -> i32 {
+        self.font.height() + self.padding * 2
+    }
+
+    fn id(&self, label: &str) -> Id {
+        let mut hasher = DefaultHasher::new();
+        self.window.as_ref().map(|window| window.id).hash(&mut hasher);
+        label.hash(&mut hasher);
+        Id(hasher.finish())
+    }
+
+    /// Starts a window with `pivot` of it at `pos`: (0, 0) puts its top left corner there,
+    /// (0.5, 0.5) its center, (1, 1) its bottom right corner
+    pub fn begin_window(&mut self, name: &str, pos: V2i, pivot: V2f) {
+        debug_assert!(self.window.is_none(), "Ui::begin_window: the previous window isn't ended");
+        let id = self.id(name);
+        let size = self.state.window_sizes.get(&id).copied().unwrap_or_else(|| V2::diag(0));
+        let min = pos - V2::new((size.x as f32 * pivot.x) as i32, (size.y as f32 * pivot.y) as i32);
+        if size.x > 0 && size.y > 0 {
+            render::fill_rect(self.canvas, min, min + size, Color::BLACK);
+            render::draw_rect(self.canvas, min, min + size, Color::WHITE, 1);
+        }
+        let inner = min + V2::diag(self.padding);
+        self.window = Some(Window { id, min, max: inner, cursor: inner, last: AABB { min: inner, max: inner } });
+    }
+
+    /// Ends the current window, returns where it is
+    pub fn end_window(&mut self) -> AABB<i32> {
+        let window = self.window.take().expect("Ui::end_window: no window");
+        let rect = AABB { min: window.min, max: window.max + V2::diag(self.padding) };
+        self.state.window_sizes.insert(window.id, rect.max - rect.min);
+        self.window_rects.push(rect);
+        rect
+    }
+
+    /// Puts the next widget to the right of the previous one
+    pub fn same_line(&mut self) {
+        self.same_line = true;
+    }
+
+    /// Empty space of `height` canvas pixels
+    pub fn space(&mut self, height: i32) {
+        self.place(V2::new(0, height));
+    }
+
+    /// Rectangle of the next widget of `size` in the current window
+    fn place(&mut self, size: V2i) -> AABB<i32> {
+        let padding = self.padding;
+        let same_line = mem::take(&mut self.same_line);
+        let window = self.window.as_mut().expect("Ui: widgets must be inside of a window");
+        let min = if same_line {
+            V2::new(window.last.max.x + padding, window.last.min.y)
+        } else {
+            window.cursor
+        };
+        let rect = AABB { min, max: min + size };
+        window.last = rect;
+        window.cursor = V2::new(window.min.x + padding, window.cursor.y.max(rect.max.y + padding));
+        window.max = V2::new(window.max.x.max(rect.max.x), window.max.y.max(rect.max.y));
+        rect
+    }
+
+    /// Whether the mouse is over `rect`, and whether `id` was clicked:
+    /// the left button was pressed on it and is released over it
+    fn interact(&mut self, id: Id, rect: AABB<i32>) -> (bool, bool) {
+        let hovered = rect.contains(self.mouse);
+        let button = self.input.mouse[MouseKey::LB];
+        if hovered && button.pressed() {
+            self.state.active = Some(id);
+        }
+        let clicked = hovered && button.released() && self.state.active == Some(id);
+        (hovered, clicked)
+    }
+
+    fn draw_text(&self, pos: V2i, text: &str) {
+        self.font.draw_string(self.canvas, pos, text);
+    }
+
+    /// Lines of `text` are split at '\n'
+    pub fn label(&mut self, text: &str) {
+        let layout = self.font.layout(text, None);
+        let size = layout.bounds.max + V2::new(0, self.padding * 2);
+        let rect = self.place(V2::new(size.x, size.y.max(self.line_height())));
+        self.font.draw_layout(self.canvas, rect.min + V2::new(0, self.padding), &layout, TextStyle::default());
+    }
+
+    /// Returns true when clicked
+    pub fn button(&mut self, label: &str) -> bool {
+        let (id, text) = (self.id(label), display(label));
+        let rect = self.place(V2::new(self.font.width(text) + self.padding * 2, self.line_height()));
+        let (hovered, clicked) = self.interact(id, rect);
+        if hovered && self.state.active == Some(id) {
+            render::fill_rect(self.canvas, rect.min, rect.max, Color::GREY);
+        }
+        let color = if hovered { Color::WHITE } else { Color::YELLOW };
+        render::draw_rect(self.canvas, rect.min, rect.max, color, 1);
+        self.draw_text(rect.min + V2::diag(self.padding), text);
+        clicked
+    }
+
+    /// Returns true when `value` changed
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let (id, text) = (self.id(label), display(label));
+        let box_size = self.font.height();
+        let width = box_size + self.padding + self.font.width(text);
+        let rect = self.place(V2::new(width, self.line_height()));
+        let (hovered, clicked) = self.interact(id, rect);
+        if clicked {
+            *value = !*value;
+        }
+
+        let box_min = rect.min + V2::new(0, self.padding);
+        let color = if hovered { Color::WHITE } else { Color::YELLOW };
+        render::draw_rect(self.canvas, box_min, box_min + V2::diag(box_size), color, 1);
+        if *value {
+            let inset = V2::diag(self.padding);
+            render::fill_rect(self.canvas, box_min + inset, box_min + V2::diag(box_size) - inset, Color::WHITE);
+        }
+        self.draw_text(V2::new(box_min.x + box_size + self.padding, box_min.y), text);
+        clicked
+    }
+
+    /// Dragging along it sets `value` from `min` to `max`. Returns true when `value` changed
+    pub fn slider(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let (id, text) = (self.id(label), display(label));
+        let track_width = self.font.height() * 6;
+        let text = format!("{}: {:.2}", text, value);
+        let rect = self.place(V2::new(track_width + self.padding + self.font.width(&text), self.line_height()));
+        let track = AABB { min: rect.min, max: rect.min + V2::new(track_width, self.line_height()) };
+        let (hovered, _) = self.interact(id, track);
+
+        let old = *value;
+        if self.state.active == Some(id) && self.input.mouse[MouseKey::LB].is_down() {
+            *value = slider_value(track, self.mouse.x, min, max);
+        }
+
+        let color = if hovered || self.state.active == Some(id) { Color::WHITE } else { Color::YELLOW };
+        let middle = track.min.y + track.height() / 2;
+        render::fill_rect(self.canvas, V2::new(track.min.x, middle), V2::new(track.max.x, middle + 1), Color::GREY);
+        let t = if max > min { ((*value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+        let knob_x = track.min.x + ((track_width - self.padding * 2) as f32 * t) as i32;
+        render::fill_rect(self.canvas, V2::new(knob_x, track.min.y), V2::new(knob_x + self.padding * 2, track.max.y), color);
+        self.draw_text(V2::new(track.max.x + self.padding, rect.min.y + self.padding), &text);
+        (*value - old).abs() > f32::EPSILON
+    }
+
+    /// One line of text `width` canvas pixels wide, clicking it focuses it.
+    /// Returns true when Enter is pressed while it's focused
+    pub fn text_field(&mut self, label: &str, text: &mut String, width: i32) -> bool {
+        let (id, shown_label) = (self.id(label), display(label));
+        let label_width = if shown_label.is_empty() { 0 } else { self.padding + self.font.width(shown_label) };
+        let rect = self.place(V2::new(width + label_width, self.line_height()));
+        let field = AABB { min: rect.min, max: rect.min + V2::new(width, self.line_height()) };
+        let (hovered, clicked) = self.interact(id, field);
+        if clicked {
+            self.state.focused = Some(id);
+        } else if !hovered && self.input.mouse[MouseKey::LB].pressed() && self.state.focused == Some(id) {
+            self.state.focused = None;
+        }
+
+        let focused = self.state.focused == Some(id);
+        let input = self.input;
+        let pressed = |key: KBKey| input.keyboard[key].pressed();
+        let mut entered = false;
+        if focused {
+            self.focus_kept = true;
+            text.extend(typed(input));
+            if pressed(KBKey::Backspace) {
+                text.pop();
+            }
+            if pressed(KBKey::Enter) {
+                entered = true;
+            }
+            if pressed(KBKey::Escape) {
+                self.state.focused = None;
+            }
+        }
+
+        let color = if focused || hovered { Color::WHITE } else { Color::YELLOW };
+        render::draw_rect(self.canvas, field.min, field.max, color, 1);
+        self.canvas.push_clip(field);
+        // the end of a long text stays visible
+        let text_width = self.font.width(text);
+        let x = field.min.x + self.padding - (text_width - (width - self.padding * 3)).max(0);
+        self.draw_text(V2::new(x, field.min.y + self.padding), text);
+        if focused {
+            let cursor = V2::new(x + text_width, field.min.y + self.padding);
+            render::fill_rect(self.canvas, cursor, cursor + V2::new(1, self.font.height()), Color::WHITE);
+        }
+        self.canvas.pop_clip();
+        if !shown_label.is_empty() {
+            self.draw_text(V2::new(field.max.x + self.padding, field.min.y + self.padding), shown_label);
+        }
+        entered
+    }
+
+    /// Typed characters go to the text field with `label` of the current window
+    pub fn focus(&mut self, label: &str) {
+        self.state.focused = Some(self.id(label));
+    }
+
+    /// `rows` of `items` at a time, scrolled to keep `selected` visible when it changes.
+    /// Clicking the bar on the right scrolls by a page. Returns true when an item is clicked,
+    /// it's `selected` then
+    pub fn list(&mut self, label: &str, items: &[String], selected: &mut usize, rows: usize) -> bool {
+        let id = self.id(label);
+        let rows = rows.max(1);
+        let line = self.line_height();
+        let bar_width = self.padding * 2 + 1;
+        let width = items.iter()
+            .map(|item| self.font.width(item))
+            .max()
+            .unwrap_or(0)
+            .max(self.font.height() * 8) + self.padding * 2;
+        let rect = self.place(V2::new(width + bar_width, line * rows as i32));
+        let area = AABB { min: rect.min, max: V2::new(rect.max.x - bar_width, rect.max.y) };
+        let bar = AABB { min: V2::new(area.max.x, rect.min.y), max: rect.max };
+
+        let (first, last_selected) = self.state.lists.get(&id).copied().unwrap_or((0, usize::MAX));
+        let mut first = if *selected == last_selected {
+            first.min(items.len().saturating_sub(rows))
+        } else {
+            scroll_to_show(first, *selected, rows, items.len())
+        };
+
+        let (_, clicked) = self.interact(id, area);
+        let clicked_item = Some(first + ((self.mouse.y - area.min.y) / line) as usize)
+            .filter(|&i| clicked && i < items.len());
+        if let Some(i) = clicked_item {
+            *selected = i;
+        }
+        if bar.contains(self.mouse) && self.input.mouse[MouseKey::LB].pressed() {
+            let thumb_middle = bar.min.y + bar.height() * (first * 2 + rows) as i32 / (items.len().max(rows) * 2) as i32;
+            first = if self.mouse.y < thumb_middle {
+                first.saturating_sub(rows)
+            } else {
+                (first + rows).min(items.len().saturating_sub(rows))
+            };
+        }
+        self.state.lists.insert(id, (first, *selected));
+
+        render::draw_rect(self.canvas, rect.min, rect.max, Color::WHITE, 1);
+        self.canvas.push_clip(area);
+        for (row, item) in items.iter().enumerate().skip(first).take(rows) {
+            let min = V2::new(area.min.x, area.min.y + line * (row - first) as i32);
+            if row == *selected {
+                render::draw_rect(self.canvas, min, V2::new(area.max.x, min.y + line), Color::YELLOW, 1);
+            }
+            self.draw_text(min + V2::diag(self.padding), item);
+        }
+        self.canvas.pop_clip();
+
+        render::fill_rect(self.canvas, bar.min, bar.max, Color::GREY);
+        if items.len() > rows {
+            let top = bar.min.y + bar.height() * first as i32 / items.len() as i32;
+            let bottom = bar.min.y + bar.height() * (first + rows) as i32 / items.len() as i32;
+            render::fill_rect(self.canvas, V2::new(bar.min.x, top), V2::new(bar.max.x, bottom), Color::WHITE);
+        }
+        clicked_item.is_some()
+    }
+
+    /// `text` in a box next to `pos`, on whichever side keeps it inside of the canvas.
+    /// It isn't a window, the mouse goes through it
+    pub fn tooltip(&mut self, text: &str, pos: V2i) {
+        let layout = self.font.layout(text, Some(self.canvas.width() / 2));
+        let size = layout.bounds.max + V2::diag(self.padding * 2);
+        let offset = V2::diag(10);
+        let mut min = pos + offset;
+        if min.x + size.x > self.canvas.width() {
+            min.x = pos.x - size.x - offset.x;
+        }
+        if min.y + size.y > self.canvas.height() {
+            min.y = pos.y - size.y - offset.y;
+        }
+        render::fill_rect(self.canvas, min, min + size, Color::BLACK);
+        render::draw_rect(self.canvas, min, min + size, Color::WHITE, 1);
+        self.font.draw_layout(self.canvas, min + V2::diag(self.padding), &layout, TextStyle::default());
+    }
+}
+
+/// Shown part of a label, without the `##` suffix
+fn display(label: &str) -> &str {
+    label.find("##").map_or(label, |i| &label[..i])
+}
+
+/// First visible row of a list so that `selected` is visible, moving as little as possible from `first`
+fn scroll_to_show(first: usize, selected: usize, rows: usize, count: usize) -> usize {
+    let first = if selected < first {
+        selected
+    } else if selected >= first + rows {
+        selected + 1 - rows
+    } else {
+        first
+    };
+    first.min(count.saturating_sub(rows))
+}
+
+/// Value of a slider from `min` to `max` along `track` with the mouse at `x`
+fn slider_value(track: AABB<i32>, x: i32, min: f32, max: f32) -> f32 {
+    let t = (x - track.min.x) as f32 / (track.width() - 1).max(1) as f32;
+    min + (max - min) * t.clamp(0.0, 1.0)
+}
+
+/// Characters typed this frame, keys that don't type one are left out
+fn typed(input: &Input) -> Vec<char> {
+    let shift = input.keyboard[KBKey::Shift].is_down();
+    KBKey::variants()
+        .filter(|&&key| input.keyboard[key].pressed())
+        .filter_map(|&key| {
+            let code = key as u8;
+            match key {
+                KBKey::Space => Some(' '),
+                KBKey::Minus => Some(if shift { '_' } else { '-' }),
+                KBKey::Period => Some('.'),
+                KBKey::Comma => Some(','),
+                _ if code.is_ascii_digit() => Some(code as char),
+                _ if code.is_ascii_uppercase() && shift => Some(code as char),
+                _ if code.is_ascii_uppercase() => Some(code.to_ascii_lowercase() as char),
+                _ => None,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+use super::*;
+
+#[test]
+fn display_hides_id_suffix() {
+    assert_eq!(display("less##hp"), "less");
+    assert_eq!(display("##name"), "");
+    assert_eq!(display("Open"), "Open");
+}
+
+#[test]
+fn scroll_keeps_selected_visible() {
+    assert_eq!(scroll_to_show(0, 2, 5, 20), 0);
+    assert_eq!(scroll_to_show(0, 7, 5, 20), 3);
+    assert_eq!(scroll_to_show(10, 4, 5, 20), 4);
+    assert_eq!(scroll_to_show(18, 19, 5, 20), 15);
+    assert_eq!(scroll_to_show(3, 1, 5, 3), 0);
+}
+
+#[test]
+fn slider_value_is_clamped() {
+    let track = AABB { min: V2::new(10, 0), max: V2::new(21, 4) };
+    assert!((slider_value(track, 10, 0.0, 1.0)).abs() < 1e-6);
+    assert!((slider_value(track, 15, 0.0, 1.0) - 0.5).abs() < 1e-6);
+    assert!((slider_value(track, 40, -2.0, 2.0) - 2.0).abs() < 1e-6);
+    assert!((slider_value(track, 0, -2.0, 2.0) + 2.0).abs() < 1e-6);
+}
+
+#[test]
+fn mouse_over_windows_or_held_widget() {
+    let mut state = UiState::default();
+    state.window_rects.push(AABB { min: V2::new(0, 0), max: V2::new(10, 10) });
+    assert!(state.wants_mouse(V2::new(5, 5)));
+    assert!(!state.wants_mouse(V2::new(20, 5)));
+    state.active = Some(Id(1));
+    assert!(state.wants_mouse(V2::new(20, 5)));
+}
@@ -125,6 +125,11 @@ impl Window {
         self.handle == unsafe { winuser::GetActiveWindow() }
     }
 
+    /// Ends the message loop, `dispatch_messages` returns `false` after this
+    pub fn close(&self) {
+        unsafe { winuser::PostQuitMessage(0) };
+    }
+
     pub fn toggle_fullscreen(&mut self) {
         use winapi::um::winuser::{
             MonitorFromWindow, GetMonitorInfoA,
@@ -274,7 +279,8 @@ impl Window {
         //  WM_INPUTLANGCHANGE
         match message {
             winuser::WM_CLOSE => winuser::PostQuitMessage(0),
-            winuser::WM_ACTIVATEAPP => (), //TODO: pause the game and something else maybe
+            // the game checks `Window::is_active` and pauses itself
+            winuser::WM_ACTIVATEAPP => (),
             _ => result = winuser::DefWindowProcA(window_handle, message, w_param, l_param),
         }
 